@@ -0,0 +1,196 @@
+// SPDX-FileCopyrightText: 2023 Klarälvdalens Datakonsult AB, a KDAB Group company <info@kdab.com>
+// SPDX-FileContributor: Andrew Hayzen <andrew.hayzen@kdab.com>
+//
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+
+use crate::{ClangFormat, ClangFormatError};
+
+/// The results of a batch which have not been taken yet and the waker of the task waiting for them
+#[derive(Default)]
+struct State {
+    results: VecDeque<(usize, Result<String, ClangFormatError>)>,
+    finished: bool,
+    waker: Option<Waker>,
+}
+
+impl State {
+    fn wake(&mut self) {
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// Marks the batch as finished when the background thread ends, even if it panicked
+struct FinishGuard(Arc<Mutex<State>>);
+
+impl Drop for FinishGuard {
+    fn drop(&mut self) {
+        let mut state = self.0.lock().unwrap_or_else(|err| err.into_inner());
+        state.finished = true;
+        state.wake();
+    }
+}
+
+/// A stream of the results of `ClangFormat::format_batch_stream` in the order they complete
+///
+/// Each item is the index of the input and its result. The batch is formatted on a background
+/// thread, so the stream can be polled from any async runtime, such as `smol`, `async-std`,
+/// or `tokio`. `FormatBatchStream::poll_next` has the signature of `futures::Stream::poll_next`,
+/// so the stream can be wrapped to implement that trait.
+#[must_use = "streams do nothing unless polled"]
+pub struct FormatBatchStream {
+    state: Arc<Mutex<State>>,
+}
+
+impl FormatBatchStream {
+    /// Start formatting the inputs on a background thread
+    pub(crate) fn spawn(format: ClangFormat, inputs: Vec<String>) -> Self {
+        let state = Arc::new(Mutex::new(State::default()));
+        let guard = FinishGuard(state.clone());
+        thread::spawn(move || {
+            format.format_batch_each(&inputs, |index, result| {
+                let mut state = guard.0.lock().expect("batch state poisoned");
+                state.results.push_back((index, result));
+                state.wake();
+            });
+            drop(guard);
+        });
+        Self { state }
+    }
+
+    /// Poll for the next result, which is `None` once every result has been returned
+    pub fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<(usize, Result<String, ClangFormatError>)>> {
+        let mut state = self.state.lock().expect("batch state poisoned");
+        match state.results.pop_front() {
+            Some(result) => Poll::Ready(Some(result)),
+            None if state.finished => Poll::Ready(None),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+
+    /// Wait for the next result, which is `None` once every result has been returned
+    pub async fn next(&mut self) -> Option<(usize, Result<String, ClangFormatError>)> {
+        std::future::poll_fn(|cx| Pin::new(&mut *self).poll_next(cx)).await
+    }
+}
+
+impl fmt::Debug for FormatBatchStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FormatBatchStream").finish_non_exhaustive()
+    }
+}
+
+/// A future which resolves to the results of `ClangFormat::format_batch_async`
+///
+/// This collects a `FormatBatchStream` into the results in the same order as the inputs.
+#[must_use = "futures do nothing unless polled"]
+pub struct FormatBatchFuture {
+    stream: FormatBatchStream,
+    results: Vec<Option<Result<String, ClangFormatError>>>,
+}
+
+impl FormatBatchFuture {
+    /// Start formatting the inputs on a background thread
+    pub(crate) fn spawn(format: ClangFormat, inputs: Vec<String>) -> Self {
+        let results = inputs.iter().map(|_| None).collect();
+        Self {
+            stream: FormatBatchStream::spawn(format, inputs),
+            results,
+        }
+    }
+}
+
+impl Future for FormatBatchFuture {
+    type Output = Vec<Result<String, ClangFormatError>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        loop {
+            match Pin::new(&mut self.stream).poll_next(cx) {
+                Poll::Ready(Some((index, result))) => self.results[index] = Some(result),
+                Poll::Ready(None) => {
+                    let results = std::mem::take(&mut self.results);
+                    return Poll::Ready(
+                        results
+                            .into_iter()
+                            .map(|result| result.expect("batch worker panicked"))
+                            .collect(),
+                    );
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+impl fmt::Debug for FormatBatchFuture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FormatBatchFuture").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{block_on, UppercaseRunner};
+    use crate::FormatRunner;
+    use std::process::{Command, ExitStatus, Output};
+    use std::time::Duration;
+
+    /// Echoes the input, sleeping for longer when the input starts with `slow`
+    #[derive(Debug)]
+    struct SlowRunner;
+
+    impl FormatRunner for SlowRunner {
+        fn run(&self, _command: &mut Command, input: &[u8]) -> Result<Output, ClangFormatError> {
+            if input.starts_with(b"slow") {
+                thread::sleep(Duration::from_millis(200));
+            }
+            Ok(Output {
+                status: ExitStatus::default(),
+                stdout: input.to_vec(),
+                stderr: vec![],
+            })
+        }
+    }
+
+    #[test]
+    fn format_batch_async() {
+        let future = ClangFormat::new()
+            .runner(UppercaseRunner)
+            .max_parallelism(2)
+            .format_batch_async(["int a;\n", "int b;\n", "int c;\n"]);
+        let outputs: Vec<String> = block_on(future).into_iter().map(Result::unwrap).collect();
+        assert_eq!(outputs, ["INT A;\n", "INT B;\n", "INT C;\n"]);
+
+        let future = ClangFormat::new().format_batch_async(Vec::<String>::new());
+        assert!(block_on(future).is_empty());
+    }
+
+    #[test]
+    fn format_batch_stream_in_completion_order() {
+        let mut stream = ClangFormat::new()
+            .runner(SlowRunner)
+            .max_parallelism(2)
+            .format_batch_stream(["slow a;\n", "int b;\n"]);
+        let (index, output) = block_on(stream.next()).unwrap();
+        assert_eq!((index, output.unwrap()), (1, "int b;\n".to_string()));
+        let (index, output) = block_on(stream.next()).unwrap();
+        assert_eq!((index, output.unwrap()), (0, "slow a;\n".to_string()));
+        assert!(block_on(stream.next()).is_none());
+    }
+}
@@ -0,0 +1,4382 @@
+// SPDX-FileCopyrightText: 2023 Klarälvdalens Datakonsult AB, a KDAB Group company <info@kdab.com>
+// SPDX-FileContributor: Andrew Hayzen <andrew.hayzen@kdab.com>
+//
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use std::collections::HashMap;
+use std::env;
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Output, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+use std::time::Instant;
+
+use crate::cancel::{self, CancelHandle};
+use crate::hash::Fnv1a;
+use crate::runner::{self, spawn, FormatRunner, ProcessOptions};
+#[cfg(feature = "auto_encoding")]
+use crate::DetectedEncoding;
+#[cfg(feature = "resource_usage")]
+use crate::ResourceUsage;
+#[cfg(feature = "encoding")]
+use crate::TextEncoding;
+use crate::{
+    cursor, diagnostic, diff, guards, replacements, structure, style, ClangFormatError,
+    ClangFormatStyle, ClangFormatVersion, CommandLine, Diagnostic, FileStyleResolution,
+    FormatOutcome, FormatOutputWithReplacements, FormatOutputWithWarnings, FormatStats,
+    FormattedRegion, Hunk, Language, QualifierAlignment, Replacement, StyleConfig,
+};
+#[cfg(feature = "async")]
+use crate::{FormatBatchFuture, FormatBatchStream, FormatFuture};
+#[cfg(feature = "debug_log")]
+use crate::{LogStream, ProcessLog};
+
+/// The style registered with `ClangFormat::set_default_style`
+static DEFAULT_STYLE: OnceLock<ClangFormatStyle> = OnceLock::new();
+
+/// Whether each clang-format command line supports `--cursor`, so that it is only probed once
+static CURSOR_SUPPORT: OnceLock<Mutex<HashMap<Vec<OsString>, bool>>> = OnceLock::new();
+
+/// Distinguishes the temporary files of concurrent calls, such as to `ClangFormat::format_filelist`
+static TEMP_FILE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Builder to configure and execute clang-format with additional options
+///
+/// Methods taking a path accept any `impl AsRef<Path>`, so `String`, `PathBuf`,
+/// and UTF-8 path types such as `camino::Utf8PathBuf` work without conversion.
+///
+/// # Example
+///
+/// ```
+/// # use clang_format::{ClangFormat, ClangFormatStyle};
+/// # fn main() {
+/// let input = r#"
+///     struct Test {
+///
+///     };
+/// "#;
+/// let output = ClangFormat::new()
+///     .style(ClangFormatStyle::Mozilla)
+///     .format(input);
+/// assert!(output.is_ok());
+/// assert_eq!(output.unwrap(), "\nstruct Test\n{};\n");
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ClangFormat {
+    binary: Option<PathBuf>,
+    style: Option<ClangFormatStyle>,
+    /// Passed as `--style=file:<path>` instead of the style, as the path may not be UTF-8
+    style_file: Option<PathBuf>,
+    strict: bool,
+    current_dir: Option<PathBuf>,
+    temp_dir: Option<PathBuf>,
+    #[cfg(feature = "encoding")]
+    encoding: Option<&'static dyn TextEncoding>,
+    #[cfg(feature = "auto_encoding")]
+    auto_encoding: bool,
+    reject_empty_output: bool,
+    qualifier_alignment: Option<QualifierAlignment>,
+    verify_style_support: bool,
+    compat_version: Option<ClangFormatVersion>,
+    require_parseable_version: bool,
+    max_input_bytes: Option<usize>,
+    assume_filename: Option<PathBuf>,
+    locale: Option<String>,
+    fallback_style: Option<ClangFormatStyle>,
+    reject_diagnostics: bool,
+    no_error_categories: Vec<String>,
+    max_output_bytes: Option<usize>,
+    low_priority: bool,
+    write_chunk_size: Option<usize>,
+    normalize_crlf: bool,
+    match_input_final_newline: bool,
+    max_parallelism: Option<usize>,
+    search_paths: Vec<PathBuf>,
+    command_prefix: Vec<String>,
+    runner: Option<Arc<dyn FormatRunner>>,
+    cancel: Option<Arc<CancelHandle>>,
+    #[cfg(feature = "debug_log")]
+    log: Option<Arc<Mutex<ProcessLog>>>,
+}
+
+impl ClangFormat {
+    /// Create a new builder using the default style
+    ///
+    /// This is the style registered with `ClangFormat::set_default_style`,
+    /// otherwise `ClangFormatStyle::Default`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the style used when no style is given
+    ///
+    /// This is used by `clang_format` and by any `ClangFormat` without an explicit style,
+    /// which allows for a single canonical style, such as for generated code, without
+    /// passing the style to every call. Explicit styles still take precedence.
+    ///
+    /// The default style can only be registered once, if it has already been
+    /// registered the given style is returned as the error.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use clang_format::{clang_format, ClangFormat, ClangFormatStyle};
+    /// # fn main() {
+    /// assert!(ClangFormat::set_default_style(ClangFormatStyle::Mozilla).is_ok());
+    ///
+    /// let output = clang_format("struct Test {};\n");
+    /// assert!(output.is_ok());
+    /// assert_eq!(output.unwrap(), "struct Test\n{};\n");
+    /// # }
+    /// ```
+    pub fn set_default_style(style: ClangFormatStyle) -> Result<(), ClangFormatStyle> {
+        DEFAULT_STYLE.set(style)
+    }
+
+    /// Set the clang-format binary to execute
+    ///
+    /// By default the `CLANG_FORMAT_BINARY` environment variable is used if set,
+    /// otherwise `clang-format` is found from the `PATH`.
+    /// Paths which are not valid UTF-8 are supported.
+    pub fn binary(mut self, binary: impl AsRef<Path>) -> Self {
+        self.binary = Some(binary.as_ref().to_path_buf());
+        self
+    }
+
+    /// Search the given directories for the clang-format binary before the `PATH`
+    ///
+    /// When the binary is a name without a directory, such as the default `clang-format`
+    /// or `clang-format-16`, the first of these directories containing it is used to
+    /// construct an absolute path. Otherwise the binary is found from the `PATH` as usual.
+    /// This allows for explicit discovery without changing the environment of the process.
+    pub fn search_paths(mut self, paths: &[PathBuf]) -> Self {
+        self.search_paths = paths.to_vec();
+        self
+    }
+
+    /// Execute clang-format through a launcher, such as `bazel run //:clang-format --`
+    ///
+    /// The first token is executed with the remaining tokens, followed by the binary and its
+    /// arguments. The input and output are still piped through the launcher, which allows for
+    /// hermetic build environments or containers where clang-format is not on the `PATH`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use clang_format::ClangFormat;
+    /// # fn main() {
+    /// let command_line = ClangFormat::new()
+    ///     .command_prefix(&["docker".to_string(), "run".to_string(), "-i".to_string(), "clang".to_string()])
+    ///     .binary("clang-format")
+    ///     .command_line()
+    ///     .unwrap();
+    /// assert_eq!(command_line.binary, "docker");
+    /// assert_eq!(command_line.args[..4], ["run", "-i", "clang", "clang-format"]);
+    /// # }
+    /// ```
+    pub fn command_prefix(mut self, prefix: &[String]) -> Self {
+        self.command_prefix = prefix.to_vec();
+        self
+    }
+
+    /// Set the style to pass to clang-format
+    pub fn style(mut self, style: impl Into<ClangFormatStyle>) -> Self {
+        self.style = Some(style.into());
+        self
+    }
+
+    /// Set the style to the layers merged from left to right, with later layers winning
+    ///
+    /// clang-format only accepts a single `--style`, so the layers are combined with
+    /// `ClangFormatStyle::merge` into a single inline style before formatting.
+    ///
+    /// - A built-in style sets `BasedOnStyle: <name>`, replacing the base of any earlier layer
+    ///   but keeping the keys of earlier `Custom` layers. So a built-in layer after a `Custom`
+    ///   layer only changes the base, it does not reset the keys which were already set.
+    /// - A `Custom` style in the inline form sets each of its keys, replacing the value of the key
+    ///   from any earlier layer, including `BasedOnStyle`.
+    /// - `Default`, `File`, and `Unspecified` have no keys, so they do not change the style.
+    ///
+    /// A single layer is used as is, and when there are no layers the style is not changed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use clang_format::{ClangFormat, ClangFormatStyle};
+    /// # fn main() {
+    /// let command_line = ClangFormat::new()
+    ///     .with_style_layers(&[
+    ///         ClangFormatStyle::Google,
+    ///         ClangFormatStyle::Custom("{ IndentWidth: 4, ColumnLimit: 100 }".to_string()),
+    ///         ClangFormatStyle::Mozilla,
+    ///         ClangFormatStyle::Custom("{ ColumnLimit: 120 }".to_string()),
+    ///     ])
+    ///     .command_line()
+    ///     .unwrap();
+    /// assert_eq!(
+    ///     command_line.style(),
+    ///     Some("{ BasedOnStyle: Mozilla, IndentWidth: 4, ColumnLimit: 120 }")
+    /// );
+    /// # }
+    /// ```
+    pub fn with_style_layers(mut self, layers: &[ClangFormatStyle]) -> Self {
+        if let Some(style) = layers.iter().cloned().reduce(ClangFormatStyle::merge) {
+            self.style = Some(style);
+        }
+        self
+    }
+
+    /// Reject styles containing keys that clang-format does not know
+    ///
+    /// When enabled the diagnostics of clang-format are inspected, and if any key of the style
+    /// was reported as unknown `ClangFormatError::UnknownStyleKey` is returned.
+    /// This catches misspelled keys in a `ClangFormatStyle::Custom` style.
+    ///
+    /// Other errors in the style, such as invalid values, are returned as
+    /// `ClangFormatError::InvalidStyle` with the line and column in the style.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Treat any diagnostic printed by clang-format as an error
+    ///
+    /// When enabled the diagnostics of clang-format are captured, and if clang-format
+    /// succeeds but printed anything `ClangFormatError::Diagnostics` is returned.
+    /// This is stricter than `ClangFormat::strict` and also catches warnings and
+    /// deprecation notices which do not change the exit status.
+    pub fn reject_diagnostics(mut self, reject: bool) -> Self {
+        self.reject_diagnostics = reject;
+        self
+    }
+
+    /// Do not treat diagnostics of the given category as errors
+    ///
+    /// This is passed to `--Wno-error=<category>` and can be called multiple times for
+    /// multiple categories. Currently clang-format only supports the `unknown` category,
+    /// which reports unknown keys in a `.clang-format` file as warnings rather than errors,
+    /// so that configuration written for a newer clang-format can still be used.
+    ///
+    /// Since clang-format 13
+    pub fn no_error_category(mut self, category: impl Into<String>) -> Self {
+        self.no_error_categories.push(category.into());
+        self
+    }
+
+    /// Convert `\r\n` line endings in the output to `\n`
+    ///
+    /// On Windows the output of clang-format may contain `\r\n`, this allows for comparing the
+    /// output in the same way on every platform. This is a post-processing step applied to
+    /// the collected output, it does not change what clang-format produces and is not applied
+    /// by `ClangFormat::format_streaming` or `ClangFormat::format_file_streamed`. It is also not
+    /// applied to `ClangFormat::replacements`, whose offsets refer to the unchanged output, and
+    /// for `ClangFormat::format_with_cursor` the cursor is moved to match.
+    pub fn normalize_crlf(mut self, normalize: bool) -> Self {
+        self.normalize_crlf = normalize;
+        self
+    }
+
+    /// Ensure the output ends with a newline only if the input does
+    ///
+    /// Depending on the version and configuration, clang-format may add a final newline to input
+    /// without one, which causes a no-op diff in version control. When enabled a final newline is
+    /// removed from the output if the input has none, and the line ending of the input is added
+    /// to non-empty output if the input has one. Like `ClangFormat::normalize_crlf` this is applied
+    /// to the collected output, not by `ClangFormat::format_streaming`,
+    /// `ClangFormat::format_file_streamed` or `ClangFormat::replacements`.
+    pub fn match_input_final_newline(mut self, match_input: bool) -> Self {
+        self.match_input_final_newline = match_input;
+        self
+    }
+
+    /// Set the directory where temporary files are written
+    ///
+    /// This allows for directories which are not writable, or are on a slow volume, to be
+    /// avoided. By default `std::env::temp_dir` is used, except for the following.
+    ///
+    /// The output of `ClangFormat::format_file_streamed` is by default written next to the file
+    /// so that it can be atomically renamed over it. If the temporary directory is on another
+    /// filesystem than the file, the output is copied over the file instead, which is not atomic.
+    pub fn temp_dir(mut self, dir: impl AsRef<Path>) -> Self {
+        self.temp_dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Set the working directory of the clang-format process
+    ///
+    /// With `ClangFormatStyle::File` clang-format searches for a `.clang-format` file
+    /// starting from this directory, which allows for formatting input that does not
+    /// exist on disk yet.
+    pub fn current_dir(mut self, dir: impl AsRef<Path>) -> Self {
+        self.current_dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Set the encoding of the input given to `ClangFormat::format_bytes`
+    ///
+    /// The input is decoded to UTF-8 before formatting and the output is encoded back
+    /// afterwards, as clang-format works best with UTF-8.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use clang_format::{ClangFormat, Latin1};
+    /// # fn main() {
+    /// let output = ClangFormat::new()
+    ///     .encoding(&Latin1)
+    ///     .format_bytes(b"// caf\xe9\nint  a;\n");
+    /// assert!(output.is_ok());
+    /// assert_eq!(output.unwrap(), b"// caf\xe9\nint a;\n");
+    /// # }
+    /// ```
+    #[cfg(feature = "encoding")]
+    pub fn encoding(mut self, encoding: &'static dyn TextEncoding) -> Self {
+        self.encoding = Some(encoding);
+        self
+    }
+
+    /// Detect the encoding of the input and restore it in the output
+    ///
+    /// When enabled and no encoding is set with `ClangFormat::encoding`, the encoding of
+    /// the input to `ClangFormat::format_bytes` and `ClangFormat::format_file_streamed` is
+    /// detected with `DetectedEncoding::detect`. The input is decoded to UTF-8 for clang-format
+    /// and the output is encoded back, including any byte order mark.
+    ///
+    /// Detection is a heuristic, see `DetectedEncoding::detect` for its limits. Files are read
+    /// into memory rather than streamed, as the whole input is needed for detection.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use clang_format::ClangFormat;
+    /// # fn main() {
+    /// let output = ClangFormat::new()
+    ///     .auto_encoding(true)
+    ///     .format_bytes(b"\xef\xbb\xbf// caf\xc3\xa9\nint  a;\n");
+    /// assert!(output.is_ok());
+    /// assert_eq!(output.unwrap(), b"\xef\xbb\xbf// caf\xc3\xa9\nint a;\n");
+    /// # }
+    /// ```
+    #[cfg(feature = "auto_encoding")]
+    pub fn auto_encoding(mut self, enabled: bool) -> Self {
+        self.auto_encoding = enabled;
+        self
+    }
+
+    /// The encoding given with `ClangFormat::encoding` or detected from the input
+    #[cfg(feature = "encoding")]
+    fn input_encoding(&self, _input: &[u8]) -> Option<&'static dyn TextEncoding> {
+        #[cfg(feature = "auto_encoding")]
+        if self.encoding.is_none() && self.auto_encoding {
+            return match DetectedEncoding::detect(_input) {
+                DetectedEncoding::Utf8 => None,
+                DetectedEncoding::Utf8Bom => Some(&DetectedEncoding::Utf8Bom),
+                DetectedEncoding::Utf16Le => Some(&DetectedEncoding::Utf16Le),
+                DetectedEncoding::Utf16Be => Some(&DetectedEncoding::Utf16Be),
+                DetectedEncoding::Latin1 => Some(&DetectedEncoding::Latin1),
+            };
+        }
+        self.encoding
+    }
+
+    /// Treat empty output for non-empty input as an error
+    ///
+    /// When enabled and clang-format succeeds but returns no output for input which
+    /// contains anything other than whitespace, `ClangFormatError::UnexpectedEmptyOutput`
+    /// is returned instead of an empty string. Empty input can still result in empty output.
+    pub fn reject_empty_output(mut self, reject: bool) -> Self {
+        self.reject_empty_output = reject;
+        self
+    }
+
+    /// Override how qualifiers such as `const` are arranged, regardless of the style
+    ///
+    /// Since clang-format 14
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use clang_format::{ClangFormat, QualifierAlignment};
+    /// # fn main() {
+    /// let output = ClangFormat::new()
+    ///     .qualifier_alignment(QualifierAlignment::Left)
+    ///     .format("int const a = 1;\n");
+    /// assert!(output.is_ok());
+    /// assert_eq!(output.unwrap(), "const int a = 1;\n");
+    /// # }
+    /// ```
+    pub fn qualifier_alignment(mut self, alignment: QualifierAlignment) -> Self {
+        self.qualifier_alignment = Some(alignment);
+        self
+    }
+
+    /// Check that the installed clang-format supports the style before formatting
+    ///
+    /// When enabled the version of clang-format is queried before formatting with styles that
+    /// need a newer clang-format, such as `ClangFormatStyle::GNU`, `ClangFormatStyle::Microsoft`,
+    /// or `ClangFormatStyle::inherit_parent_config`, and `ClangFormatError::UnsupportedStyle`
+    /// is returned if the version is too old.
+    /// If the version cannot be determined the check is skipped.
+    ///
+    /// This is opt-in as it spawns clang-format an additional time.
+    pub fn verify_style_support(mut self, verify: bool) -> Self {
+        self.verify_style_support = verify;
+        self
+    }
+
+    /// Remove the keys of a `Custom` style which the given version of clang-format does not support
+    ///
+    /// This allows for a style to target the oldest clang-format used by a team, newer options
+    /// are removed with `ClangFormatStyle::compatible_with` rather than failing on older binaries.
+    /// Each removed key is reported as a warning by `ClangFormat::format_with_warnings`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use clang_format::{ClangFormat, ClangFormatStyle};
+    /// # fn main() {
+    /// let output = ClangFormat::new()
+    ///     .style(ClangFormatStyle::Custom("{ InsertBraces: true }".to_string()))
+    ///     .compat_version((14, 0, 0))
+    ///     .format_with_warnings("int a;\n");
+    /// assert!(output.is_ok());
+    /// assert_eq!(output.unwrap().warnings.len(), 1);
+    /// # }
+    /// ```
+    pub fn compat_version(mut self, version: impl Into<ClangFormatVersion>) -> Self {
+        self.compat_version = Some(version.into());
+        self
+    }
+
+    /// Fail when the version of clang-format cannot be parsed
+    ///
+    /// When enabled the version of clang-format is queried before formatting, and if the output
+    /// of `--version` cannot be parsed, such as for some forks of clang-format,
+    /// `ClangFormatError::UnparseableVersion` is returned rather than the version being ignored.
+    /// This detects when a non-standard build of clang-format was picked up.
+    ///
+    /// This is opt-in as it spawns clang-format an additional time.
+    pub fn require_parseable_version(mut self, require: bool) -> Self {
+        self.require_parseable_version = require;
+        self
+    }
+
+    /// Reject input larger than the given number of bytes before spawning clang-format
+    ///
+    /// Larger input results in `ClangFormatError::InputTooLarge`, which protects services
+    /// from the latency of formatting abusively large input.
+    pub fn max_input_bytes(mut self, limit: usize) -> Self {
+        self.max_input_bytes = Some(limit);
+        self
+    }
+
+    /// Stop clang-format once its output is larger than the given number of bytes
+    ///
+    /// When the output exceeds the limit the clang-format process is killed and
+    /// `ClangFormatError::OutputTooLarge` is returned, which protects services from a
+    /// combination of style and input producing runaway output. Note that this does not
+    /// apply to `ClangFormat::format_file_streamed`, which writes the output directly to disk.
+    pub fn max_output_bytes(mut self, limit: usize) -> Self {
+        self.max_output_bytes = Some(limit);
+        self
+    }
+
+    /// Run clang-format with a lower scheduling priority
+    ///
+    /// On Unix the niceness of the clang-format process is increased by 10, so that build
+    /// systems spawning many clang-format processes do not starve other work such as compilers.
+    /// On other platforms this has no effect.
+    pub fn low_priority(mut self, low_priority: bool) -> Self {
+        self.low_priority = low_priority;
+        self
+    }
+
+    /// Set the number of bytes written to stdin of clang-format per write, by default 64 KiB
+    ///
+    /// The input is written on another thread while the output is read, larger chunks mean
+    /// fewer system calls and smaller chunks let clang-format start reading sooner. This allows
+    /// for tuning the throughput of very large inputs. A size of zero is treated as one byte.
+    /// This has no effect with a `FormatRunner` from `ClangFormat::runner`.
+    pub fn write_chunk_size(mut self, size: usize) -> Self {
+        self.write_chunk_size = Some(size);
+        self
+    }
+
+    /// Set how many clang-format processes `ClangFormat::format_batch` runs concurrently
+    ///
+    /// By default this is the number of logical CPUs, a value of 0 is treated as 1.
+    pub fn max_parallelism(mut self, max_parallelism: usize) -> Self {
+        self.max_parallelism = Some(max_parallelism);
+        self
+    }
+
+    /// Set the filename clang-format assumes the input has
+    ///
+    /// This is passed to `--assume-filename` and is used by clang-format to detect the
+    /// language of the input and, with `ClangFormatStyle::File`, to find the `.clang-format` file.
+    ///
+    /// The language is chosen from the extension, the content of the input is only inspected
+    /// for `.h` files, which may be Objective-C. So input with a preamble such as a `#!` line
+    /// or a license header is formatted as the language of the extension.
+    pub fn assume_filename(mut self, filename: impl AsRef<Path>) -> Self {
+        self.assume_filename = Some(filename.as_ref().to_path_buf());
+        self
+    }
+
+    /// Run clang-format under the given locale, such as `C.UTF-8` or `C`
+    ///
+    /// This sets the `LC_ALL` and `LANG` environment variables of the clang-format process,
+    /// so that the locale of the machine cannot affect the output. Without this the locale
+    /// is inherited from the current process.
+    pub fn locale(mut self, locale: impl Into<String>) -> Self {
+        self.locale = Some(locale.into());
+        self
+    }
+
+    /// Set the style clang-format uses when `ClangFormatStyle::File` finds no `.clang-format` file
+    ///
+    /// This is passed to `--fallback-style`, clang-format only accepts the name of a
+    /// built-in style or `none`, which skips formatting. The fallback style is chosen from
+    ///
+    /// 1. the style given to this method
+    /// 2. the `CLANG_FORMAT_FALLBACK_STYLE` environment variable, parsed as a `ClangFormatStyle`
+    /// 3. the built-in default of clang-format, which is `LLVM`
+    ///
+    /// If the chosen style is not the name of a built-in style or `none`, such as
+    /// `ClangFormatStyle::Default` or an inline `Custom` style, `ClangFormatError::UnknownStyleName`
+    /// is returned when formatting. The fallback style is only passed when formatting, not to
+    /// queries such as `ClangFormat::dump_config`.
+    pub fn fallback_style(mut self, style: impl Into<ClangFormatStyle>) -> Self {
+        self.fallback_style = Some(style.into());
+        self
+    }
+
+    /// Set the runner which executes clang-format, instead of spawning the process
+    ///
+    /// This allows for injecting a mock `FormatRunner` in tests, see `FormatRunner` for an example.
+    /// All commands of this builder are executed by the runner, including querying the version.
+    pub fn runner(mut self, runner: impl FormatRunner + 'static) -> Self {
+        self.runner = Some(Arc::new(runner));
+        self
+    }
+
+    /// Explain which configuration `ClangFormatStyle::File` resolves to for the given path
+    ///
+    /// The path is the file being formatted, or a directory, and relative paths are
+    /// resolved from `ClangFormat::current_dir`. Like clang-format, the directories up to
+    /// the root are searched for a `.clang-format` or `_clang-format` file. If none is found
+    /// the fallback style which applies is returned, see `ClangFormat::fallback_style`.
+    ///
+    /// This does not run clang-format, so it explains why formatting with `ClangFormatStyle::File`
+    /// looks like `LLVM` when there is no configuration file. Like formatting, if the
+    /// `CLANG_FORMAT_FALLBACK_STYLE` environment variable is not a valid style
+    /// `ClangFormatError::UnknownStyleName` is returned.
+    pub fn explain_file_style(
+        &self,
+        start: impl AsRef<Path>,
+    ) -> Result<FileStyleResolution, ClangFormatError> {
+        let start = start.as_ref();
+        let start = match &self.current_dir {
+            Some(dir) => dir.join(start),
+            None => env::current_dir().unwrap_or_default().join(start),
+        };
+        let dir = if start.is_dir() {
+            start.as_path()
+        } else {
+            start.parent().unwrap_or(&start)
+        };
+
+        Ok(match style::find_config_file(dir) {
+            Some(path) => FileStyleResolution::Found(path),
+            None => FileStyleResolution::NotFound {
+                fallback: resolve_fallback_style(
+                    self.fallback_style.as_ref(),
+                    env::var_os("CLANG_FORMAT_FALLBACK_STYLE"),
+                )?
+                .unwrap_or(ClangFormatStyle::Llvm),
+            },
+        })
+    }
+
+    /// Describe the command line clang-format is executed with when formatting
+    ///
+    /// This includes the exact style passed to clang-format, see `CommandLine::style`.
+    pub fn command_line(&self) -> Result<CommandLine, ClangFormatError> {
+        Ok(CommandLine::new(&self.command(&[])?))
+    }
+
+    /// Execute clang-format with exactly the given arguments and return the raw output
+    ///
+    /// This is an escape hatch for invocations which the rest of the API does not cover.
+    /// Only the binary, command prefix, working directory, and runner of this builder are used,
+    /// no style or other arguments are added. The input is written to stdin and stdin is closed,
+    /// without input stdin is closed immediately. stdout and stderr are collected, and the exit
+    /// status is not checked.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use clang_format::ClangFormat;
+    /// # fn main() {
+    /// let output = ClangFormat::new().run_raw(&["--style=LLVM".into()], Some(b"int  a;\n"));
+    /// assert!(output.is_ok());
+    /// let output = output.unwrap();
+    /// assert!(output.status.success());
+    /// assert_eq!(output.stdout, b"int a;\n");
+    /// # }
+    /// ```
+    pub fn run_raw(
+        &self,
+        args: &[OsString],
+        input: Option<&[u8]>,
+    ) -> Result<Output, ClangFormatError> {
+        let mut command = self.binary_command();
+        if let Some(dir) = &self.current_dir {
+            command.current_dir(dir);
+        }
+        command.args(args).stderr(Stdio::piped());
+        self.execute(&mut command, input.unwrap_or_default())
+    }
+
+    /// Query the configuration clang-format resolves for the style
+    ///
+    /// This is the output of `--dump-config`, taking into account the assumed filename.
+    pub fn dump_config(&self) -> Result<String, ClangFormatError> {
+        let mut command = self.command_with(&["--dump-config".to_string()], false)?;
+        let output = self.execute(&mut command, &[])?;
+        self.check_output(&command, &output)?;
+        Ok(String::from_utf8(output.stdout)?)
+    }
+
+    /// Query the configuration clang-format resolves for the style as a typed `StyleConfig`
+    ///
+    /// The output of `ClangFormat::dump_config` is parsed, common options such as `IndentWidth`
+    /// and `ColumnLimit` are typed fields and the remaining options are kept as YAML values.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use clang_format::{ClangFormat, ClangFormatStyle};
+    /// # fn main() {
+    /// let config = ClangFormat::new()
+    ///     .style(ClangFormatStyle::Custom("{ IndentWidth: 3 }".to_string()))
+    ///     .dump_config_typed();
+    /// assert!(config.is_ok());
+    /// assert_eq!(config.unwrap().indent_width, Some(3));
+    /// # }
+    /// ```
+    pub fn dump_config_typed(&self) -> Result<StyleConfig, ClangFormatError> {
+        StyleConfig::parse(&self.dump_config()?)
+    }
+
+    /// Compare the configuration of the style between this clang-format binary and another
+    ///
+    /// `--dump-config` is executed with both binaries, with all other options of this builder,
+    /// and the top level keys whose values differ are returned as `(key, value, other_value)`.
+    /// Values are in YAML flow style, such as `{ AfterClass: true }`, and a key which one of
+    /// the binaries does not know has an empty value. This shows which defaults change when
+    /// upgrading clang-format.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use clang_format::{ClangFormat, ClangFormatStyle};
+    /// # fn main() {
+    /// let diff = ClangFormat::new()
+    ///     .binary("clang-format-14")
+    ///     .style(ClangFormatStyle::Google)
+    ///     .config_diff("clang-format-16");
+    /// assert!(diff.is_ok());
+    /// for (key, old, new) in diff.unwrap() {
+    ///     println!("{key}: {old} -> {new}");
+    /// }
+    /// # }
+    /// ```
+    pub fn config_diff(
+        &self,
+        other_binary: impl AsRef<Path>,
+    ) -> Result<Vec<(String, String, String)>, ClangFormatError> {
+        let config = self.dump_config()?;
+        let other_config = self.clone().binary(other_binary).dump_config()?;
+        style::config_diff(&config, &other_config)
+    }
+
+    /// Query the language clang-format uses to format the input
+    ///
+    /// The language is detected from the assumed filename, when no filename is assumed
+    /// clang-format uses `Language::Cpp`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use clang_format::{ClangFormat, Language};
+    /// # fn main() {
+    /// let language = ClangFormat::new().assume_filename("test.java").language();
+    /// assert!(language.is_ok());
+    /// assert_eq!(language.unwrap(), Language::Java);
+    /// # }
+    /// ```
+    pub fn language(&self) -> Result<Language, ClangFormatError> {
+        let config = self.dump_config()?;
+        let value = style::config_value(&config, "Language").unwrap_or_default();
+        Language::from_config_str(value)
+            .ok_or_else(|| ClangFormatError::UnknownLanguage(value.to_string()))
+    }
+
+    /// Query the version of the clang-format binary
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use clang_format::{ClangFormat, ClangFormatVersion};
+    /// # fn main() {
+    /// let version = ClangFormat::new().version();
+    /// assert!(version.is_ok());
+    /// assert!(version.unwrap() >= ClangFormatVersion::new(9, 0, 0));
+    /// # }
+    /// ```
+    pub fn version(&self) -> Result<ClangFormatVersion, ClangFormatError> {
+        let output = self.version_output()?;
+        ClangFormatVersion::parse(&output)
+            .ok_or_else(|| ClangFormatError::UnparseableVersion(output.trim().to_string()))
+    }
+
+    /// The output of `clang-format --version`
+    fn version_output(&self) -> Result<String, ClangFormatError> {
+        let mut command = self.binary_command();
+        command.arg("--version").stderr(Stdio::piped());
+        let output = self.execute(&mut command, &[])?;
+        if !output.status.success() {
+            return Err(ClangFormatError::NonZeroExitStatus {
+                command: CommandLine::new(&command),
+                status: output.status,
+            });
+        }
+        Ok(String::from_utf8(output.stdout)?)
+    }
+
+    /// Compute a stable key for caching the output of formatting the input
+    ///
+    /// The key is a hash of the input, the arguments clang-format would be executed with,
+    /// such as the style and assumed filename, and the full output of `clang-format --version`,
+    /// so that it changes whenever any of them changes. The hash is stable across processes
+    /// and platforms, so the key can be persisted.
+    ///
+    /// Note that with `ClangFormatStyle::File` the contents of the `.clang-format` file are
+    /// not part of the key.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use clang_format::{ClangFormat, ClangFormatStyle};
+    /// # fn main() {
+    /// let format = ClangFormat::new().style(ClangFormatStyle::Mozilla);
+    /// let key = format.cache_key("int  a;\n");
+    /// assert!(key.is_ok());
+    /// assert_eq!(key.unwrap(), format.cache_key("int  a;\n").unwrap());
+    /// # }
+    /// ```
+    pub fn cache_key(&self, input: &str) -> Result<u64, ClangFormatError> {
+        let mut hasher = Fnv1a::new();
+        hasher.write_field(input.as_bytes());
+        for arg in self.command(&[])?.get_args() {
+            hasher.write_field(arg.as_encoded_bytes());
+        }
+        hasher.write_field(self.version_output()?.as_bytes());
+        Ok(hasher.finish())
+    }
+
+    /// Check that the clang-format binary is at least the given version
+    ///
+    /// This is intended to be called once during initialization, so that an unsuitable
+    /// clang-format is reported up front with `ClangFormatError::VersionTooOld`
+    /// rather than by each call site.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use clang_format::ClangFormat;
+    /// # fn main() {
+    /// assert!(ClangFormat::new().require_version((9, 0, 0)).is_ok());
+    /// # }
+    /// ```
+    pub fn require_version(
+        &self,
+        min: impl Into<ClangFormatVersion>,
+    ) -> Result<(), ClangFormatError> {
+        let required_version = min.into();
+        let found_version = self.version()?;
+        if found_version < required_version {
+            return Err(ClangFormatError::VersionTooOld {
+                required_version,
+                found_version,
+            });
+        }
+        Ok(())
+    }
+
+    /// Check whether the clang-format binary supports the given command line flag
+    ///
+    /// The flag can be given with or without leading dashes, eg `--qualifier-alignment`
+    /// or `dry-run`, and is searched for in the output of `clang-format --help`.
+    /// If clang-format cannot be run this returns false.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use clang_format::ClangFormat;
+    /// # fn main() {
+    /// assert!(ClangFormat::new().supports_flag("--style"));
+    /// assert!(!ClangFormat::new().supports_flag("--no-such-flag"));
+    /// # }
+    /// ```
+    pub fn supports_flag(&self, flag: &str) -> bool {
+        self.probe_flag(flag).unwrap_or(false)
+    }
+
+    /// Check whether the flag is listed in `clang-format --help`, failing if it cannot be run
+    fn probe_flag(&self, flag: &str) -> Result<bool, ClangFormatError> {
+        let mut command = self.binary_command();
+        command.arg("--help").stderr(Stdio::null());
+        self.execute(&mut command, &[]).map(|output| {
+            output.status.success()
+                && help_mentions_flag(&String::from_utf8_lossy(&output.stdout), flag)
+        })
+    }
+
+    /// Check whether the clang-format binary supports `--cursor`, probing each binary only once
+    ///
+    /// A runner may answer differently for the same binary, so only spawned processes are cached.
+    fn supports_cursor(&self) -> bool {
+        if self.runner.is_some() {
+            return self.supports_flag("--cursor");
+        }
+
+        let command = self.binary_command();
+        let key: Vec<OsString> = std::iter::once(command.get_program())
+            .chain(command.get_args())
+            .map(ToOwned::to_owned)
+            .collect();
+        let cache = CURSOR_SUPPORT.get_or_init(Default::default);
+        if let Some(supported) = cache.lock().expect("cursor support poisoned").get(&key) {
+            return *supported;
+        }
+
+        // Failing to run clang-format is not cached, as the binary may become available
+        match self.probe_flag("--cursor") {
+            Ok(supported) => {
+                cache
+                    .lock()
+                    .expect("cursor support poisoned")
+                    .insert(key, supported);
+                supported
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Query the names of the predefined styles the clang-format binary supports
+    ///
+    /// The names are read from the description of `--style` in `clang-format --help`.
+    /// If they cannot be found there, each of the styles known to this crate is tried
+    /// with `--dump-config` and the names of those which succeed are returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use clang_format::ClangFormat;
+    /// # fn main() {
+    /// let styles = ClangFormat::new().available_builtin_styles();
+    /// assert!(styles.is_ok());
+    /// assert!(styles.unwrap().contains(&"LLVM".to_string()));
+    /// # }
+    /// ```
+    pub fn available_builtin_styles(&self) -> Result<Vec<String>, ClangFormatError> {
+        let mut command = self.binary_command();
+        command.arg("--help").stderr(Stdio::null());
+        let output = self.execute(&mut command, &[])?;
+        if output.status.success() {
+            if let Some(styles) = help_builtin_styles(&String::from_utf8_lossy(&output.stdout)) {
+                return Ok(styles);
+            }
+        }
+
+        let mut styles = vec![];
+        for style in ClangFormatStyle::named() {
+            if style == ClangFormatStyle::File {
+                continue;
+            }
+
+            let mut command = self.binary_command();
+            command
+                .arg(format!("--style={}", style.as_str()))
+                .arg("--dump-config")
+                .stderr(Stdio::null());
+            if self.execute(&mut command, &[])?.status.success() {
+                styles.push(style.canonical_name().to_string());
+            }
+        }
+        Ok(styles)
+    }
+
+    /// Query the languages which the clang-format binary supports
+    ///
+    /// This is derived from the version of clang-format, as support for languages such as
+    /// C#, JSON, and Verilog was added over time. `Language::None` is not included.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use clang_format::{ClangFormat, Language};
+    /// # fn main() {
+    /// let languages = ClangFormat::new().supported_languages();
+    /// assert!(languages.is_ok());
+    /// assert!(languages.unwrap().contains(&Language::Cpp));
+    /// # }
+    /// ```
+    pub fn supported_languages(&self) -> Result<Vec<Language>, ClangFormatError> {
+        let version = self.version()?;
+        Ok(Language::ALL
+            .into_iter()
+            .filter(|language| *language != Language::None)
+            .filter(|language| {
+                language
+                    .required_version()
+                    .is_none_or(|required| version >= required)
+            })
+            .collect())
+    }
+
+    /// Execute clang-format with the given input and collect the output
+    pub fn format(&self, input: impl AsRef<str>) -> Result<String, ClangFormatError> {
+        self.format_with_args(input.as_ref(), &[])
+    }
+
+    /// Execute clang-format with the given input and collect the output as an `Arc<str>`
+    ///
+    /// This allows for the output to be stored and shared, such as in a cache, without
+    /// cloning the string for each reader.
+    pub fn format_shared(&self, input: impl AsRef<str>) -> Result<Arc<str>, ClangFormatError> {
+        self.format(input).map(Arc::from)
+    }
+
+    /// Execute clang-format with the given input and describe whether the output differs
+    ///
+    /// This allows for skipping work such as writing files when nothing changed.
+    pub fn format_outcome(
+        &self,
+        input: impl AsRef<str>,
+    ) -> Result<FormatOutcome, ClangFormatError> {
+        let input = input.as_ref();
+        let output = self.format(input)?;
+        if output == input {
+            Ok(FormatOutcome::Unchanged)
+        } else {
+            Ok(FormatOutcome::Changed(output))
+        }
+    }
+
+    /// Execute clang-format for each of the inputs in parallel and collect the outputs
+    ///
+    /// The results are in the same order as the inputs. At most `ClangFormat::max_parallelism`
+    /// clang-format processes are run at once, which defaults to the number of logical CPUs.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use clang_format::ClangFormat;
+    /// # fn main() {
+    /// let outputs = ClangFormat::new()
+    ///     .max_parallelism(2)
+    ///     .format_batch(&["int  a;\n", "int  b;\n", "int  c;\n"]);
+    /// assert_eq!(outputs.len(), 3);
+    /// assert_eq!(outputs[1].as_ref().unwrap(), "int b;\n");
+    /// # }
+    /// ```
+    pub fn format_batch<I: AsRef<str> + Sync>(
+        &self,
+        inputs: &[I],
+    ) -> Vec<Result<String, ClangFormatError>> {
+        let results = Mutex::new(Vec::with_capacity(inputs.len()));
+        self.format_batch_each(inputs, |index, result| {
+            results
+                .lock()
+                .expect("batch results poisoned")
+                .push((index, result))
+        });
+        let mut results = results.into_inner().expect("batch results poisoned");
+        results.sort_by_key(|(index, _)| *index);
+        results.into_iter().map(|(_, result)| result).collect()
+    }
+
+    /// Execute clang-format for each of the inputs in parallel, passing each result to the callback as it completes
+    pub(crate) fn format_batch_each<I: AsRef<str> + Sync>(
+        &self,
+        inputs: &[I],
+        on_result: impl Fn(usize, Result<String, ClangFormatError>) + Sync,
+    ) {
+        let workers = self
+            .max_parallelism
+            .or_else(|| thread::available_parallelism().ok().map(usize::from))
+            .unwrap_or(1)
+            .clamp(1, inputs.len().max(1));
+        let next = AtomicUsize::new(0);
+
+        thread::scope(|scope| {
+            // Each worker takes the next input until none are left
+            for _ in 0..workers {
+                scope.spawn(|| loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    match inputs.get(index) {
+                        Some(input) => on_result(index, self.format(input)),
+                        None => break,
+                    }
+                });
+            }
+        });
+    }
+
+    /// Execute clang-format for each of the inputs in parallel, returning a stream of the outputs
+    ///
+    /// Like `ClangFormat::format_batch` with the inputs formatted on a background thread, but each
+    /// result is returned with the index of its input as soon as it completes. The stream does not
+    /// depend on any particular async runtime.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use clang_format::ClangFormat;
+    /// # async fn example() {
+    /// let mut outputs = ClangFormat::new().format_batch_stream(["int  a;\n", "int  b;\n"]);
+    /// while let Some((index, output)) = outputs.next().await {
+    ///     println!("input {index} formatted to {:?}", output.unwrap());
+    /// }
+    /// # }
+    /// ```
+    #[cfg(feature = "async")]
+    pub fn format_batch_stream(
+        &self,
+        inputs: impl IntoIterator<Item = impl Into<String>>,
+    ) -> FormatBatchStream {
+        FormatBatchStream::spawn(self.clone(), inputs.into_iter().map(Into::into).collect())
+    }
+
+    /// Execute clang-format for each of the inputs in parallel, returning a future of the outputs
+    ///
+    /// This collects `ClangFormat::format_batch_stream`, the future does not depend on any
+    /// particular async runtime. The results are in the same order as the inputs.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use clang_format::ClangFormat;
+    /// # async fn example() {
+    /// let outputs = ClangFormat::new()
+    ///     .format_batch_async(["int  a;\n", "int  b;\n"])
+    ///     .await;
+    /// assert_eq!(outputs[1].as_ref().unwrap(), "int b;\n");
+    /// # }
+    /// ```
+    #[cfg(feature = "async")]
+    pub fn format_batch_async(
+        &self,
+        inputs: impl IntoIterator<Item = impl Into<String>>,
+    ) -> FormatBatchFuture {
+        FormatBatchFuture::spawn(self.clone(), inputs.into_iter().map(Into::into).collect())
+    }
+
+    /// Execute clang-format with the given input on a background thread, returning a future of the output
+    ///
+    /// Dropping the future before it completes cancels formatting, which is useful in a server
+    /// when a client disconnects. The clang-format process is killed, and on Unix it is started
+    /// in a new process group so that any processes it started are killed too. The future does
+    /// not depend on any particular async runtime.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use clang_format::ClangFormat;
+    /// # async fn example() {
+    /// let output = ClangFormat::new().format_async("int  a;\n").await;
+    /// assert!(output.is_ok());
+    /// assert_eq!(output.unwrap(), "int a;\n");
+    /// # }
+    /// ```
+    #[cfg(feature = "async")]
+    pub fn format_async(&self, input: impl Into<String>) -> FormatFuture {
+        let handle = Arc::new(CancelHandle::default());
+        let format = Self {
+            cancel: Some(handle.clone()),
+            ..self.clone()
+        };
+        FormatFuture::spawn(format, input.into(), handle)
+    }
+
+    /// Execute clang-format with the given input and record a timestamped log of its output
+    ///
+    /// Each chunk read from stdout and stderr is recorded with the time since formatting
+    /// started, interleaved in the order they arrived. The log is returned for failures too,
+    /// which turns intermittent failures in CI into a trace of what clang-format did.
+    /// This is heavier than formatting normally, as stderr is captured and each chunk is copied.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use clang_format::{ClangFormat, LogStream};
+    /// # fn main() {
+    /// let (output, log) = ClangFormat::new().format_with_log("int  a;\n");
+    /// assert_eq!(output.unwrap(), "int a;\n");
+    /// assert_eq!(log.entries[0].stream, LogStream::Stdout);
+    /// println!("{log}");
+    /// # }
+    /// ```
+    #[cfg(feature = "debug_log")]
+    pub fn format_with_log(
+        &self,
+        input: impl AsRef<str>,
+    ) -> (Result<String, ClangFormatError>, ProcessLog) {
+        let log = Arc::new(Mutex::new(ProcessLog::new()));
+        let format = Self {
+            log: Some(log.clone()),
+            ..self.clone()
+        };
+        let result = format
+            .run_collect(input.as_ref().as_bytes(), &[], true)
+            .and_then(|output| Ok(String::from_utf8(output.stdout)?));
+        let log = log.lock().expect("process log poisoned").clone();
+        (result, log)
+    }
+
+    /// Execute clang-format with the given input and collect the output with any warnings
+    ///
+    /// Rather than failing like `reject_diagnostics`, or ignoring stderr, the output of a
+    /// successful run is returned with each line of stderr which is a warning, such as
+    /// the deprecation of a style option. This allows tools to show warnings without aborting.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use clang_format::ClangFormat;
+    /// # fn main() {
+    /// let output = ClangFormat::new().format_with_warnings("int  a;\n");
+    /// assert!(output.is_ok());
+    /// let output = output.unwrap();
+    /// assert_eq!(output.output, "int a;\n");
+    /// assert!(output.warnings.is_empty());
+    /// # }
+    /// ```
+    pub fn format_with_warnings(
+        &self,
+        input: impl AsRef<str>,
+    ) -> Result<FormatOutputWithWarnings, ClangFormatError> {
+        let output = self.run_collect(input.as_ref().as_bytes(), &[], true)?;
+        let mut warnings = self.compat_warnings();
+        warnings.extend(
+            String::from_utf8_lossy(&output.stderr)
+                .lines()
+                .filter(|line| line.contains("warning: "))
+                .map(str::to_string),
+        );
+        Ok(FormatOutputWithWarnings {
+            output: String::from_utf8(output.stdout)?,
+            warnings,
+        })
+    }
+
+    /// Execute clang-format with the given input and collect the output with `FormatStats`
+    ///
+    /// The output is read incrementally, so the time until clang-format writes the first byte
+    /// of output is measured as well as the total duration. With the `resource_usage` feature
+    /// the CPU time and maximum resident set size of clang-format are also measured on Unix.
+    pub fn format_with_stats(
+        &self,
+        input: impl AsRef<str>,
+    ) -> Result<(String, FormatStats), ClangFormatError> {
+        let input = input.as_ref();
+        #[cfg(feature = "resource_usage")]
+        let usage_before = ResourceUsage::children();
+        let start = Instant::now();
+        let output = self.run_collect(input.as_bytes(), &[], false)?;
+        let stats = FormatStats {
+            input_bytes: input.len(),
+            output_bytes: output.stdout.len(),
+            duration: start.elapsed(),
+            time_to_first_byte: output.first_chunk.map(|instant| instant - start),
+            #[cfg(feature = "resource_usage")]
+            resource_usage: usage_before
+                .and_then(|before| ResourceUsage::children().map(|after| after.since(&before))),
+        };
+        Ok((String::from_utf8(output.stdout)?, stats))
+    }
+
+    /// Execute clang-format with the given input and split the output into lines
+    ///
+    /// Lines do not include their `\n` or `\r\n` terminator, and a final newline does not
+    /// result in an empty last line. Empty output results in no lines.
+    pub fn format_lines(&self, input: impl AsRef<str>) -> Result<Vec<String>, ClangFormatError> {
+        Ok(self.format(input)?.lines().map(str::to_string).collect())
+    }
+
+    /// Execute clang-format with the given input as if it had no `clang-format off` regions
+    ///
+    /// The `clang-format off` and `clang-format on` comments are removed before formatting and
+    /// are not restored, so the output shows how clang-format would format every line. This is
+    /// intended for analysis, such as comparing with `ClangFormat::format_hunks`, and the output
+    /// should not be used to overwrite the source as the guards and any manual layout are lost.
+    pub fn format_ignoring_guards(&self, input: &str) -> Result<String, ClangFormatError> {
+        self.format(guards::strip_guards(input))
+    }
+
+    /// Execute clang-format with the given input and the style configuration as a string
+    ///
+    /// The input is piped over stdin, so the configuration cannot be. Instead it is written to
+    /// a temporary file in the directory set with `ClangFormat::temp_dir`, which is passed as
+    /// `--style=file:<path>` and removed afterwards. The configuration is the contents of a
+    /// `.clang-format` file and replaces the style of this builder.
+    ///
+    /// Since clang-format 14
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use clang_format::ClangFormat;
+    /// # fn main() {
+    /// let output = ClangFormat::new()
+    ///     .format_with_config_string("struct Test {};\n", "BasedOnStyle: Mozilla\n");
+    /// assert!(output.is_ok());
+    /// assert_eq!(output.unwrap(), "struct Test\n{};\n");
+    /// # }
+    /// ```
+    pub fn format_with_config_string(
+        &self,
+        input: &str,
+        config_yaml: &str,
+    ) -> Result<String, ClangFormatError> {
+        let dir = self.temp_dir.clone().unwrap_or_else(env::temp_dir);
+        let path = dir.join(format!(
+            "clang-format-rs-style.{}.{}.yaml",
+            process::id(),
+            TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        write_new_file(&path, config_yaml.as_bytes())?;
+
+        // The style is only used for checking the version and describing errors
+        let mut format = self
+            .clone()
+            .style(ClangFormatStyle::Custom(format!("file:{}", path.display())));
+        format.style_file = Some(path.clone());
+        let result = format.format(input);
+        let _ = fs::remove_file(&path);
+        result
+    }
+
+    /// Execute clang-format with the given input and indent each line of the output
+    ///
+    /// The given number of spaces is prepended to each line which is not empty, so the output
+    /// can be spliced into a larger document at that indentation, such as by a template engine.
+    /// Note that the `ColumnLimit` of the style does not take the indentation into account,
+    /// and lines inside multi-line string literals are also indented.
+    pub fn format_indented_by(
+        &self,
+        input: impl AsRef<str>,
+        spaces: usize,
+    ) -> Result<String, ClangFormatError> {
+        let output = self.format(input)?;
+        let indent = " ".repeat(spaces);
+        Ok(output
+            .split_inclusive('\n')
+            .map(|line| {
+                if line.trim_end_matches(['\r', '\n']).is_empty() {
+                    line.to_string()
+                } else {
+                    format!("{indent}{line}")
+                }
+            })
+            .collect())
+    }
+
+    /// Execute clang-format with the given input and return the regions which changed
+    pub fn format_hunks(&self, input: &str) -> Result<Vec<Hunk>, ClangFormatError> {
+        let output = self.format(input)?;
+        Ok(diff::hunks(input, &output))
+    }
+
+    /// Execute clang-format with the given input and find the output lines longer than the `ColumnLimit`
+    ///
+    /// clang-format cannot always wrap lines within the limit, such as long string literals.
+    /// The `ColumnLimit` is read from `ClangFormat::dump_config` and the 1-based line numbers of
+    /// the output which exceed it are returned. A `ColumnLimit` of 0 means there is no limit.
+    /// Columns are counted as characters.
+    pub fn lines_over_column_limit(&self, input: &str) -> Result<Vec<usize>, ClangFormatError> {
+        let output = self.format(input)?;
+        let limit = self.column_limit()?;
+        if limit == 0 {
+            return Ok(vec![]);
+        }
+
+        Ok(output
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| line.chars().count() > limit)
+            .map(|(index, _)| index + 1)
+            .collect())
+    }
+
+    /// Execute clang-format with the given input and compute the fraction of lines within the `ColumnLimit`
+    ///
+    /// This is the number of non-blank lines of the output which are within the `ColumnLimit`
+    /// divided by the number of non-blank lines, as in `ClangFormat::lines_over_column_limit`.
+    /// The result is `1.0` when there are no non-blank lines or the `ColumnLimit` is 0.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use clang_format::{ClangFormat, ClangFormatStyle};
+    /// # fn main() {
+    /// let compliance = ClangFormat::new()
+    ///     .style(ClangFormatStyle::Custom("{ ColumnLimit: 10 }".to_string()))
+    ///     .column_compliance("int a;\n\nint abcdefghijklmno;\n");
+    /// assert!(compliance.is_ok());
+    /// assert_eq!(compliance.unwrap(), 0.5);
+    /// # }
+    /// ```
+    pub fn column_compliance(&self, input: &str) -> Result<f64, ClangFormatError> {
+        let output = self.format(input)?;
+        let limit = self.column_limit()?;
+        let lengths: Vec<usize> = output
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| line.chars().count())
+            .collect();
+        if limit == 0 || lengths.is_empty() {
+            return Ok(1.0);
+        }
+
+        let within = lengths.iter().filter(|len| **len <= limit).count();
+        Ok(within as f64 / lengths.len() as f64)
+    }
+
+    /// The `ColumnLimit` of the configuration clang-format resolves for the style
+    fn column_limit(&self) -> Result<usize, ClangFormatError> {
+        let config = self.dump_config()?;
+        style::config_value(&config, "ColumnLimit")
+            .and_then(|limit| limit.parse().ok())
+            .ok_or_else(|| {
+                ClangFormatError::InvalidStyleConfig("missing or invalid ColumnLimit".to_string())
+            })
+    }
+
+    /// Check whether the built-in default of clang-format has the same configuration as the style
+    ///
+    /// The `--dump-config` output of `ClangFormatStyle::Default` is compared with that of the style,
+    /// both with the other options of this builder. The default should match `ClangFormatStyle::Llvm`,
+    /// but a patched or vendored clang-format may differ, which would silently change formatting.
+    pub fn default_matches(
+        &self,
+        style: impl Into<ClangFormatStyle>,
+    ) -> Result<bool, ClangFormatError> {
+        let default = self
+            .clone()
+            .style(ClangFormatStyle::Default)
+            .dump_config()?;
+        let config = self.clone().style(style).dump_config()?;
+        Ok(default == config)
+    }
+
+    /// Check that two styles format the given input identically
+    ///
+    /// Both styles are used with the other options of this builder, which allows for
+    /// confirming that a new style is equivalent to an old one on representative code.
+    pub fn styles_agree(
+        &self,
+        input: &str,
+        a: impl Into<ClangFormatStyle>,
+        b: impl Into<ClangFormatStyle>,
+    ) -> Result<bool, ClangFormatError> {
+        Ok(self.style_differences(input, a, b)?.is_empty())
+    }
+
+    /// Format the given input with two styles and return the regions where the outputs differ
+    ///
+    /// In each `Hunk` the original text is the output of style `a`
+    /// and the formatted text is the output of style `b`.
+    pub fn style_differences(
+        &self,
+        input: &str,
+        a: impl Into<ClangFormatStyle>,
+        b: impl Into<ClangFormatStyle>,
+    ) -> Result<Vec<Hunk>, ClangFormatError> {
+        let output_a = self.clone().style(a).format(input)?;
+        let output_b = self.clone().style(b).format(input)?;
+        Ok(diff::hunks(&output_a, &output_b))
+    }
+
+    /// Execute clang-format with the given input and check that the structure of the code is preserved
+    ///
+    /// This is a cheap guard against catastrophic formatting failures, such as for generated code.
+    /// It is not a parser, the number of each kind of bracket outside of comments and literals
+    /// must be the same in the input and output, otherwise `ClangFormatError::StructuralMismatch`
+    /// is returned. Note that style options which add or remove braces, such as `InsertBraces`,
+    /// also cause a mismatch.
+    pub fn format_and_sanity_check(&self, input: &str) -> Result<String, ClangFormatError> {
+        let output = self.format(input)?;
+        structure::compare(input, &output).map_err(ClangFormatError::StructuralMismatch)?;
+        Ok(output)
+    }
+
+    /// Check that formatting the formatted output again does not change it
+    ///
+    /// The input is formatted, then the output is formatted again, and this returns whether
+    /// both outputs match. Some versions of clang-format are not idempotent for certain styles.
+    pub fn is_idempotent(&self, input: &str) -> Result<bool, ClangFormatError> {
+        let once = self.format(input)?;
+        let twice = self.format(&once)?;
+        Ok(once == twice)
+    }
+
+    /// Execute clang-format on the bodies of the functions with the given name only
+    ///
+    /// The functions are found with a heuristic scan which matches braces outside of comments
+    /// and literals, and the lines from the opening to the closing brace of each body are passed
+    /// to clang-format with `--lines`. The rest of the input is left untouched. If no definition
+    /// is found `ClangFormatError::FunctionNotFound` is returned.
+    ///
+    /// This is not a parser, so the heuristic has limitations
+    ///
+    /// - every overload with the name is formatted, a qualified name such as `Widget::draw`
+    ///   can be given to narrow the match
+    /// - names followed by template arguments, such as an explicit specialization `foo<int>()`,
+    ///   are not found, and macros which expand to a signature are not understood
+    /// - a brace initializer in a constructor initializer list, such as `: a{1}`,
+    ///   is taken as the start of the body
+    pub fn format_function(&self, input: &str, name: &str) -> Result<String, ClangFormatError> {
+        // clang-format lines are 1-based and the range is inclusive
+        let args: Vec<String> = structure::function_body_lines(input, name)
+            .into_iter()
+            .map(|lines| format!("--lines={}:{}", lines.start + 1, lines.end))
+            .collect();
+        if args.is_empty() {
+            return Err(ClangFormatError::FunctionNotFound(name.to_string()));
+        }
+        self.format_with_args(input, &args)
+    }
+
+    /// Format the input repeatedly until the output no longer changes
+    ///
+    /// Some versions of clang-format are not idempotent for certain styles, so formatting
+    /// the output again changes it. This formats at most `max_iters` times, stopping once
+    /// formatting returns its input unchanged, which protects generated code from oscillating
+    /// formatting. If the output has not stabilized `ClangFormatError::NotConverged` is returned.
+    pub fn format_to_fixpoint(
+        &self,
+        input: &str,
+        max_iters: usize,
+    ) -> Result<String, ClangFormatError> {
+        let mut current = input.to_string();
+        for _ in 0..max_iters {
+            let next = self.format(&current)?;
+            if next == current {
+                return Ok(next);
+            }
+            current = next;
+        }
+        Err(ClangFormatError::NotConverged {
+            iterations: max_iters,
+        })
+    }
+
+    /// Execute clang-format with the given input and check whether only whitespace changes
+    ///
+    /// The input and output are compared ignoring all whitespace, so this is true when
+    /// formatting only changes indentation, spacing, and line breaks. Such changes are
+    /// low risk to apply automatically, whereas other changes, such as sorting includes
+    /// or inserting braces, may need a review. Note that whitespace inside string literals
+    /// is also ignored.
+    pub fn changes_are_whitespace_only(&self, input: &str) -> Result<bool, ClangFormatError> {
+        let output = self.format(input)?;
+        let non_whitespace = |text: &str| {
+            text.chars()
+                .filter(|c| !c.is_whitespace())
+                .collect::<Vec<_>>()
+        };
+        Ok(non_whitespace(input) == non_whitespace(&output))
+    }
+
+    /// Format the input the given number of times and check that every output is the same
+    ///
+    /// This guards against flaky formatting in reproducible builds or CI, if an output
+    /// differs from the first output `ClangFormatError::NondeterministicOutput` is returned
+    /// with the 1-based number of the run.
+    pub fn assert_deterministic(&self, input: &str, runs: usize) -> Result<(), ClangFormatError> {
+        if runs == 0 {
+            return Ok(());
+        }
+
+        let first = self.format(input)?;
+        for run in 2..=runs {
+            if self.format(input)? != first {
+                return Err(ClangFormatError::NondeterministicOutput { run });
+            }
+        }
+        Ok(())
+    }
+
+    /// Execute clang-format with the given input and return the edits it would make
+    ///
+    /// Rather than the formatted output, this returns the replacements which would turn
+    /// the input into the formatted output, which is useful for editors and review tools.
+    ///
+    /// clang-format only provides replacements as XML, from `--output-replacements-xml`,
+    /// this parses that output so that callers do not depend on its format.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use clang_format::{ClangFormat, Replacement};
+    /// # fn main() {
+    /// let replacements = ClangFormat::new().replacements("int  a;\n");
+    /// assert!(replacements.is_ok());
+    /// assert_eq!(
+    ///     replacements.unwrap(),
+    ///     vec![Replacement { offset: 3, length: 2, text: " ".to_string() }]
+    /// );
+    /// # }
+    /// ```
+    pub fn replacements(&self, input: &str) -> Result<Vec<Replacement>, ClangFormatError> {
+        let output =
+            self.format_with_args_unprocessed(input, &["--output-replacements-xml".to_string()])?;
+        replacements::parse_xml(&output).map_err(ClangFormatError::UnparseableReplacements)
+    }
+
+    /// Execute clang-format with the given input and track the byte offset of a cursor
+    ///
+    /// Returns the formatted output with the offset of the cursor in it. When clang-format
+    /// supports `--cursor` the exact position is used, otherwise it is estimated by diffing the
+    /// input and output, see `estimate_cursor`. The estimate is an approximation which may differ
+    /// from the `--cursor` result when formatting changes more than whitespace.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use clang_format::ClangFormat;
+    /// # fn main() {
+    /// let output = ClangFormat::new().format_with_cursor("int    a;\n", 7);
+    /// assert!(output.is_ok());
+    /// assert_eq!(output.unwrap(), ("int a;\n".to_string(), 4));
+    /// # }
+    /// ```
+    pub fn format_with_cursor(
+        &self,
+        input: &str,
+        cursor: usize,
+    ) -> Result<(String, usize), ClangFormatError> {
+        self.format_with_cursor_and_args(input, cursor, vec![])
+    }
+
+    /// Execute clang-format on a region of lines and return the formatted region and the cursor
+    ///
+    /// The lines are a 0-based range which is passed to clang-format with `--lines`, so the rest
+    /// of the input is left unchanged. Only the text of the region is returned, which allows for
+    /// an editor to re-render the edited lines rather than the whole buffer, such as when
+    /// formatting on typing. The cursor is tracked as in `ClangFormat::format_with_cursor`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use clang_format::ClangFormat;
+    /// # fn main() {
+    /// let input = "int  a;\nint    b;\nint  c;\n";
+    /// let region = ClangFormat::new().format_region_with_cursor(input, 1..2, 15);
+    /// assert!(region.is_ok());
+    /// let region = region.unwrap();
+    /// assert_eq!(region.line_range, 1..2);
+    /// assert_eq!(region.text, "int b;\n");
+    /// assert_eq!(region.cursor, 12);
+    /// # }
+    /// ```
+    pub fn format_region_with_cursor(
+        &self,
+        input: &str,
+        lines: Range<usize>,
+        cursor: usize,
+    ) -> Result<FormattedRegion, ClangFormatError> {
+        // clang-format lines are 1-based and the range is inclusive
+        let args = vec![format!(
+            "--lines={}:{}",
+            lines.start + 1,
+            lines.end.max(lines.start + 1)
+        )];
+        let (output, cursor) = self.format_with_cursor_and_args(input, cursor, args)?;
+        let (line_range, formatted_line_range) = cursor::region(input, &output, lines);
+        let text = output
+            .split_inclusive('\n')
+            .skip(formatted_line_range.start)
+            .take(formatted_line_range.len())
+            .collect();
+        Ok(FormattedRegion {
+            line_range,
+            formatted_line_range,
+            text,
+            cursor,
+        })
+    }
+
+    /// Execute clang-format with the given input and extra arguments, and track the cursor
+    fn format_with_cursor_and_args(
+        &self,
+        input: &str,
+        cursor: usize,
+        mut args: Vec<String>,
+    ) -> Result<(String, usize), ClangFormatError> {
+        if self.supports_cursor() {
+            args.push(format!("--cursor={cursor}"));
+            let output = self.format_with_args_unprocessed(input, &args)?;
+            let (cursor, output) =
+                cursor::parse_output(&output).map_err(ClangFormatError::UnparseableCursor)?;
+
+            // Post-process the code after the header, moving the cursor with the removed bytes
+            let removed_before_cursor = if self.normalize_crlf {
+                output.as_bytes()[..cursor.min(output.len())]
+                    .windows(2)
+                    .filter(|pair| pair == b"\r\n")
+                    .count()
+            } else {
+                0
+            };
+            let mut output = output.into_bytes();
+            self.post_process(input.as_bytes(), &mut output);
+            Ok((String::from_utf8(output)?, cursor - removed_before_cursor))
+        } else {
+            let output = self.format_with_args(input, &args)?;
+            let cursor = cursor::estimate(input, &output, cursor);
+            Ok((output, cursor))
+        }
+    }
+
+    /// Execute clang-format with the given input and return both the edits and the formatted output
+    ///
+    /// clang-format is executed once with `--output-replacements-xml`, and the replacements are
+    /// applied to the input with `apply_replacements` to produce the output. This allows for
+    /// showing the individual edits and using the result without executing clang-format twice.
+    pub fn format_with_replacements(
+        &self,
+        input: &str,
+    ) -> Result<FormatOutputWithReplacements, ClangFormatError> {
+        let replacements = self.replacements(input)?;
+        Ok(FormatOutputWithReplacements {
+            output: replacements::apply_replacements(input, &replacements)?,
+            replacements,
+        })
+    }
+
+    /// Execute clang-format with `--dry-run` and return where the input is not formatted
+    ///
+    /// This uses clang-format's own violation reporting, giving precise locations
+    /// without diffing the output. When the input is already formatted this is empty.
+    ///
+    /// Violations are not an error, but clang-format failing for any other reason is, such as
+    /// an invalid style or crashing with an exit code other than 1.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use clang_format::ClangFormat;
+    /// # fn main() {
+    /// let diagnostics = ClangFormat::new().dry_run("int  a;\n");
+    /// assert!(diagnostics.is_ok());
+    /// let diagnostics = diagnostics.unwrap();
+    /// assert_eq!(diagnostics.len(), 1);
+    /// assert_eq!((diagnostics[0].line, diagnostics[0].column), (1, 4));
+    ///
+    /// assert_eq!(ClangFormat::new().dry_run("int a;\n").unwrap(), vec![]);
+    /// # }
+    /// ```
+    pub fn dry_run(&self, input: &str) -> Result<Vec<Diagnostic>, ClangFormatError> {
+        self.check_input_size(input.len())?;
+        self.check_version()?;
+
+        let mut command = self.command(&["--dry-run".to_string(), "--Werror".to_string()])?;
+        command.stderr(Stdio::piped());
+        let output = self.execute(&mut command, input.as_bytes())?;
+
+        let diagnostics = diagnostic::parse(&String::from_utf8_lossy(&output.stderr));
+        self.check_violations_output(&command, &output, !diagnostics.is_empty())?;
+        Ok(diagnostics)
+    }
+
+    /// Check whether the file at the given path needs formatting without reading it into memory
+    ///
+    /// The file is streamed into `clang-format --dry-run` and the result is based on the exit
+    /// status, so memory use stays flat when checking many large files. Unless a filename is
+    /// assumed, the path of the file is used as the assumed filename.
+    ///
+    /// clang-format stops after the first violation, use `ClangFormat::dry_run` to find all of them.
+    /// As with `ClangFormat::dry_run`, clang-format failing for any other reason than a violation
+    /// is an error.
+    ///
+    /// Since clang-format 10, which added `--ferror-limit`
+    pub fn file_needs_formatting(&self, path: impl AsRef<Path>) -> Result<bool, ClangFormatError> {
+        let path = path.as_ref();
+        let assumed;
+        let format = match self.assume_filename {
+            Some(_) => self,
+            None => {
+                assumed = self.clone().assume_filename(path);
+                &assumed
+            }
+        };
+
+        let mut input = File::open(path)?;
+        format.check_input_size(usize::try_from(input.metadata()?.len()).unwrap_or(usize::MAX))?;
+        format.check_version()?;
+
+        let mut command = format.command(&[
+            "--dry-run".to_string(),
+            "--Werror".to_string(),
+            "--ferror-limit=1".to_string(),
+        ])?;
+        command.stderr(Stdio::piped());
+        let output = match &format.runner {
+            Some(runner) => {
+                // Runners work with bytes, so the file cannot be given to clang-format directly
+                let mut input_bytes = vec![];
+                input.read_to_end(&mut input_bytes)?;
+                runner.run(&mut command, &input_bytes)?
+            }
+            None => {
+                command.stdin(input).stdout(Stdio::null());
+                spawn(&mut command)?.wait_with_output()?
+            }
+        };
+
+        if output.status.success() {
+            return Ok(false);
+        }
+        let violations = !diagnostic::parse(&String::from_utf8_lossy(&output.stderr)).is_empty();
+        format.check_violations_output(&command, &output, violations)
+    }
+
+    /// Check the output of `--dry-run --Werror`, returning whether there are violations
+    ///
+    /// clang-format exits with code 1 when there are violations, so this is not an error if
+    /// violations were reported. clang-format also exits with code 1 for errors such as an
+    /// invalid style, which report no violations, and other codes or signals are crashes,
+    /// these are reported as errors even if violations were reported before.
+    fn check_violations_output(
+        &self,
+        command: &Command,
+        output: &Output,
+        violations: bool,
+    ) -> Result<bool, ClangFormatError> {
+        if violations && output.status.code() == Some(1) {
+            return Ok(true);
+        }
+        self.check_output(command, output)?;
+        Ok(false)
+    }
+
+    /// Execute clang-format with the given input and count the lines which would change
+    ///
+    /// Each changed region counts the larger of its input and output line counts,
+    /// so a line which is split into two lines counts as two.
+    pub fn changed_line_count(&self, input: &str) -> Result<usize, ClangFormatError> {
+        let output = self.format(input)?;
+        Ok(diff::changed_line_count(input, &output))
+    }
+
+    /// Execute clang-format with the given input as bytes and collect the output as bytes
+    ///
+    /// With the `encoding` feature, when an encoding is set or detected the input is decoded to
+    /// UTF-8 before formatting and the output is encoded back. Otherwise the bytes are passed
+    /// through unchanged.
+    pub fn format_bytes(&self, input: &[u8]) -> Result<Vec<u8>, ClangFormatError> {
+        #[cfg(feature = "encoding")]
+        if let Some(encoding) = self.input_encoding(input) {
+            let output = self.format_with_args(&encoding.decode(input), &[])?;
+            return Ok(encoding.encode(&output));
+        }
+        self.run(input, &[])
+    }
+
+    /// Execute clang-format with the contents of the buffer and replace them with the output
+    ///
+    /// The bytes are handled as in `ClangFormat::format_bytes`. The buffer takes the allocation
+    /// of the output, so the output is not copied, and if formatting fails it is left unchanged.
+    pub fn format_bytes_in_place(&self, buf: &mut Vec<u8>) -> Result<(), ClangFormatError> {
+        *buf = self.format_bytes(buf)?;
+        Ok(())
+    }
+
+    /// Execute clang-format with the given input and extra arguments, and collect the output
+    pub(crate) fn format_with_args(
+        &self,
+        input: &str,
+        args: &[String],
+    ) -> Result<String, ClangFormatError> {
+        Ok(String::from_utf8(self.run(input.as_bytes(), args)?)?)
+    }
+
+    /// Execute clang-format with the given input and extra arguments, and collect stdout without post-processing
+    fn format_with_args_unprocessed(
+        &self,
+        input: &str,
+        args: &[String],
+    ) -> Result<String, ClangFormatError> {
+        let output = self.run_collect_unprocessed(input.as_bytes(), args, false)?;
+        Ok(String::from_utf8(output.stdout)?)
+    }
+
+    /// Execute clang-format with the given input, passing the output to the callback as it arrives
+    ///
+    /// This allows for writing the output of very large inputs directly to a file or socket,
+    /// rather than collecting all of it in memory. If the callback returns an error the
+    /// clang-format process is killed and the error is returned.
+    ///
+    /// Note that the bytes are passed through unchanged, any encoding is not applied.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use clang_format::ClangFormat;
+    /// # fn main() {
+    /// let mut output = vec![];
+    /// let result = ClangFormat::new().format_streaming(b"int  a;\n", |chunk| {
+    ///     output.extend_from_slice(chunk);
+    ///     Ok(())
+    /// });
+    /// assert!(result.is_ok());
+    /// assert_eq!(output, b"int a;\n");
+    /// # }
+    /// ```
+    pub fn format_streaming(
+        &self,
+        input: &[u8],
+        on_chunk: impl FnMut(&[u8]) -> io::Result<()>,
+    ) -> Result<(), ClangFormatError> {
+        self.run_streaming(input, &[], false, on_chunk)?;
+        Ok(())
+    }
+
+    /// Execute clang-format with the given input, passing each line of the output to the callback as it arrives
+    ///
+    /// This is `ClangFormat::format_streaming` for consumers which process the output a line
+    /// at a time, such as a downstream process in a pipeline. Each line is passed once its
+    /// newline has been read, without its `\n` or `\r\n` terminator, and a final line without
+    /// a newline is passed once clang-format exits. If the callback returns an error, or a
+    /// line is not valid UTF-8, the clang-format process is killed and the error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use clang_format::ClangFormat;
+    /// # fn main() {
+    /// let mut lines = vec![];
+    /// let result = ClangFormat::new().format_lines_streaming("int  a;\nint  b;\n", |line| {
+    ///     lines.push(line.to_string());
+    ///     Ok(())
+    /// });
+    /// assert!(result.is_ok());
+    /// assert_eq!(lines, ["int a;", "int b;"]);
+    /// # }
+    /// ```
+    pub fn format_lines_streaming(
+        &self,
+        input: &str,
+        mut on_line: impl FnMut(&str) -> io::Result<()>,
+    ) -> Result<(), ClangFormatError> {
+        fn emit(line: &[u8], on_line: &mut impl FnMut(&str) -> io::Result<()>) -> io::Result<()> {
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            let line = std::str::from_utf8(line)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            on_line(line)
+        }
+
+        // Bytes after the last newline, which are the start of the next line
+        let mut pending = vec![];
+        self.run_streaming(input.as_bytes(), &[], false, |chunk| {
+            pending.extend_from_slice(chunk);
+            let mut start = 0;
+            while let Some(len) = pending[start..].iter().position(|byte| *byte == b'\n') {
+                emit(&pending[start..start + len], &mut on_line)?;
+                start += len + 1;
+            }
+            pending.drain(..start);
+            Ok(())
+        })?;
+        if !pending.is_empty() {
+            emit(&pending, &mut on_line)?;
+        }
+        Ok(())
+    }
+
+    /// Execute clang-format on the file at the given path, replacing it with the formatted output
+    ///
+    /// The file is streamed into clang-format and the output is streamed into a temporary
+    /// file next to it, or in the directory set with `ClangFormat::temp_dir`, which then
+    /// replaces the original. This avoids holding the whole file in memory and leaves the
+    /// original untouched if formatting fails.
+    ///
+    /// The path is passed to `--assume-filename` so that the language and `.clang-format` file
+    /// are found from it, unless another filename is set with `ClangFormat::assume_filename`.
+    /// This allows for formatting a file such as `generated.tmp` as if it was `generated.cpp`.
+    ///
+    /// With `ClangFormat::encoding` or `ClangFormat::auto_encoding` the file is read into memory
+    /// and formatted with `ClangFormat::format_bytes`, so that its encoding is restored.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use clang_format::ClangFormat;
+    /// # fn main() {
+    /// let result = ClangFormat::new()
+    ///     .assume_filename("src/generated.cpp")
+    ///     .format_file_streamed("src/generated.tmp");
+    /// assert!(result.is_ok());
+    /// # }
+    /// ```
+    pub fn format_file_streamed(&self, path: impl AsRef<Path>) -> Result<(), ClangFormatError> {
+        let path = path.as_ref();
+        let assumed;
+        let format = match self.assume_filename {
+            Some(_) => self,
+            None => {
+                assumed = self.clone().assume_filename(path);
+                &assumed
+            }
+        };
+
+        let mut file_name = OsString::from(".");
+        file_name.push(path.file_name().unwrap_or_default());
+        file_name.push(format!(
+            ".{}.{}.tmp",
+            process::id(),
+            TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let temp_path = match &self.temp_dir {
+            Some(dir) => dir.join(file_name),
+            None => path.with_file_name(file_name),
+        };
+        // Never write to a file which already exists, as it is not ours to remove afterwards
+        let temp_file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temp_path)?;
+
+        let result = (|| {
+            let input = File::open(path)?;
+            let metadata = input.metadata()?;
+            format.check_input_size(usize::try_from(metadata.len()).unwrap_or(usize::MAX))?;
+            let permissions = metadata.permissions();
+            // The encoding can only be detected and converted with the whole file in memory
+            #[cfg(feature = "encoding")]
+            let decode = self.encoding.is_some();
+            #[cfg(feature = "auto_encoding")]
+            let decode = decode || self.auto_encoding;
+            #[cfg(feature = "encoding")]
+            if decode {
+                let mut bytes = vec![];
+                let mut input = input;
+                input.read_to_end(&mut bytes)?;
+                let mut temp_file = temp_file;
+                temp_file.write_all(&format.format_bytes(&bytes)?)?;
+            } else {
+                format.stream(input, temp_file)?;
+            }
+            #[cfg(not(feature = "encoding"))]
+            format.stream(input, temp_file)?;
+            fs::set_permissions(&temp_path, permissions)?;
+            if let Err(err) = fs::rename(&temp_path, path) {
+                // A temporary directory may be on another filesystem, where renaming fails
+                if self.temp_dir.is_none() {
+                    return Err(err.into());
+                }
+                fs::copy(&temp_path, path)?;
+                fs::remove_file(&temp_path)?;
+            }
+            Ok(())
+        })();
+
+        // Ensure that the temporary file does not linger if anything failed
+        if result.is_err() {
+            let _ = fs::remove_file(&temp_path);
+        }
+        result
+    }
+
+    /// Execute clang-format in place on each of the files listed in the given file
+    ///
+    /// The list contains one path per line, as produced by many build systems, and is passed
+    /// to clang-format with `--files` and `-i`. Relative paths are relative to the working
+    /// directory of clang-format, see `ClangFormat::current_dir`.
+    ///
+    /// Rather than clang-format aborting the whole run, paths which do not exist are skipped
+    /// and returned. In that case the remaining paths are written to a temporary list in the
+    /// directory set with `ClangFormat::temp_dir`.
+    ///
+    /// Since clang-format 16
+    pub fn format_filelist(
+        &self,
+        list_path: impl AsRef<Path>,
+    ) -> Result<Vec<PathBuf>, ClangFormatError> {
+        let list_path = list_path.as_ref();
+        let list = fs::read_to_string(list_path)?;
+        let (existing, missing): (Vec<&str>, Vec<&str>) = list
+            .lines()
+            .map(str::trim)
+            .filter(|path| !path.is_empty())
+            .partition(|path| match &self.current_dir {
+                Some(dir) => dir.join(path).exists(),
+                None => Path::new(path).exists(),
+            });
+        let missing: Vec<PathBuf> = missing.into_iter().map(PathBuf::from).collect();
+        if existing.is_empty() {
+            return Ok(missing);
+        }
+
+        let filtered_path = if missing.is_empty() {
+            None
+        } else {
+            let dir = self.temp_dir.clone().unwrap_or_else(env::temp_dir);
+            let path = dir.join(format!(
+                "clang-format-rs-files.{}.{}.txt",
+                process::id(),
+                TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+            ));
+            write_new_file(&path, (existing.join("\n") + "\n").as_bytes())?;
+            Some(path)
+        };
+
+        let result = (|| {
+            let mut arg = OsString::from("--files=");
+            arg.push(filtered_path.as_deref().unwrap_or(list_path));
+            self.check_version()?;
+            let mut command = self.command(&["-i".to_string()])?;
+            command.arg(arg);
+            let output = self.execute(&mut command, &[])?;
+            self.check_output(&command, &output)
+        })();
+
+        if let Some(path) = filtered_path {
+            let _ = fs::remove_file(path);
+        }
+        result.map(|_| missing)
+    }
+
+    /// Create the command for formatting with the binary, directory, and arguments of this builder
+    fn command(&self, args: &[String]) -> Result<Command, ClangFormatError> {
+        self.command_with(args, true)
+    }
+
+    /// Create the command with the binary, directory, and arguments of this builder
+    ///
+    /// The fallback style is only resolved and passed when formatting.
+    fn command_with(&self, args: &[String], formatting: bool) -> Result<Command, ClangFormatError> {
+        let mut command = self.binary_command();
+        if let Some(dir) = &self.current_dir {
+            command.current_dir(dir);
+        }
+        if let Some(locale) = &self.locale {
+            command.env("LC_ALL", locale).env("LANG", locale);
+        }
+        if self.low_priority {
+            lower_priority(&mut command);
+        }
+        if self.cancel.is_some() {
+            cancel::new_process_group(&mut command);
+        }
+        if let Some(alignment) = self.qualifier_alignment {
+            command.arg(format!("--qualifier-alignment={}", alignment.as_str()));
+        }
+        if let Some(filename) = &self.assume_filename {
+            let mut arg = OsString::from("--assume-filename=");
+            arg.push(filename);
+            command.arg(arg);
+        }
+        let compatible_style;
+        let mut style = self.effective_style();
+        if let Some(version) = self.compat_version {
+            compatible_style = style.compatible_with(version);
+            style = &compatible_style;
+        }
+        if let Some(path) = &self.style_file {
+            let mut arg = OsString::from("--style=file:");
+            arg.push(path);
+            command.arg(arg);
+        } else if *style != ClangFormatStyle::Unspecified {
+            command.arg(format!("--style={}", style.as_str()));
+        }
+        for category in &self.no_error_categories {
+            command.arg(format!("--Wno-error={category}"));
+        }
+        if formatting {
+            let fallback_style = resolve_fallback_style(
+                self.fallback_style.as_ref(),
+                env::var_os("CLANG_FORMAT_FALLBACK_STYLE"),
+            )?;
+            if let Some(fallback_style) = fallback_style {
+                command.arg(format!("--fallback-style={}", fallback_style.as_str()));
+            }
+        }
+        command
+            .args(args)
+            .stderr(if self.strict || self.reject_diagnostics {
+                Stdio::piped()
+            } else {
+                Stdio::inherit()
+            });
+        Ok(command)
+    }
+
+    /// Check that the input is within the size limit, if any
+    fn check_input_size(&self, size: usize) -> Result<(), ClangFormatError> {
+        match self.max_input_bytes {
+            Some(limit) if size > limit => Err(ClangFormatError::InputTooLarge { size, limit }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check that the version of clang-format is parseable and supports the style, if requested
+    fn check_version(&self) -> Result<(), ClangFormatError> {
+        let required_version = self
+            .effective_style()
+            .required_version()
+            .filter(|_| self.verify_style_support);
+        if required_version.is_none() && !self.require_parseable_version {
+            return Ok(());
+        }
+
+        match (self.version(), required_version) {
+            (Ok(found_version), Some(required_version)) if found_version < required_version => {
+                Err(ClangFormatError::UnsupportedStyle {
+                    style: self.effective_style().clone(),
+                    required_version,
+                    found_version,
+                })
+            }
+            // Let clang-format report any problems if the version is not known
+            (Err(ClangFormatError::UnparseableVersion(_)), _)
+                if !self.require_parseable_version =>
+            {
+                Ok(())
+            }
+            (Ok(_), _) => Ok(()),
+            (Err(err), _) => Err(err),
+        }
+    }
+
+    /// Check the diagnostics and exit status of a finished clang-format process
+    fn check_output(&self, command: &Command, output: &Output) -> Result<(), ClangFormatError> {
+        // Unknown keys are reported regardless of whether clang-format treats them as an error
+        if self.strict {
+            if let Some(key) = unknown_style_key(&String::from_utf8_lossy(&output.stderr)) {
+                return Err(ClangFormatError::UnknownStyleKey(key));
+            }
+        }
+
+        // TODO: use exit_ok() once it is a stable feature
+        // https://doc.rust-lang.org/stable/std/process/struct.ExitStatus.html#method.exit_ok
+        // https://github.com/rust-lang/rust/issues/84908
+        if !output.status.success() {
+            if self.strict {
+                if let Some(error) =
+                    diagnostic::parse_style_error(&String::from_utf8_lossy(&output.stderr))
+                {
+                    return Err(ClangFormatError::InvalidStyle(error));
+                }
+            }
+            return Err(ClangFormatError::NonZeroExitStatus {
+                command: CommandLine::new(command),
+                status: output.status,
+            });
+        }
+
+        if self.reject_diagnostics && !output.stderr.is_empty() {
+            return Err(ClangFormatError::Diagnostics(
+                String::from_utf8_lossy(&output.stderr).into_owned(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Execute clang-format with the given input bytes and extra arguments, and collect stdout
+    fn run(&self, input: &[u8], args: &[String]) -> Result<Vec<u8>, ClangFormatError> {
+        self.run_collect(input, args, false)
+            .map(|output| output.stdout)
+    }
+
+    /// Execute clang-format with the given input bytes and extra arguments, and collect the formatted output and stderr
+    ///
+    /// Unless stderr is captured, it is only collected when it is piped for checking the output.
+    fn run_collect(
+        &self,
+        input: &[u8],
+        args: &[String],
+        capture_stderr: bool,
+    ) -> Result<CollectedOutput, ClangFormatError> {
+        let mut output = self.run_collect_unprocessed(input, args, capture_stderr)?;
+        self.post_process(input, &mut output.stdout);
+        Ok(output)
+    }
+
+    /// Execute clang-format with the given input bytes and extra arguments, and collect stdout and stderr as is
+    ///
+    /// Unlike `run_collect` the output is not post-processed, so it can be other than formatted code.
+    fn run_collect_unprocessed(
+        &self,
+        input: &[u8],
+        args: &[String],
+        capture_stderr: bool,
+    ) -> Result<CollectedOutput, ClangFormatError> {
+        let mut stdout = vec![];
+        let mut first_chunk = None;
+        let stderr = self.run_streaming(input, args, capture_stderr, |chunk| {
+            first_chunk.get_or_insert_with(Instant::now);
+            stdout.extend_from_slice(chunk);
+            Ok(())
+        })?;
+
+        if self.reject_empty_output
+            && stdout.is_empty()
+            && !input.iter().all(u8::is_ascii_whitespace)
+        {
+            return Err(ClangFormatError::UnexpectedEmptyOutput);
+        }
+
+        Ok(CollectedOutput {
+            stdout,
+            stderr,
+            first_chunk,
+        })
+    }
+
+    /// Apply `ClangFormat::normalize_crlf` and `ClangFormat::match_input_final_newline` to the formatted output
+    ///
+    /// This is only for plain formatted output, byte offsets in other output such as
+    /// the `--cursor` header or `--output-replacements-xml` would no longer be correct.
+    fn post_process(&self, input: &[u8], output: &mut Vec<u8>) {
+        if self.normalize_crlf {
+            *output = normalize_crlf(output);
+        }
+        if self.match_input_final_newline {
+            match_final_newline(input, output);
+        }
+    }
+
+    /// Execute clang-format with the given input bytes and extra arguments, passing chunks of stdout to the callback
+    ///
+    /// Returns stderr, which is empty unless it is captured or piped for checking the output.
+    fn run_streaming(
+        &self,
+        input: &[u8],
+        args: &[String],
+        capture_stderr: bool,
+        mut on_chunk: impl FnMut(&[u8]) -> io::Result<()>,
+    ) -> Result<Vec<u8>, ClangFormatError> {
+        self.check_input_size(input.len())?;
+        self.check_version()?;
+
+        // Stop reading once the output exceeds the limit, which kills clang-format
+        let mut output_bytes = 0;
+        let mut output_too_large = false;
+        let mut on_chunk = |chunk: &[u8]| {
+            output_bytes += chunk.len();
+            if self
+                .max_output_bytes
+                .is_some_and(|limit| output_bytes > limit)
+            {
+                output_too_large = true;
+                return Err(io::Error::other("output too large"));
+            }
+            on_chunk(chunk)
+        };
+
+        let mut command = self.command(args)?;
+        if capture_stderr {
+            command.stderr(Stdio::piped());
+        }
+        let result = match &self.runner {
+            Some(runner) => runner.run(&mut command, input).and_then(|output| {
+                #[cfg(feature = "debug_log")]
+                if let Some(log) = &self.log {
+                    let mut log = log.lock().expect("process log poisoned");
+                    for (stream, bytes) in [
+                        (LogStream::Stdout, &output.stdout),
+                        (LogStream::Stderr, &output.stderr),
+                    ] {
+                        if !bytes.is_empty() {
+                            log.record(stream, bytes);
+                        }
+                    }
+                }
+                if !output.stdout.is_empty() {
+                    on_chunk(&output.stdout)?;
+                }
+                Ok(output)
+            }),
+            None => runner::run_process(&mut command, input, self.process_options(), &mut on_chunk),
+        };
+
+        match (result, self.max_output_bytes) {
+            (Err(_), Some(limit)) if output_too_large => {
+                Err(ClangFormatError::OutputTooLarge { limit })
+            }
+            (result, _) => {
+                let output = result?;
+                self.check_output(&command, &output)?;
+                Ok(output.stderr)
+            }
+        }
+    }
+
+    /// Execute clang-format reading from and writing to the given files
+    fn stream(&self, input: File, output: File) -> Result<(), ClangFormatError> {
+        self.check_version()?;
+
+        let mut command = self.command(&[])?;
+        if let Some(runner) = &self.runner {
+            // Runners work with bytes, so the files cannot be given to clang-format directly
+            let mut input_bytes = vec![];
+            let mut input = input;
+            input.read_to_end(&mut input_bytes)?;
+            let result = runner.run(&mut command, &input_bytes)?;
+            self.check_output(&command, &result)?;
+            let mut output = output;
+            output.write_all(&result.stdout)?;
+            return Ok(());
+        }
+
+        command.stdin(input).stdout(output);
+        let output = spawn(&mut command)?.wait_with_output()?;
+        self.check_output(&command, &output)
+    }
+
+    /// Execute the command with the runner of this builder, collecting the output
+    fn execute(&self, command: &mut Command, input: &[u8]) -> Result<Output, ClangFormatError> {
+        match &self.runner {
+            Some(runner) => runner.run(command, input),
+            None => runner::run_process_collect(command, input, self.process_options()),
+        }
+    }
+
+    /// How to communicate with a spawned clang-format process
+    fn process_options(&self) -> ProcessOptions<'_> {
+        ProcessOptions {
+            write_chunk_size: self
+                .write_chunk_size
+                .unwrap_or(runner::DEFAULT_WRITE_CHUNK_SIZE),
+            cancel: self.cancel.as_deref(),
+            #[cfg(feature = "debug_log")]
+            log: self.log.as_deref(),
+        }
+    }
+
+    /// A warning for each key of the style which is removed by `ClangFormat::compat_version`
+    fn compat_warnings(&self) -> Vec<String> {
+        let Some(version) = self.compat_version else {
+            return vec![];
+        };
+        self.effective_style()
+            .unsupported_keys(version)
+            .into_iter()
+            .map(|key| {
+                format!("warning: removed style option {key}, which is not supported by clang-format {version}")
+            })
+            .collect()
+    }
+
+    /// The style to execute with, preferring the builder over the registered default style
+    fn effective_style(&self) -> &ClangFormatStyle {
+        const DEFAULT: &ClangFormatStyle = &ClangFormatStyle::Default;
+
+        self.style
+            .as_ref()
+            .or_else(|| DEFAULT_STYLE.get())
+            .unwrap_or(DEFAULT)
+    }
+
+    /// Create a command for the binary, executed through the command prefix if there is one
+    fn binary_command(&self) -> Command {
+        match self.command_prefix.split_first() {
+            Some((launcher, args)) => {
+                let mut command = Command::new(launcher);
+                command.args(args).arg(self.binary_path());
+                command
+            }
+            None => Command::new(self.binary_path()),
+        }
+    }
+
+    /// The binary to execute, preferring the builder over the environment
+    ///
+    /// A binary name without any directory is first searched for in the search paths.
+    fn binary_path(&self) -> OsString {
+        let binary = match &self.binary {
+            Some(binary) => binary.clone().into_os_string(),
+            None => env::var_os("CLANG_FORMAT_BINARY").unwrap_or_else(|| "clang-format".into()),
+        };
+
+        if Path::new(&binary).components().count() != 1 {
+            return binary;
+        }
+        self.search_paths
+            .iter()
+            .flat_map(|dir| {
+                let path = dir.join(&binary);
+                let mut exe = path.clone().into_os_string();
+                exe.push(env::consts::EXE_SUFFIX);
+                [path, PathBuf::from(exe)]
+            })
+            .find(|path| path.is_file())
+            .map(|path| path.into_os_string())
+            .unwrap_or(binary)
+    }
+}
+
+/// The collected output of a clang-format process
+struct CollectedOutput {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    /// When the first chunk of stdout was read, if there was any output
+    first_chunk: Option<Instant>,
+}
+
+/// Replace each `\r\n` with `\n`
+fn normalize_crlf(bytes: &[u8]) -> Vec<u8> {
+    let mut normalized = Vec::with_capacity(bytes.len());
+    for (index, byte) in bytes.iter().enumerate() {
+        if *byte != b'\r' || bytes.get(index + 1) != Some(&b'\n') {
+            normalized.push(*byte);
+        }
+    }
+    normalized
+}
+
+/// Add or remove the final newline of the output so that it matches the input
+fn match_final_newline(input: &[u8], output: &mut Vec<u8>) {
+    match (input.ends_with(b"\n"), output.ends_with(b"\n")) {
+        (false, true) => {
+            output.pop();
+            if output.ends_with(b"\r") {
+                output.pop();
+            }
+        }
+        (true, false) if !output.is_empty() => {
+            if input.ends_with(b"\r\n") {
+                output.push(b'\r');
+            }
+            output.push(b'\n');
+        }
+        _ => {}
+    }
+}
+
+/// Write the contents to a new temporary file
+///
+/// Never write to a file which already exists, as it is not ours to truncate or remove.
+fn write_new_file(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut file = OpenOptions::new().write(true).create_new(true).open(path)?;
+    if let Err(err) = file.write_all(contents) {
+        let _ = fs::remove_file(path);
+        return Err(err);
+    }
+    Ok(())
+}
+
+/// Increase the niceness of the process spawned by the command by 10, up to the maximum of 19
+#[cfg(unix)]
+fn lower_priority(command: &mut Command) {
+    use crate::sys::{getpriority, setpriority, PRIO_PROCESS};
+    use std::os::unix::process::CommandExt;
+
+    // SAFETY: getpriority only reads the niceness of this process, which the child inherits.
+    // It cannot fail for the current process, so -1 is a niceness rather than an error.
+    let niceness = unsafe { getpriority(PRIO_PROCESS, 0) };
+    let niceness = niceness.saturating_add(10).min(19);
+    // SAFETY: setpriority is not on the POSIX list of async-signal-safe functions, but it is a
+    // thin wrapper of the system call which does not allocate or take locks, unlike nice which
+    // reads and clears errno in glibc. It only changes the priority of the child process.
+    // Failing to change the priority is not an error, so the result is ignored.
+    unsafe {
+        command.pre_exec(move || {
+            setpriority(PRIO_PROCESS, 0, niceness);
+            Ok(())
+        });
+    }
+}
+
+/// Changing the priority is only supported on Unix
+#[cfg(not(unix))]
+fn lower_priority(_command: &mut Command) {}
+
+/// Choose the fallback style from the builder, otherwise from the environment variable
+///
+/// When neither is set no fallback style is passed, so that clang-format uses its default.
+/// clang-format only accepts the name of a built-in style or `none`, any other style results
+/// in `ClangFormatError::UnknownStyleName`.
+fn resolve_fallback_style(
+    explicit: Option<&ClangFormatStyle>,
+    env: Option<OsString>,
+) -> Result<Option<ClangFormatStyle>, ClangFormatError> {
+    let style = match (explicit, env) {
+        (Some(style), _) => style.clone(),
+        (None, Some(env)) => env.to_string_lossy().parse()?,
+        (None, None) => return Ok(None),
+    };
+    match &style {
+        ClangFormatStyle::Custom(custom) if custom.trim().eq_ignore_ascii_case("none") => {
+            Ok(Some(ClangFormatStyle::Custom("none".to_string())))
+        }
+        _ => ClangFormatStyle::named()
+            .into_iter()
+            .filter(|named| *named != ClangFormatStyle::File)
+            .find(|named| named.canonical_name() == style.canonical_name())
+            .map(Some)
+            .ok_or_else(|| {
+                ClangFormatError::UnknownStyleName(match style {
+                    ClangFormatStyle::Custom(custom) => custom,
+                    other => other.canonical_name().to_string(),
+                })
+            }),
+    }
+}
+
+/// Check whether the flag is listed in the output of `clang-format --help`
+///
+/// Flags are listed with one or two leading dashes, eg `--style=<string>` or `-i`.
+fn help_mentions_flag(help: &str, flag: &str) -> bool {
+    let name = flag.trim_start_matches('-');
+    if name.is_empty() {
+        return false;
+    }
+
+    help.match_indices(name).any(|(index, _)| {
+        // The name must be directly after the leading dashes of a flag
+        let before = &help[..index];
+        let before_dashes = before.trim_end_matches('-');
+        let preceded_by_dashes = before_dashes.len() < before.len()
+            && before_dashes
+                .chars()
+                .next_back()
+                .is_none_or(|c| !c.is_ascii_alphanumeric());
+        let followed_by_boundary = help[index + name.len()..]
+            .chars()
+            .next()
+            .is_none_or(|c| !c.is_ascii_alphanumeric() && c != '-' && c != '_');
+        preceded_by_dashes && followed_by_boundary
+    })
+}
+
+/// Find the names of the predefined styles in the output of `clang-format --help`
+///
+/// These are listed in the description of `--style` in the form `A preset: LLVM, GNU, ...`,
+/// or `Coding style, currently supports: LLVM, Google, ...` before clang-format 14.
+fn help_builtin_styles(help: &str) -> Option<Vec<String>> {
+    let start = ["A preset:", "currently supports:"]
+        .into_iter()
+        .find_map(|needle| help.find(needle).map(|index| index + needle.len()))?;
+    let list = &help[start..];
+    let list = &list[..list.find('.')?];
+    let styles: Vec<String> = list
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect();
+    if styles.is_empty() {
+        None
+    } else {
+        Some(styles)
+    }
+}
+
+/// Find the first key reported as unknown in the diagnostics of clang-format
+///
+/// These are in the form `YAML:1:3: error: unknown key 'IndentWidht'`
+fn unknown_style_key(stderr: &str) -> Option<String> {
+    const NEEDLE: &str = "unknown key '";
+
+    stderr.lines().find_map(|line| {
+        let start = line.find(NEEDLE)? + NEEDLE.len();
+        let len = line[start..].find('\'')?;
+        Some(line[start..start + len].to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{ConcurrencyRunner, ScriptedRunner, UppercaseRunner};
+    use std::ffi::OsStr;
+
+    #[test]
+    fn parse_help_flags() {
+        let help = "  --dry-run                      - If set, do not actually make the formatting changes\n  --qualifier-alignment=<string> - If set, overrides the qualifier alignment style\n  -i                             - Inplace edit <file>s, if specified.\n";
+        assert!(help_mentions_flag(help, "--dry-run"));
+        assert!(help_mentions_flag(help, "qualifier-alignment"));
+        assert!(help_mentions_flag(help, "-i"));
+        assert!(!help_mentions_flag(help, "--dry"));
+        assert!(!help_mentions_flag(help, "--alignment"));
+        assert!(!help_mentions_flag(help, "--"));
+    }
+
+    #[test]
+    fn parse_help_builtin_styles() {
+        let help = "  --style=<string>               - Set coding style. <string> can be:\n                                   1. A preset: LLVM, GNU, Google, Chromium, Microsoft,\n                                      Mozilla, WebKit.\n                                   2. 'file' to load style configuration from a\n";
+        assert_eq!(
+            help_builtin_styles(help).unwrap(),
+            [
+                "LLVM",
+                "GNU",
+                "Google",
+                "Chromium",
+                "Microsoft",
+                "Mozilla",
+                "WebKit"
+            ]
+        );
+
+        let help = "  -style=<string>            - Coding style, currently supports:\n                               LLVM, Google, Chromium, Mozilla, WebKit.\n                             Use -style=file to load style configuration from\n";
+        assert_eq!(
+            help_builtin_styles(help).unwrap(),
+            ["LLVM", "Google", "Chromium", "Mozilla", "WebKit"]
+        );
+
+        assert_eq!(
+            help_builtin_styles(
+                "  --dry-run - If set, do not actually make the formatting changes\n"
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn parse_unknown_style_key() {
+        let stderr = "YAML:1:17: error: unknown key 'IndentWidht'\n{ BasedOnStyle: LLVM, IndentWidht: 8 }\n                ^~~~~~~~~~~\nError parsing -style: Invalid argument\n";
+        assert_eq!(unknown_style_key(stderr), Some("IndentWidht".to_string()));
+        assert_eq!(unknown_style_key(""), None);
+    }
+
+    #[test]
+    fn format_max_input_bytes() {
+        // The binary is never spawned when the input is too large
+        let format = ClangFormat::new()
+            .binary("/nonexistent/clang-format")
+            .max_input_bytes(8);
+        assert!(matches!(
+            format.format("struct Test {};\n"),
+            Err(ClangFormatError::InputTooLarge { size: 16, limit: 8 })
+        ));
+        assert!(matches!(
+            format.format("int a;\n"),
+            Err(ClangFormatError::SpawnFailure { .. })
+        ));
+    }
+
+    #[test]
+    fn format_max_output_bytes() {
+        let format = ClangFormat::new()
+            .runner(Arc::new(ScriptedRunner::echo()))
+            .max_output_bytes(8);
+        assert!(matches!(
+            format.format("struct Test {};\n"),
+            Err(ClangFormatError::OutputTooLarge { limit: 8 })
+        ));
+        assert_eq!(format.format("int a;\n").unwrap(), "int a;\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn command_low_priority() {
+        // Run the nice utility in place of clang-format, which prints the niceness it runs with
+        let niceness = |low_priority| {
+            let format = ClangFormat::new()
+                .binary("sh")
+                .style(ClangFormatStyle::Unspecified)
+                .low_priority(low_priority);
+            let mut command = format
+                .command(&["-c".to_string(), "nice".to_string()])
+                .unwrap();
+            let output = format.execute(&mut command, &[]).unwrap();
+            String::from_utf8(output.stdout)
+                .unwrap()
+                .trim()
+                .parse::<i32>()
+                .unwrap()
+        };
+        let normal = niceness(false);
+        assert_eq!(niceness(true), (normal + 10).min(19));
+    }
+
+    #[test]
+    fn binary_search_paths() {
+        let dir = env::temp_dir().join(format!("clang-format-rs-search-{}", process::id()));
+        let empty = dir.join("empty");
+        let bin = dir.join("bin");
+        fs::create_dir_all(&empty).unwrap();
+        fs::create_dir_all(&bin).unwrap();
+        let binary = bin.join(format!("clang-format-16{}", env::consts::EXE_SUFFIX));
+        fs::write(&binary, "").unwrap();
+
+        let format = ClangFormat::new().search_paths(&[empty.clone(), bin.clone()]);
+        let found = format.clone().binary("clang-format-16").binary_path();
+        let not_found = format.clone().binary("clang-format-17").binary_path();
+        let with_dir = format.binary("/usr/bin/clang-format-16").binary_path();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(found, binary);
+        assert_eq!(not_found, "clang-format-17");
+        assert_eq!(with_dir, "/usr/bin/clang-format-16");
+    }
+
+    #[test]
+    fn command_locale() {
+        let command = ClangFormat::new().locale("C.UTF-8").command(&[]).unwrap();
+        let envs: Vec<_> = command.get_envs().collect();
+        assert!(envs.contains(&(OsStr::new("LC_ALL"), Some(OsStr::new("C.UTF-8")))));
+        assert!(envs.contains(&(OsStr::new("LANG"), Some(OsStr::new("C.UTF-8")))));
+
+        let command = ClangFormat::new().command(&[]).unwrap();
+        assert_eq!(command.get_envs().count(), 0);
+    }
+
+    #[test]
+    fn command_line_style() {
+        let command_line = ClangFormat::new()
+            .binary("clang-format-16")
+            .style(ClangFormatStyle::Custom("{ IndentWidth: 8 }".to_string()))
+            .assume_filename("test.cpp")
+            .command_line()
+            .unwrap();
+        assert_eq!(command_line.binary, "clang-format-16");
+        assert_eq!(
+            command_line.args,
+            ["--assume-filename=test.cpp", "--style={ IndentWidth: 8 }"]
+        );
+        assert_eq!(command_line.style(), Some("{ IndentWidth: 8 }"));
+
+        let command_line = ClangFormat::new()
+            .style(ClangFormatStyle::Unspecified)
+            .command_line()
+            .unwrap();
+        assert_eq!(command_line.style(), None);
+    }
+
+    #[test]
+    fn command_prefix() {
+        let runner = Arc::new(ScriptedRunner::echo());
+        let format = ClangFormat::new()
+            .command_prefix(&["bazel".to_string(), "run".to_string(), "--".to_string()])
+            .binary("clang-format-16")
+            .style(ClangFormatStyle::Llvm)
+            .runner(runner.clone());
+        let command_line = format.command_line().unwrap();
+        assert_eq!(command_line.binary, "bazel");
+        assert_eq!(
+            command_line.args,
+            ["run", "--", "clang-format-16", "--style=LLVM"]
+        );
+
+        assert_eq!(format.format("int a;\n").unwrap(), "int a;\n");
+        assert_eq!(
+            runner.args()[0],
+            ["run", "--", "clang-format-16", "--style=LLVM"]
+        );
+    }
+
+    #[test]
+    fn command_run_raw() {
+        let runner = Arc::new(ScriptedRunner::echo());
+        let output = ClangFormat::new()
+            .style(ClangFormatStyle::Mozilla)
+            .assume_filename("test.cpp")
+            .runner(runner.clone())
+            .run_raw(&["-n".into(), "--Werror".into()], Some(b"int a;\n"))
+            .unwrap();
+        assert_eq!(output.stdout, b"int a;\n");
+        assert_eq!(runner.args(), [["-n", "--Werror"]]);
+    }
+
+    #[test]
+    fn command_style_layers() {
+        let format = ClangFormat::new().with_style_layers(&[
+            ClangFormatStyle::Custom("{ IndentWidth: 8 }".to_string()),
+            ClangFormatStyle::Llvm,
+            ClangFormatStyle::Default,
+        ]);
+        assert_eq!(
+            format.command_line().unwrap().style(),
+            Some("{ IndentWidth: 8, BasedOnStyle: LLVM }")
+        );
+
+        let format = ClangFormat::new()
+            .style(ClangFormatStyle::WebKit)
+            .with_style_layers(&[]);
+        assert_eq!(format.command_line().unwrap().style(), Some("WebKit"));
+    }
+
+    #[test]
+    fn command_style() {
+        let command = ClangFormat::new()
+            .style(ClangFormatStyle::Mozilla)
+            .command(&[])
+            .unwrap();
+        assert_eq!(command.get_args().collect::<Vec<_>>(), ["--style=Mozilla"]);
+
+        let command = ClangFormat::new()
+            .style(ClangFormatStyle::Unspecified)
+            .command(&[])
+            .unwrap();
+        assert_eq!(command.get_args().count(), 0);
+    }
+
+    #[test]
+    fn command_path_args() {
+        let command = |filename: &dyn AsRef<Path>| {
+            ClangFormat::new()
+                .style(ClangFormatStyle::Unspecified)
+                .assume_filename(filename)
+                .command(&[])
+                .unwrap()
+        };
+        for command in [
+            command(&"src/test.cpp"),
+            command(&"src/test.cpp".to_string()),
+            command(&PathBuf::from("src/test.cpp")),
+        ] {
+            assert_eq!(
+                command.get_args().collect::<Vec<_>>(),
+                ["--assume-filename=src/test.cpp"]
+            );
+        }
+    }
+
+    #[test]
+    fn command_fallback_style() {
+        let command = ClangFormat::new()
+            .style(ClangFormatStyle::File)
+            .fallback_style(ClangFormatStyle::Google)
+            .command(&[])
+            .unwrap();
+        assert_eq!(
+            command.get_args().collect::<Vec<_>>(),
+            ["--style=file", "--fallback-style=Google"]
+        );
+    }
+
+    #[test]
+    fn command_no_error_category() {
+        let command = ClangFormat::new()
+            .style(ClangFormatStyle::Unspecified)
+            .no_error_category("unknown")
+            .no_error_category("other".to_string())
+            .command(&[])
+            .unwrap();
+        assert_eq!(
+            command.get_args().collect::<Vec<_>>(),
+            ["--Wno-error=unknown", "--Wno-error=other"]
+        );
+    }
+
+    #[test]
+    fn fallback_style_precedence() {
+        let mozilla = ClangFormatStyle::Mozilla;
+        assert_eq!(
+            resolve_fallback_style(Some(&mozilla), Some("webkit".into())).unwrap(),
+            Some(ClangFormatStyle::Mozilla)
+        );
+        assert_eq!(
+            resolve_fallback_style(None, Some("webkit".into())).unwrap(),
+            Some(ClangFormatStyle::WebKit)
+        );
+        assert_eq!(resolve_fallback_style(None, None).unwrap(), None);
+        assert!(matches!(
+            resolve_fallback_style(None, Some("Mozzila".into())),
+            Err(ClangFormatError::UnknownStyleName(name)) if name == "Mozzila"
+        ));
+    }
+
+    #[test]
+    fn fallback_style_named_only() {
+        let google = ClangFormatStyle::Custom(" google ".to_string());
+        assert_eq!(
+            resolve_fallback_style(Some(&google), None).unwrap(),
+            Some(ClangFormatStyle::Google)
+        );
+        assert_eq!(
+            resolve_fallback_style(None, Some("None".into())).unwrap(),
+            Some(ClangFormatStyle::Custom("none".to_string()))
+        );
+        for style in [
+            ClangFormatStyle::Default,
+            ClangFormatStyle::Unspecified,
+            ClangFormatStyle::File,
+            ClangFormatStyle::Custom("{ IndentWidth: 8 }".to_string()),
+        ] {
+            assert!(matches!(
+                resolve_fallback_style(Some(&style), None),
+                Err(ClangFormatError::UnknownStyleName(_))
+            ));
+        }
+        assert!(matches!(
+            resolve_fallback_style(None, Some("{ IndentWidth: 8 }".into())),
+            Err(ClangFormatError::UnknownStyleName(name)) if name == "{ IndentWidth: 8 }"
+        ));
+
+        // The fallback is an error when formatting, but is not passed to queries
+        let format = ClangFormat::new().fallback_style("{ IndentWidth: 8 }");
+        assert!(matches!(
+            format.command(&[]),
+            Err(ClangFormatError::UnknownStyleName(_))
+        ));
+        let command = format
+            .command_with(&["--dump-config".to_string()], false)
+            .unwrap();
+        assert!(command
+            .get_args()
+            .all(|arg| !arg.to_string_lossy().starts_with("--fallback-style")));
+    }
+
+    #[test]
+    fn format_mock_runner() {
+        let runner = Arc::new(ScriptedRunner::echo());
+        let format = ClangFormat::new()
+            .binary("/nonexistent/clang-format")
+            .style(ClangFormatStyle::Mozilla)
+            .runner(runner.clone());
+        assert_eq!(format.format("int  a;\n").unwrap(), "int  a;\n");
+
+        let mut output = vec![];
+        let result = format.format_streaming(b"int b;\n", |chunk| {
+            output.extend_from_slice(chunk);
+            Ok(())
+        });
+        assert!(result.is_ok());
+        assert_eq!(output, b"int b;\n");
+        assert!(matches!(
+            format.version(),
+            Err(ClangFormatError::UnparseableVersion(_))
+        ));
+        assert_eq!(
+            runner.args(),
+            [
+                vec![OsString::from("--style=Mozilla")],
+                vec![OsString::from("--style=Mozilla")],
+                vec![OsString::from("--version")],
+            ]
+        );
+    }
+
+    #[test]
+    fn format_with_stats() {
+        let runner = Arc::new(ScriptedRunner::echo());
+        let (output, stats) = ClangFormat::new()
+            .runner(runner)
+            .format_with_stats("int a;\n")
+            .unwrap();
+        assert_eq!(output, "int a;\n");
+        assert_eq!(stats.input_bytes, 7);
+        assert_eq!(stats.output_bytes, 7);
+        assert!(stats
+            .time_to_first_byte
+            .is_some_and(|time| time <= stats.duration));
+        #[cfg(all(unix, feature = "resource_usage"))]
+        assert!(stats.resource_usage.is_some());
+
+        let (_, stats) = ClangFormat::new()
+            .runner(Arc::new(ScriptedRunner::echo()))
+            .format_with_stats("")
+            .unwrap();
+        assert_eq!(stats.time_to_first_byte, None);
+    }
+
+    /// Joins the first two lines of the input on each run, so is never idempotent
+    #[derive(Debug)]
+    struct JoinLinesRunner;
+
+    impl FormatRunner for JoinLinesRunner {
+        fn run(&self, _command: &mut Command, input: &[u8]) -> Result<Output, ClangFormatError> {
+            let input = String::from_utf8(input.to_vec())?;
+            Ok(Output {
+                status: process::ExitStatus::default(),
+                stdout: input.replacen('\n', " ", 1).into_bytes(),
+                stderr: vec![],
+            })
+        }
+    }
+
+    #[test]
+    fn format_compat_version() {
+        let runner = Arc::new(ScriptedRunner::echo());
+        let format = ClangFormat::new()
+            .runner(runner.clone())
+            .style(ClangFormatStyle::Custom(
+                "{ IndentWidth: 4, InsertBraces: true }".to_string(),
+            ))
+            .compat_version((14, 0, 0));
+        let output = format.format_with_warnings("int a;\n").unwrap();
+        assert_eq!(output.output, "int a;\n");
+        assert_eq!(
+            runner.args(),
+            [vec![OsString::from("--style={ IndentWidth: 4 }")]]
+        );
+        assert_eq!(
+            output.warnings,
+            ["warning: removed style option InsertBraces, which is not supported by clang-format 14.0.0"]
+        );
+    }
+
+    /// Outputs the contents of the file given to `--style=file:<path>`
+    #[derive(Debug)]
+    struct StyleFileRunner;
+
+    impl FormatRunner for StyleFileRunner {
+        fn run(&self, command: &mut Command, _input: &[u8]) -> Result<Output, ClangFormatError> {
+            let path = command
+                .get_args()
+                .find_map(|arg| arg.as_encoded_bytes().strip_prefix(b"--style=file:"))
+                .expect("no style file");
+            // SAFETY: the bytes are split directly after an ASCII prefix of an OsStr
+            let path = unsafe { OsStr::from_encoded_bytes_unchecked(path) };
+            Ok(Output {
+                status: process::ExitStatus::default(),
+                stdout: fs::read(path)?,
+                stderr: vec![],
+            })
+        }
+    }
+
+    #[test]
+    fn format_with_config_string() {
+        let dir = env::temp_dir().join(format!("clang-format-rs-config-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let output = ClangFormat::new()
+            .runner(StyleFileRunner)
+            .temp_dir(&dir)
+            .format_with_config_string("int a;\n", "IndentWidth: 8\n")
+            .unwrap();
+        assert_eq!(output, "IndentWidth: 8\n");
+        let temp_files = fs::read_dir(&dir).unwrap().count();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(temp_files, 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn format_with_config_string_non_utf8_temp_dir() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let mut name = OsString::from(format!("clang-format-rs-config-{}-", process::id()));
+        name.push(OsStr::from_bytes(b"\xff"));
+        let dir = env::temp_dir().join(name);
+        fs::create_dir_all(&dir).unwrap();
+        let output = ClangFormat::new()
+            .runner(StyleFileRunner)
+            .temp_dir(&dir)
+            .format_with_config_string("int a;\n", "IndentWidth: 8\n");
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(output.unwrap(), "IndentWidth: 8\n");
+    }
+
+    #[test]
+    fn format_config_diff() {
+        let diff = ClangFormat::new()
+            .binary("clang-format-14")
+            .runner(
+                ScriptedRunner::output("---\nColumnLimit: 100\n...\n").reply_to(
+                    "clang-format-16",
+                    "---\nColumnLimit: 80\nInsertNewlineAtEOF: false\n...\n",
+                ),
+            )
+            .config_diff("clang-format-16")
+            .unwrap();
+        assert_eq!(
+            diff,
+            [
+                (
+                    "ColumnLimit".to_string(),
+                    "100".to_string(),
+                    "80".to_string()
+                ),
+                (
+                    "InsertNewlineAtEOF".to_string(),
+                    String::new(),
+                    "false".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn format_cache_key() {
+        let key = |version, style: ClangFormatStyle, input| {
+            ClangFormat::new()
+                .runner(ScriptedRunner::output(version))
+                .style(style)
+                .cache_key(input)
+                .unwrap()
+        };
+        let version = "clang-format version 16.0.6\n";
+        let base = key(version, ClangFormatStyle::Mozilla, "int a;\n");
+        assert_eq!(base, key(version, ClangFormatStyle::Mozilla, "int a;\n"));
+        assert_ne!(base, key(version, ClangFormatStyle::Mozilla, "int b;\n"));
+        assert_ne!(base, key(version, ClangFormatStyle::Google, "int a;\n"));
+        assert_ne!(
+            base,
+            key(
+                "clang-format version 17.0.1\n",
+                ClangFormatStyle::Mozilla,
+                "int a;\n"
+            )
+        );
+    }
+
+    #[test]
+    fn format_lines_streaming() {
+        let mut lines = vec![];
+        ClangFormat::new()
+            .runner(UppercaseRunner)
+            .format_lines_streaming("int a;\r\n\nint b;", |line| {
+                lines.push(line.to_string());
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(lines, ["INT A;", "", "INT B;"]);
+
+        let result = ClangFormat::new()
+            .runner(UppercaseRunner)
+            .format_lines_streaming("int a;\nint b;\n", |_| Err(io::Error::other("closed")));
+        assert!(matches!(result, Err(ClangFormatError::Io(_))));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn format_lines_streaming_partial_lines() {
+        // The clang-format arguments are passed to the script, which ignores them
+        let mut lines = vec![];
+        ClangFormat::new()
+            .command_prefix(&[
+                "sh".to_string(),
+                "-c".to_string(),
+                "printf 'int a;\\nint '; sleep 0.1; printf 'b;\\nint c;'".to_string(),
+            ])
+            .format_lines_streaming("", |line| {
+                lines.push(line.to_string());
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(lines, ["int a;", "int b;", "int c;"]);
+    }
+
+    #[test]
+    fn format_column_compliance() {
+        let input = "int a;\n\nint abcdefghij;\n  \nint b;\nint abcdefghijk;\n";
+        let compliance = |config, input| {
+            ClangFormat::new()
+                .runner(ScriptedRunner::echo().reply_to("--dump-config", config))
+                .column_compliance(input)
+        };
+        assert_eq!(
+            compliance("---\nColumnLimit:     14\n...\n", input).unwrap(),
+            0.5
+        );
+        assert_eq!(
+            compliance("---\nColumnLimit:     0\n...\n", input).unwrap(),
+            1.0
+        );
+        assert_eq!(
+            compliance("---\nColumnLimit:     14\n...\n", "\n").unwrap(),
+            1.0
+        );
+        assert!(matches!(
+            compliance("---\nIndentWidth:     2\n...\n", input),
+            Err(ClangFormatError::InvalidStyleConfig(_))
+        ));
+    }
+
+    #[test]
+    fn format_dump_config_typed() {
+        let config = ClangFormat::new()
+            .runner(ScriptedRunner::echo().reply_to(
+                "--dump-config",
+                "---\nLanguage:        Java\nColumnLimit:     100\nSortIncludes:    true\n...\n",
+            ))
+            .dump_config_typed()
+            .unwrap();
+        assert_eq!(config.language, Some(Language::Java));
+        assert_eq!(config.column_limit, Some(100));
+        assert_eq!(
+            config.other.get("SortIncludes").map(String::as_str),
+            Some("true")
+        );
+    }
+
+    #[test]
+    fn format_lines_over_column_limit() {
+        let input = "int a;\nint abcdefghij;\nint b;\nint abcdefghijk;\n";
+        let lines = |config| {
+            ClangFormat::new()
+                .runner(ScriptedRunner::echo().reply_to("--dump-config", config))
+                .lines_over_column_limit(input)
+        };
+        assert_eq!(lines("---\nColumnLimit:     14\n...\n").unwrap(), [2, 4]);
+        assert_eq!(lines("---\nColumnLimit:     0\n...\n").unwrap(), []);
+        assert!(matches!(
+            lines("---\nIndentWidth:     2\n...\n"),
+            Err(ClangFormatError::InvalidStyleConfig(_))
+        ));
+    }
+
+    #[test]
+    fn format_require_parseable_version() {
+        let format = ClangFormat::new().runner(ScriptedRunner::output("my-format 1.2\n"));
+        assert!(format.format("int a;\n").is_ok());
+        assert!(matches!(
+            format.require_parseable_version(true).format("int a;\n"),
+            Err(ClangFormatError::UnparseableVersion(version)) if version == "my-format 1.2"
+        ));
+
+        let format = ClangFormat::new()
+            .require_parseable_version(true)
+            .runner(ScriptedRunner::output("clang-format version 14.0.6\n"));
+        assert!(format.format("int a;\n").is_ok());
+    }
+
+    #[test]
+    fn supported_languages() {
+        let languages = ClangFormat::new()
+            .runner(ScriptedRunner::output("clang-format version 14.0.6\n"))
+            .supported_languages()
+            .unwrap();
+        assert!(languages.contains(&Language::Cpp));
+        assert!(languages.contains(&Language::Json));
+        assert!(!languages.contains(&Language::Verilog));
+        assert!(!languages.contains(&Language::None));
+    }
+
+    #[test]
+    fn require_version() {
+        let format =
+            ClangFormat::new().runner(ScriptedRunner::output("clang-format version 14.0.6\n"));
+        assert!(format.require_version((14, 0, 0)).is_ok());
+        assert!(format.require_version((14, 0, 6)).is_ok());
+        assert!(matches!(
+            format.require_version(ClangFormatVersion::new(15, 0, 0)),
+            Err(ClangFormatError::VersionTooOld {
+                required_version,
+                found_version,
+            }) if required_version == ClangFormatVersion::new(15, 0, 0)
+                && found_version == ClangFormatVersion::new(14, 0, 6)
+        ));
+    }
+
+    #[test]
+    fn format_normalize_crlf() {
+        let format = ClangFormat::new().runner(Arc::new(ScriptedRunner::echo()));
+        let input = "int a;\r\nchar b = '\r';\r\n";
+        assert_eq!(format.format(input).unwrap(), input);
+        assert_eq!(
+            format.normalize_crlf(true).format(input).unwrap(),
+            "int a;\nchar b = '\r';\n"
+        );
+    }
+
+    #[test]
+    fn format_match_input_final_newline() {
+        let format = ClangFormat::new()
+            .runner(JoinLinesRunner)
+            .match_input_final_newline(true);
+        assert_eq!(format.format("int a;\nint b;").unwrap(), "int a; int b;");
+        assert_eq!(format.format("int a;\r\n").unwrap(), "int a;\r \r\n");
+        assert_eq!(
+            format.format("int a;\nint b;\n\n").unwrap(),
+            "int a; int b;\n\n"
+        );
+        assert_eq!(format.format("").unwrap(), "");
+
+        let mut output = b"int a;\r\n".to_vec();
+        match_final_newline(b"int a;", &mut output);
+        assert_eq!(output, b"int a;");
+        let mut output = b"int a;".to_vec();
+        match_final_newline(b"int a;\r\n", &mut output);
+        assert_eq!(output, b"int a;\r\n");
+    }
+
+    #[test]
+    fn format_batch_max_parallelism() {
+        let runner = Arc::new(ConcurrencyRunner::default());
+        let inputs: Vec<String> = (0..8).map(|index| format!("int a{index};\n")).collect();
+        let outputs = ClangFormat::new()
+            .runner(runner.clone())
+            .max_parallelism(2)
+            .format_batch(&inputs);
+        let outputs: Vec<String> = outputs.into_iter().map(Result::unwrap).collect();
+        assert_eq!(outputs, inputs);
+        assert!(runner.max_active() <= 2);
+
+        let outputs = ClangFormat::new().format_batch::<&str>(&[]);
+        assert!(outputs.is_empty());
+    }
+
+    #[test]
+    fn format_lines() {
+        let format = ClangFormat::new().runner(Arc::new(ScriptedRunner::echo()));
+        assert_eq!(
+            format.format_lines("int a;\r\n\nint b;\n").unwrap(),
+            ["int a;", "", "int b;"]
+        );
+        assert_eq!(format.format_lines("int a;").unwrap(), ["int a;"]);
+        assert!(format.format_lines("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn format_and_sanity_check() {
+        let output = ClangFormat::new()
+            .runner(JoinLinesRunner)
+            .format_and_sanity_check("struct A {\n};\n");
+        assert_eq!(output.unwrap(), "struct A { };\n");
+
+        let output = ClangFormat::new()
+            .runner(JoinLinesRunner)
+            .format_and_sanity_check("int a; // {\n}\n");
+        assert!(matches!(
+            output,
+            Err(ClangFormatError::StructuralMismatch(message)) if message == "input has 1 '}' but output has 0"
+        ));
+    }
+
+    #[test]
+    fn format_outcome() {
+        let format = ClangFormat::new().runner(JoinLinesRunner);
+        assert_eq!(
+            format.format_outcome("int a;").unwrap(),
+            FormatOutcome::Unchanged
+        );
+        assert_eq!(
+            format.format_outcome("int a;\nint b;\n").unwrap(),
+            FormatOutcome::Changed("int a; int b;\n".to_string())
+        );
+    }
+
+    /// Indents each line of the input by the `IndentWidth` of the inline style
+    #[derive(Debug)]
+    struct IndentRunner;
+
+    impl FormatRunner for IndentRunner {
+        fn run(&self, command: &mut Command, input: &[u8]) -> Result<Output, ClangFormatError> {
+            let style = command
+                .get_args()
+                .find_map(|arg| arg.to_str()?.strip_prefix("--style="))
+                .unwrap_or_default();
+            let width = style::flow_mapping_entries(style)
+                .unwrap_or_default()
+                .into_iter()
+                .find(|(key, _)| key == "IndentWidth")
+                .map_or(0, |(_, value)| value.parse().unwrap());
+            let stdout: String = String::from_utf8(input.to_vec())?
+                .lines()
+                .map(|line| format!("{}{line}\n", " ".repeat(width)))
+                .collect();
+            Ok(Output {
+                status: process::ExitStatus::default(),
+                stdout: stdout.into_bytes(),
+                stderr: vec![],
+            })
+        }
+    }
+
+    #[test]
+    fn format_styles_agree() {
+        let format = ClangFormat::new().runner(IndentRunner);
+        let input = "int a;\nint b;\n";
+        assert!(format
+            .styles_agree(
+                input,
+                "{ IndentWidth: 2 }",
+                "{ ColumnLimit: 80, IndentWidth: 2 }"
+            )
+            .unwrap());
+        assert!(!format
+            .styles_agree(input, "{ IndentWidth: 2 }", "{ IndentWidth: 4 }")
+            .unwrap());
+
+        let hunks = format
+            .style_differences(input, "{ IndentWidth: 2 }", "{ IndentWidth: 4 }")
+            .unwrap();
+        assert_eq!(hunks.len(), 1);
+        assert_eq!(hunks[0].original_text, "  int a;\n  int b;\n");
+        assert_eq!(hunks[0].formatted_text, "    int a;\n    int b;\n");
+    }
+
+    #[test]
+    fn format_indented_by() {
+        let output = ClangFormat::new()
+            .runner(Arc::new(ScriptedRunner::echo()))
+            .format_indented_by("int a;\n\nvoid b() {\r\n  c();\r\n}", 4)
+            .unwrap();
+        assert_eq!(
+            output,
+            "    int a;\n\n    void b() {\r\n      c();\r\n    }"
+        );
+    }
+
+    #[test]
+    fn default_matches() {
+        let format = ClangFormat::new().runner(
+            ScriptedRunner::output("ColumnLimit: 100\n")
+                .reply_to("--style={}", "ColumnLimit: 80\n")
+                .reply_to("--style=LLVM", "ColumnLimit: 80\n"),
+        );
+        assert!(format.default_matches(ClangFormatStyle::Llvm).unwrap());
+        assert!(!format.default_matches(ClangFormatStyle::WebKit).unwrap());
+    }
+
+    #[test]
+    fn format_shared() {
+        let output = ClangFormat::new()
+            .runner(JoinLinesRunner)
+            .format_shared("int a;\nint b;\n")
+            .unwrap();
+        let reader = Arc::clone(&output);
+        assert_eq!(&*reader, "int a; int b;\n");
+        assert_eq!(Arc::strong_count(&output), 2);
+    }
+
+    #[test]
+    fn format_is_idempotent() {
+        let format = ClangFormat::new().runner(JoinLinesRunner);
+        assert!(format.is_idempotent("int a;\n").unwrap());
+        assert!(!format.is_idempotent("int a;\nint b;\nint c;\n").unwrap());
+    }
+
+    /// Collapses repeated spaces, and supports `--cursor` when given
+    #[derive(Debug)]
+    struct CursorRunner {
+        supports_cursor: bool,
+    }
+
+    impl FormatRunner for CursorRunner {
+        fn run(&self, command: &mut Command, input: &[u8]) -> Result<Output, ClangFormatError> {
+            let args: Vec<String> = command
+                .get_args()
+                .map(|arg| arg.to_string_lossy().into_owned())
+                .collect();
+            let stdout = if args.iter().any(|arg| arg == "--help") {
+                let help = if self.supports_cursor {
+                    "  --cursor=<uint>  The position of the cursor\n"
+                } else {
+                    ""
+                };
+                help.to_string()
+            } else {
+                // Collapse spaces in the lines given with --lines, or in every line without any
+                let lines: Option<(usize, usize)> = args
+                    .iter()
+                    .find_map(|arg| arg.strip_prefix("--lines="))
+                    .and_then(|lines| lines.split_once(':'))
+                    .map(|(start, end)| (start.parse().unwrap(), end.parse().unwrap()));
+                let mut stdout = String::new();
+                for (index, line) in String::from_utf8(input.to_vec())?
+                    .split_inclusive('\n')
+                    .enumerate()
+                {
+                    let mut line = line.to_string();
+                    if lines.is_none_or(|(start, end)| (start..=end).contains(&(index + 1))) {
+                        while line.contains("  ") {
+                            line = line.replace("  ", " ");
+                        }
+                    }
+                    stdout.push_str(&line);
+                }
+                match args.iter().find_map(|arg| arg.strip_prefix("--cursor=")) {
+                    Some(_) => {
+                        format!("{{ \"Cursor\": 100, \"IncompleteFormat\": false }}\n{stdout}")
+                    }
+                    None => stdout,
+                }
+            };
+            Ok(Output {
+                status: process::ExitStatus::default(),
+                stdout: stdout.into_bytes(),
+                stderr: vec![],
+            })
+        }
+    }
+
+    #[test]
+    fn format_with_cursor() {
+        let output = ClangFormat::new()
+            .runner(CursorRunner {
+                supports_cursor: true,
+            })
+            .format_with_cursor("int    a;\n", 7);
+        assert_eq!(output.unwrap(), ("int a;\n".to_string(), 100));
+
+        let output = ClangFormat::new()
+            .runner(CursorRunner {
+                supports_cursor: false,
+            })
+            .format_with_cursor("int    a;\n", 7);
+        assert_eq!(output.unwrap(), ("int a;\n".to_string(), 4));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn format_with_cursor_probes_once() {
+        // The script stands in for clang-format, recording each time it is asked for --help
+        let probes = env::temp_dir().join(format!("clang-format-rs-probes-{}", process::id()));
+        let script = format!(
+            "for arg in \"$@\"; do if [ \"$arg\" = --help ]; then echo >> '{}'; echo '  --cursor=<uint>'; exit 0; fi; done; echo '{{ \"Cursor\": 2, \"IncompleteFormat\": false }}'; cat",
+            probes.display()
+        );
+        let format =
+            ClangFormat::new().command_prefix(&["sh".to_string(), "-c".to_string(), script]);
+        for _ in 0..3 {
+            let output = format.format_with_cursor("int a;\n", 4);
+            assert_eq!(output.unwrap(), ("int a;\n".to_string(), 2));
+        }
+        let count = fs::read_to_string(&probes).unwrap().lines().count();
+        fs::remove_file(&probes).unwrap();
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn format_with_cursor_normalize_crlf() {
+        let input = "int a;\r\nint  b;\r\n";
+        let output = ClangFormat::new()
+            .runner(
+                ScriptedRunner::output(
+                    "{ \"Cursor\": 9, \"IncompleteFormat\": false }\nint a;\r\nint b;\r\n",
+                )
+                .reply_to("--help", "  --cursor=<uint>  The position of the cursor\n"),
+            )
+            .format_with_cursor(input, 9);
+        assert_eq!(output.unwrap(), ("int a;\r\nint b;\r\n".to_string(), 9));
+
+        // The cursor stays on the same character once the earlier \r is removed
+        let output = ClangFormat::new()
+            .runner(
+                ScriptedRunner::output(
+                    "{ \"Cursor\": 9, \"IncompleteFormat\": false }\nint a;\r\nint b;\r\n",
+                )
+                .reply_to("--help", "  --cursor=<uint>  The position of the cursor\n"),
+            )
+            .normalize_crlf(true)
+            .format_with_cursor(input, 9);
+        assert_eq!(output.unwrap(), ("int a;\nint b;\n".to_string(), 8));
+
+        let output = ClangFormat::new()
+            .runner(
+                ScriptedRunner::output(
+                    "{ \"Cursor\": 9, \"IncompleteFormat\": false }\nint a;\r\nint b;\r\n",
+                )
+                .reply_to("--help", "  --cursor=<uint>  The position of the cursor\n"),
+            )
+            .normalize_crlf(true)
+            .match_input_final_newline(true)
+            .format_with_cursor("int a;\r\nint  b;", 9);
+        assert_eq!(output.unwrap(), ("int a;\nint b;".to_string(), 8));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn format_strict_invalid_style() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let stderr = "YAML:1:35: error: invalid number\n{ BasedOnStyle: LLVM, IndentWidth: x }\n                                  ^\nError parsing -style: Invalid argument\n";
+        let failure = process::ExitStatus::from_raw(1 << 8);
+        let format =
+            ClangFormat::new().runner(ScriptedRunner::output("").status(failure).stderr(stderr));
+        assert!(matches!(
+            format.clone().strict(true).format("int a;\n"),
+            Err(ClangFormatError::InvalidStyle(crate::StyleError { line: 1, column: 35, message }))
+                if message == "invalid number"
+        ));
+        assert!(matches!(
+            format.format("int a;\n"),
+            Err(ClangFormatError::NonZeroExitStatus { .. })
+        ));
+    }
+
+    #[cfg(all(unix, feature = "debug_log"))]
+    #[test]
+    fn format_with_log() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let failure = process::ExitStatus::from_raw(1 << 8);
+        let (result, log) = ClangFormat::new()
+            .runner(
+                ScriptedRunner::output("")
+                    .status(failure)
+                    .stderr("error: out of memory\n"),
+            )
+            .format_with_log("int a;\n");
+        assert!(result.is_err());
+        assert_eq!(log.entries.len(), 1);
+        assert_eq!(log.entries[0].stream, LogStream::Stderr);
+        assert_eq!(log.entries[0].bytes, b"error: out of memory\n");
+
+        let (result, log) = ClangFormat::new()
+            .runner(UppercaseRunner)
+            .format_with_log("int a;\n");
+        assert_eq!(result.unwrap(), "INT A;\n");
+        assert_eq!(log.entries[0].stream, LogStream::Stdout);
+    }
+
+    #[test]
+    fn format_ignoring_guards() {
+        let output = ClangFormat::new()
+            .runner(UppercaseRunner)
+            .format_ignoring_guards("// clang-format off\nint a;\n// clang-format on\n");
+        assert_eq!(output.unwrap(), "INT A;\n");
+    }
+
+    #[test]
+    fn format_bytes_in_place() {
+        let format = ClangFormat::new().runner(UppercaseRunner);
+        let mut buf = b"int a;\n".to_vec();
+        assert!(format.format_bytes_in_place(&mut buf).is_ok());
+        assert_eq!(buf, b"INT A;\n");
+    }
+
+    #[test]
+    fn format_region_with_cursor() {
+        let input = "int  a;\nint    b;\nint  c;\n";
+        let region = ClangFormat::new()
+            .runner(CursorRunner {
+                supports_cursor: false,
+            })
+            .format_region_with_cursor(input, 1..2, 15)
+            .unwrap();
+        assert_eq!(
+            region,
+            FormattedRegion {
+                line_range: 1..2,
+                formatted_line_range: 1..2,
+                text: "int b;\n".to_string(),
+                cursor: 12,
+            }
+        );
+
+        let region = ClangFormat::new()
+            .runner(CursorRunner {
+                supports_cursor: true,
+            })
+            .format_region_with_cursor(input, 2..3, 0)
+            .unwrap();
+        assert_eq!(region.text, "int c;\n");
+        assert_eq!(region.cursor, 100);
+    }
+
+    #[test]
+    fn format_changes_are_whitespace_only() {
+        let format = ClangFormat::new().runner(JoinLinesRunner);
+        assert!(format
+            .changes_are_whitespace_only("int a;\nint b;\n")
+            .unwrap());
+
+        let format = ClangFormat::new().runner(UppercaseRunner);
+        assert!(format
+            .changes_are_whitespace_only("int  a;\n")
+            .is_ok_and(|only| !only));
+    }
+
+    /// Echoes the input, except for the given run which is uppercase
+    #[derive(Debug)]
+    struct FlakyRunner {
+        run: usize,
+        count: AtomicUsize,
+    }
+
+    impl FormatRunner for FlakyRunner {
+        fn run(&self, _command: &mut Command, input: &[u8]) -> Result<Output, ClangFormatError> {
+            let run = self.count.fetch_add(1, Ordering::Relaxed) + 1;
+            Ok(Output {
+                status: process::ExitStatus::default(),
+                stdout: if run == self.run {
+                    input.to_ascii_uppercase()
+                } else {
+                    input.to_vec()
+                },
+                stderr: vec![],
+            })
+        }
+    }
+
+    #[test]
+    fn format_function() {
+        let input = "int  a;\nvoid  run()\n{\n  int    b;\n}\n";
+        let format = ClangFormat::new().runner(CursorRunner {
+            supports_cursor: false,
+        });
+        assert_eq!(
+            format.format_function(input, "run").unwrap(),
+            "int  a;\nvoid  run()\n{\n int b;\n}\n"
+        );
+        assert!(matches!(
+            format.format_function(input, "walk"),
+            Err(ClangFormatError::FunctionNotFound(name)) if name == "walk"
+        ));
+    }
+
+    #[test]
+    fn format_to_fixpoint() {
+        let format = ClangFormat::new().runner(JoinLinesRunner);
+        assert_eq!(
+            format
+                .format_to_fixpoint("int a;\nint b;\nint c;\n", 4)
+                .unwrap(),
+            "int a; int b; int c; "
+        );
+        assert!(matches!(
+            format.format_to_fixpoint("int a;\nint b;\nint c;\n", 3),
+            Err(ClangFormatError::NotConverged { iterations: 3 })
+        ));
+        assert!(matches!(
+            format.format_to_fixpoint("int a;", 0),
+            Err(ClangFormatError::NotConverged { iterations: 0 })
+        ));
+        assert_eq!(format.format_to_fixpoint("int a;", 1).unwrap(), "int a;");
+    }
+
+    #[test]
+    fn format_assert_deterministic() {
+        let flaky = |run| {
+            ClangFormat::new().runner(FlakyRunner {
+                run,
+                count: AtomicUsize::new(0),
+            })
+        };
+        assert!(flaky(4).assert_deterministic("int a;\n", 3).is_ok());
+        assert!(flaky(1).assert_deterministic("int a;\n", 0).is_ok());
+        assert!(matches!(
+            flaky(3).assert_deterministic("int a;\n", 5),
+            Err(ClangFormatError::NondeterministicOutput { run: 3 })
+        ));
+    }
+
+    /// Replies to `--output-replacements-xml` by replacing the first double space
+    #[derive(Debug)]
+    struct ReplacementsRunner;
+
+    impl FormatRunner for ReplacementsRunner {
+        fn run(&self, command: &mut Command, input: &[u8]) -> Result<Output, ClangFormatError> {
+            assert!(command
+                .get_args()
+                .any(|arg| arg == "--output-replacements-xml"));
+            let input = String::from_utf8(input.to_vec())?;
+            let replacement = input
+                .find("  ")
+                .map(|offset| {
+                    format!("<replacement offset='{offset}' length='2'> </replacement>\n")
+                })
+                .unwrap_or_default();
+            Ok(Output {
+                status: process::ExitStatus::default(),
+                stdout: format!("<?xml version='1.0'?>\n<replacements xml:space='preserve' incomplete_format='false'>\n{replacement}</replacements>\n").into_bytes(),
+                stderr: vec![],
+            })
+        }
+    }
+
+    #[test]
+    fn format_with_replacements() {
+        let format = ClangFormat::new().runner(ReplacementsRunner);
+        let output = format.format_with_replacements("int  a;\n").unwrap();
+        assert_eq!(output.output, "int a;\n");
+        assert_eq!(
+            output.replacements,
+            [Replacement {
+                offset: 3,
+                length: 2,
+                text: " ".to_string(),
+            }]
+        );
+
+        let output = format.format_with_replacements("int a;\n").unwrap();
+        assert_eq!(output.output, "int a;\n");
+        assert!(output.replacements.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn dry_run_diagnostics() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let failure = process::ExitStatus::from_raw(1 << 8);
+        let diagnostics = ClangFormat::new()
+            .runner(ScriptedRunner::output("").status(failure).stderr("<stdin>:2:7: error: code should be clang-formatted [-Wclang-format-violations]\n").require_arg("--dry-run"))
+            .dry_run("int a;\nint b  ;\n")
+            .unwrap();
+        assert_eq!(
+            diagnostics,
+            vec![Diagnostic {
+                line: 2,
+                column: 7,
+                message: "code should be clang-formatted".to_string(),
+            }]
+        );
+
+        let diagnostics = ClangFormat::new()
+            .runner(
+                ScriptedRunner::output("")
+                    .status(process::ExitStatus::default())
+                    .stderr("")
+                    .require_arg("--dry-run"),
+            )
+            .dry_run("int a;\n");
+        assert_eq!(diagnostics.unwrap(), vec![]);
+
+        let diagnostics = ClangFormat::new()
+            .runner(
+                ScriptedRunner::output("")
+                    .status(failure)
+                    .stderr("error: invalid style\n")
+                    .require_arg("--dry-run"),
+            )
+            .dry_run("int a;\n");
+        assert!(matches!(
+            diagnostics,
+            Err(ClangFormatError::NonZeroExitStatus { .. })
+        ));
+
+        // A crash is an error even if violations were reported before it
+        let crash = process::ExitStatus::from_raw(11);
+        let diagnostics = ClangFormat::new()
+            .runner(ScriptedRunner::output("").status(crash).stderr("<stdin>:2:7: error: code should be clang-formatted [-Wclang-format-violations]\n").require_arg("--dry-run"))
+            .dry_run("int a;\nint b  ;\n");
+        assert!(matches!(
+            diagnostics,
+            Err(ClangFormatError::NonZeroExitStatus { status, .. }) if status == crash
+        ));
+    }
+
+    #[test]
+    fn format_filelist_missing_paths() {
+        let dir = env::temp_dir().join(format!("clang-format-rs-filelist-{}", process::id()));
+        let temp_dir = dir.join("temp");
+        fs::create_dir_all(&temp_dir).unwrap();
+        fs::write(dir.join("a.cpp"), "int a;\n").unwrap();
+        let list = dir.join("files.txt");
+        fs::write(&list, "a.cpp\n\nremoved.cpp\n").unwrap();
+
+        let runner = Arc::new(ScriptedRunner::echo());
+        let format = ClangFormat::new()
+            .current_dir(&dir)
+            .temp_dir(&temp_dir)
+            .style(ClangFormatStyle::Llvm)
+            .runner(runner.clone());
+        let missing = format.format_filelist(&list);
+        let temp_files = fs::read_dir(&temp_dir).unwrap().count();
+        fs::write(&list, "a.cpp\n").unwrap();
+        let none_missing = format.format_filelist(&list);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(missing.unwrap(), [PathBuf::from("removed.cpp")]);
+        assert_eq!(temp_files, 0);
+        assert_eq!(none_missing.unwrap(), Vec::<PathBuf>::new());
+        let args = runner.args();
+        assert_eq!(args.len(), 2);
+        assert_eq!(args[0][..2], ["--style=LLVM", "-i"]);
+        assert!(args[0][2]
+            .to_string_lossy()
+            .starts_with(&format!("--files={}", temp_dir.display())));
+        assert_eq!(
+            args[1][2],
+            OsString::from(format!("--files={}", list.display()))
+        );
+    }
+
+    #[test]
+    fn format_file_streamed_temp_dir() {
+        let dir = env::temp_dir().join(format!("clang-format-rs-temp-dir-{}", process::id()));
+        let temp_dir = dir.join("temp");
+        fs::create_dir_all(&temp_dir).unwrap();
+        let path = dir.join("test.cpp");
+        fs::write(&path, "int a;\n").unwrap();
+
+        let runner = Arc::new(ScriptedRunner::echo());
+        let result = ClangFormat::new()
+            .temp_dir(&temp_dir)
+            .runner(runner)
+            .format_file_streamed(&path);
+        let output = fs::read_to_string(&path).unwrap();
+        let temp_files = fs::read_dir(&temp_dir).unwrap().count();
+        let files = fs::read_dir(&dir).unwrap().count();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(result.is_ok());
+        assert_eq!(output, "int a;\n");
+        assert_eq!(temp_files, 0);
+        assert_eq!(files, 2);
+    }
+
+    #[test]
+    fn format_file_streamed_same_name_concurrently() {
+        let dir = env::temp_dir().join(format!("clang-format-rs-same-name-{}", process::id()));
+        let temp_dir = dir.join("temp");
+        fs::create_dir_all(&temp_dir).unwrap();
+        let paths: Vec<PathBuf> = ["a", "b"]
+            .iter()
+            .map(|name| {
+                fs::create_dir_all(dir.join(name)).unwrap();
+                let path = dir.join(name).join("test.cpp");
+                fs::write(&path, format!("int {name};\n")).unwrap();
+                path
+            })
+            .collect();
+
+        let format = ClangFormat::new()
+            .temp_dir(&temp_dir)
+            .runner(UppercaseRunner);
+        let results: Vec<_> = thread::scope(|scope| {
+            let handles: Vec<_> = (0..8)
+                .map(|i| {
+                    let (format, path) = (&format, &paths[i % 2]);
+                    scope.spawn(move || format.format_file_streamed(path))
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect()
+        });
+        let outputs: Vec<String> = paths
+            .iter()
+            .map(|path| fs::read_to_string(path).unwrap())
+            .collect();
+        let temp_files = fs::read_dir(&temp_dir).unwrap().count();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(results.iter().all(Result::is_ok));
+        assert_eq!(outputs, ["INT A;\n", "INT B;\n"]);
+        assert_eq!(temp_files, 0);
+    }
+
+    #[cfg(feature = "auto_encoding")]
+    #[test]
+    fn format_file_streamed_auto_encoding() {
+        let path = env::temp_dir().join(format!("clang-format-rs-encoding-{}.cpp", process::id()));
+        fs::write(&path, b"\xff\xfei\0n\0t\0 \0\xe9\0;\0").unwrap();
+
+        let result = ClangFormat::new()
+            .auto_encoding(true)
+            .runner(UppercaseRunner)
+            .format_file_streamed(&path);
+        let output = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert!(result.is_ok());
+        // The input is given to clang-format as UTF-8 and the output is UTF-16 again
+        assert_eq!(output, b"\xff\xfeI\0N\0T\0 \0\xe9\0;\0");
+    }
+
+    #[cfg(feature = "encoding")]
+    #[test]
+    fn format_file_streamed_encoding() {
+        let path = env::temp_dir().join(format!("clang-format-rs-latin1-{}.cpp", process::id()));
+        fs::write(&path, b"// caf\xe9\nint a;\n").unwrap();
+
+        let result = ClangFormat::new()
+            .encoding(&crate::Latin1)
+            .runner(UppercaseRunner)
+            .format_file_streamed(&path);
+        let output = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert!(result.is_ok());
+        // The input is given to clang-format as UTF-8 and the output is Latin-1 again
+        assert_eq!(output, b"// CAF\xe9\nINT A;\n");
+    }
+
+    #[cfg(feature = "auto_encoding")]
+    #[test]
+    fn format_bytes_auto_encoding() {
+        let format = ClangFormat::new()
+            .auto_encoding(true)
+            .runner(UppercaseRunner);
+        assert_eq!(format.format_bytes(b"int \xe9;").unwrap(), b"INT \xe9;");
+        assert_eq!(
+            format.format_bytes(b"int \xc3\xa9;").unwrap(),
+            b"INT \xc3\xa9;"
+        );
+        // An explicit encoding takes precedence
+        assert_eq!(
+            format
+                .encoding(&crate::Latin1)
+                .format_bytes(b"\xef\xbb\xbfint;")
+                .unwrap(),
+            b"\xef\xbb\xbfINT;"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn file_needs_formatting() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let path = env::temp_dir().join(format!("clang-format-rs-needs-{}.cpp", process::id()));
+        fs::write(&path, "int  a;\n").unwrap();
+        let failure = process::ExitStatus::from_raw(1 << 8);
+        let violation =
+            "/tmp/a.cpp:1:4: error: code should be clang-formatted [-Wclang-format-violations]\n";
+        let needs = ClangFormat::new()
+            .runner(
+                ScriptedRunner::output("")
+                    .status(failure)
+                    .stderr(violation)
+                    .require_arg("--dry-run"),
+            )
+            .file_needs_formatting(&path);
+        let formatted = ClangFormat::new()
+            .runner(
+                ScriptedRunner::output("")
+                    .status(process::ExitStatus::default())
+                    .stderr("")
+                    .require_arg("--dry-run"),
+            )
+            .file_needs_formatting(&path);
+        let invalid = ClangFormat::new()
+            .runner(
+                ScriptedRunner::output("")
+                    .status(failure)
+                    .stderr("Invalid value for -style\n")
+                    .require_arg("--dry-run"),
+            )
+            .file_needs_formatting(&path);
+        let crashed = ClangFormat::new()
+            .runner(
+                ScriptedRunner::output("")
+                    .status(process::ExitStatus::from_raw(2 << 8))
+                    .stderr(violation)
+                    .require_arg("--dry-run"),
+            )
+            .file_needs_formatting(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert!(needs.unwrap());
+        assert!(!formatted.unwrap());
+        assert!(matches!(
+            invalid,
+            Err(ClangFormatError::NonZeroExitStatus { .. })
+        ));
+        assert!(matches!(
+            crashed,
+            Err(ClangFormatError::NonZeroExitStatus { .. })
+        ));
+    }
+
+    #[test]
+    fn format_with_warnings() {
+        let diagnostics = "warning: option 'AlwaysBreakTemplateDeclarations' is deprecated\n/tmp/.clang-format:3:1: warning: unknown key 'Foo'\nFoo: 1\n^\n";
+        let output = ClangFormat::new()
+            .runner(ScriptedRunner::echo().stderr(diagnostics))
+            .format_with_warnings("int a;\n")
+            .unwrap();
+        assert_eq!(output.output, "int a;\n");
+        assert_eq!(
+            output.warnings,
+            [
+                "warning: option 'AlwaysBreakTemplateDeclarations' is deprecated",
+                "/tmp/.clang-format:3:1: warning: unknown key 'Foo'"
+            ]
+        );
+    }
+
+    #[test]
+    fn format_reject_diagnostics() {
+        let warning = "warning: option 'AlwaysBreakTemplateDeclarations' is deprecated\n";
+        let output = ClangFormat::new()
+            .reject_diagnostics(true)
+            .runner(ScriptedRunner::echo().stderr(warning))
+            .format("int a;\n");
+        assert!(matches!(
+            output,
+            Err(ClangFormatError::Diagnostics(diagnostics)) if diagnostics == warning
+        ));
+
+        let output = ClangFormat::new()
+            .runner(ScriptedRunner::echo().stderr(warning))
+            .format("int a;\n");
+        assert_eq!(output.unwrap(), "int a;\n");
+
+        let output = ClangFormat::new()
+            .reject_diagnostics(true)
+            .runner(ScriptedRunner::echo().stderr(""))
+            .format("int a;\n");
+        assert_eq!(output.unwrap(), "int a;\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn format_mock_runner_failure() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let runner = Arc::new(ScriptedRunner::echo().status(process::ExitStatus::from_raw(1 << 8)));
+        let output = ClangFormat::new().runner(runner).format("int a;\n");
+        assert!(matches!(
+            output,
+            Err(ClangFormatError::NonZeroExitStatus { status, .. }) if status.code() == Some(1)
+        ));
+    }
+
+    #[test]
+    fn format_missing_binary() {
+        let output = ClangFormat::new()
+            .binary("/nonexistent/clang-format")
+            .format("struct Test {};\n");
+        match output {
+            Err(ClangFormatError::SpawnFailure { command, source }) => {
+                assert_eq!(source.kind(), io::ErrorKind::NotFound);
+                assert_eq!(command.binary, "/nonexistent/clang-format");
+                assert_eq!(command.args, vec!["--style={}"]);
+                assert_eq!(command.to_string(), "/nonexistent/clang-format --style={}");
+            }
+            _ => panic!("expected a spawn failure"),
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn format_non_utf8_binary() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let binary = OsStr::from_bytes(b"/nonexistent/clang-format-\xff");
+        let format = ClangFormat::new().binary(binary);
+        assert_eq!(format.binary_path(), binary);
+        assert!(matches!(
+            format.format("struct Test {};\n"),
+            Err(ClangFormatError::SpawnFailure { .. })
+        ));
+    }
+
+    #[test]
+    fn format_strict_unknown_key() {
+        let output = ClangFormat::new()
+            .style(ClangFormatStyle::Custom(
+                "{ BasedOnStyle: LLVM, IndentWidht: 8 }".to_string(),
+            ))
+            .strict(true)
+            .format("struct Test {};\n");
+        assert!(matches!(
+            output,
+            Err(ClangFormatError::UnknownStyleKey(key)) if key == "IndentWidht"
+        ));
+    }
+
+    #[test]
+    fn format_reject_empty_output_empty_input() {
+        let output = ClangFormat::new().reject_empty_output(true).format("");
+        assert!(output.is_ok());
+        assert_eq!(output.unwrap(), "");
+    }
+
+    #[test]
+    fn format_qualifier_alignment() {
+        let format = |alignment| {
+            ClangFormat::new()
+                .qualifier_alignment(alignment)
+                .format("const int a = 1;\nint const b = 2;\n")
+        };
+
+        // Older versions of clang-format do not know the argument
+        let output = format(QualifierAlignment::Left);
+        if matches!(output, Err(ClangFormatError::NonZeroExitStatus { .. })) {
+            return;
+        }
+        assert_eq!(output.unwrap(), "const int a = 1;\nconst int b = 2;\n");
+
+        let output = format(QualifierAlignment::Right);
+        assert_eq!(output.unwrap(), "int const a = 1;\nint const b = 2;\n");
+
+        let output = format(QualifierAlignment::Leave);
+        assert_eq!(output.unwrap(), "const int a = 1;\nint const b = 2;\n");
+    }
+
+    #[test]
+    fn format_file_streamed() {
+        let path = env::temp_dir().join(format!("clang-format-rs-streamed-{}.cpp", process::id()));
+        std::fs::write(&path, "struct Test {\n\n};\n").unwrap();
+
+        let result = ClangFormat::new()
+            .style(ClangFormatStyle::Mozilla)
+            .format_file_streamed(&path);
+        let output = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(result.is_ok());
+        assert_eq!(output, "struct Test\n{};\n");
+    }
+
+    #[test]
+    fn format_file_streamed_assume_filename() {
+        let dir = env::temp_dir().join(format!("clang-format-rs-assume-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("generated.tmp");
+        fs::write(&path, "int a;\n").unwrap();
+
+        let runner = Arc::new(ScriptedRunner::echo());
+        let format = ClangFormat::new()
+            .style(ClangFormatStyle::File)
+            .runner(runner.clone());
+        let assumed = format
+            .clone()
+            .assume_filename(dir.join("generated.cpp"))
+            .format_file_streamed(&path);
+        let unassumed = format.format_file_streamed(&path);
+        let output = fs::read_to_string(&path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(assumed.is_ok());
+        assert!(unassumed.is_ok());
+        assert_eq!(output, "int a;\n");
+        let assume_arg = |path: PathBuf| {
+            let mut arg = OsString::from("--assume-filename=");
+            arg.push(path);
+            arg
+        };
+        assert_eq!(
+            runner.args(),
+            [
+                vec![
+                    assume_arg(dir.join("generated.cpp")),
+                    OsString::from("--style=file")
+                ],
+                vec![
+                    assume_arg(dir.join("generated.tmp")),
+                    OsString::from("--style=file")
+                ],
+            ]
+        );
+    }
+
+    #[test]
+    fn explain_file_style() {
+        let dir = env::temp_dir().join(format!("clang-format-rs-explain-{}", process::id()));
+        let nested = dir.join("project").join("src");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(dir.join("project").join("_clang-format"), "").unwrap();
+        fs::write(dir.join(".clang-format"), "").unwrap();
+
+        let format = ClangFormat::new().current_dir(&dir);
+        let from_file = format.explain_file_style("project/src/main.cpp").unwrap();
+        let from_dir = format.explain_file_style(&nested).unwrap();
+        let from_root = format.explain_file_style("main.cpp").unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let expected = FileStyleResolution::Found(dir.join("project").join("_clang-format"));
+        assert_eq!(from_file, expected);
+        assert_eq!(from_dir, expected);
+        assert_eq!(
+            from_root,
+            FileStyleResolution::Found(dir.join(".clang-format"))
+        );
+    }
+
+    #[test]
+    fn format_file_streamed_missing() {
+        let path = env::temp_dir()
+            .join("clang-format-rs-nonexistent")
+            .join("test.cpp");
+        let result = ClangFormat::new().format_file_streamed(path);
+        assert!(matches!(result, Err(ClangFormatError::Io(_))));
+    }
+
+    #[test]
+    fn format_verify_style_support() {
+        let output = ClangFormat::new()
+            .style(ClangFormatStyle::Microsoft)
+            .verify_style_support(true)
+            .format("struct Test {};\n");
+
+        match ClangFormat::new().version().unwrap() {
+            version if version < ClangFormatVersion::new(9, 0, 0) => {
+                assert!(matches!(
+                    output,
+                    Err(ClangFormatError::UnsupportedStyle { found_version, .. }) if found_version == version
+                ));
+            }
+            _ => assert!(output.is_ok()),
+        }
+    }
+
+    #[test]
+    fn format_streaming_large_input() {
+        // Larger than a pipe buffer, so that writing and reading must happen concurrently
+        let input = "int  a;\n".repeat(100_000);
+        let mut output = vec![];
+        let result = ClangFormat::new().format_streaming(input.as_bytes(), |chunk| {
+            output.extend_from_slice(chunk);
+            Ok(())
+        });
+        assert!(result.is_ok());
+        assert_eq!(output, "int a;\n".repeat(100_000).as_bytes());
+    }
+
+    #[test]
+    fn format_streaming_callback_error() {
+        let input = "int  a;\n".repeat(100_000);
+        let result = ClangFormat::new().format_streaming(input.as_bytes(), |_| {
+            Err(io::Error::other("callback failed"))
+        });
+        assert!(
+            matches!(result, Err(ClangFormatError::Io(err)) if err.to_string() == "callback failed")
+        );
+    }
+
+    #[test]
+    fn format_language() {
+        let language = |filename| {
+            ClangFormat::new()
+                .assume_filename(filename)
+                .language()
+                .unwrap()
+        };
+        assert_eq!(language("test.cpp"), Language::Cpp);
+        assert_eq!(language("test.m"), Language::ObjC);
+        assert_eq!(language("test.js"), Language::JavaScript);
+        assert_eq!(language("test.proto"), Language::Proto);
+        assert_eq!(ClangFormat::new().language().unwrap(), Language::Cpp);
+    }
+
+    #[test]
+    fn format_assume_filename_shebang() {
+        let format = ClangFormat::new()
+            .style(ClangFormatStyle::Llvm)
+            .assume_filename("x.cpp");
+        let output = format.format("#!/bin/sh\nint  a;\n");
+        assert!(output.is_ok());
+        assert_eq!(output.unwrap(), "#!/bin/sh\nint a;\n");
+        assert_eq!(format.language().unwrap(), Language::Cpp);
+    }
+
+    #[test]
+    fn format_current_dir_file_style() {
+        let dir = env::temp_dir().join(format!(
+            "clang-format-rs-current-dir-{}",
+            std::process::id()
+        ));
+        let nested = dir.join("nested");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::write(
+            dir.join(".clang-format"),
+            "BasedOnStyle: Mozilla\nIndentWidth: 8\n",
+        )
+        .unwrap();
+
+        let output = ClangFormat::new()
+            .style(ClangFormatStyle::File)
+            .current_dir(&nested)
+            .format("struct Test { bool field; };\n");
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(output.is_ok());
+        assert_eq!(output.unwrap(), "struct Test\n{\n        bool field;\n};\n");
+    }
+}
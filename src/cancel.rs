@@ -0,0 +1,140 @@
+// SPDX-FileCopyrightText: 2023 Klarälvdalens Datakonsult AB, a KDAB Group company <info@kdab.com>
+// SPDX-FileContributor: Andrew Hayzen <andrew.hayzen@kdab.com>
+//
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Cancelling a running clang-format process and its descendants
+
+use std::fmt;
+use std::process::{Child, Command};
+use std::sync::Mutex;
+
+/// The child process which is running and whether it has been cancelled
+#[derive(Default)]
+struct State {
+    cancelled: bool,
+    child: Option<Child>,
+}
+
+/// Allows for another thread to kill the clang-format process which is running
+///
+/// The child is held here until its output has been read, this ensures that it has not been
+/// reaped when it is killed, so the process id cannot have been reused by another process.
+#[derive(Default)]
+pub(crate) struct CancelHandle {
+    state: Mutex<State>,
+}
+
+impl CancelHandle {
+    /// Kill the running process, and any process started after this
+    #[cfg(feature = "async")]
+    pub(crate) fn cancel(&self) {
+        let mut state = self.state.lock().expect("cancel state poisoned");
+        state.cancelled = true;
+        if let Some(child) = &mut state.child {
+            kill(child);
+        }
+    }
+
+    /// Whether the handle has been cancelled
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.state.lock().expect("cancel state poisoned").cancelled
+    }
+
+    /// Hold the child so that it can be cancelled, killing it if already cancelled
+    pub(crate) fn register(&self, mut child: Child) {
+        let mut state = self.state.lock().expect("cancel state poisoned");
+        if state.cancelled {
+            kill(&mut child);
+        }
+        state.child = Some(child);
+    }
+
+    /// Kill the registered child, without cancelling the handle
+    pub(crate) fn kill(&self) {
+        if let Some(child) = &mut self.state.lock().expect("cancel state poisoned").child {
+            kill(child);
+        }
+    }
+
+    /// Take back the registered child, after which it can no longer be cancelled
+    pub(crate) fn take(&self) -> Option<Child> {
+        self.state
+            .lock()
+            .expect("cancel state poisoned")
+            .child
+            .take()
+    }
+}
+
+impl fmt::Debug for CancelHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CancelHandle")
+            .field("cancelled", &self.is_cancelled())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Start the process of the command in a new process group, so that its descendants can be killed
+#[cfg(unix)]
+pub(crate) fn new_process_group(command: &mut Command) {
+    use std::os::unix::process::CommandExt;
+
+    command.process_group(0);
+}
+
+/// Process groups are only supported on Unix
+#[cfg(not(unix))]
+pub(crate) fn new_process_group(_command: &mut Command) {}
+
+/// Kill the process group of the child, which is the child itself when it is not a group leader
+#[cfg(unix)]
+fn kill(child: &mut Child) {
+    use crate::sys::{self, SIGKILL};
+    use std::os::raw::c_int;
+
+    if let Ok(pid) = c_int::try_from(child.id()) {
+        // SAFETY: the child has not been reaped, so the process group id is still ours.
+        // If the child is not a group leader this fails, and the child is killed below.
+        unsafe {
+            sys::kill(-pid, SIGKILL);
+        }
+    }
+    let _ = child.kill();
+}
+
+/// Kill the child, descendants are not killed on other platforms
+#[cfg(not(unix))]
+fn kill(child: &mut Child) {
+    let _ = child.kill();
+}
+
+#[cfg(all(test, unix, feature = "async"))]
+mod tests {
+    use super::*;
+    use std::process::Stdio;
+
+    #[test]
+    fn cancel_kills_process_group() {
+        let mut command = Command::new("sh");
+        command
+            .args(["-c", "sleep 30 & wait"])
+            .stdout(Stdio::null());
+        new_process_group(&mut command);
+
+        let handle = CancelHandle::default();
+        handle.register(command.spawn().unwrap());
+        handle.cancel();
+        assert!(handle.is_cancelled());
+        let status = handle.take().unwrap().wait().unwrap();
+        assert!(!status.success());
+    }
+
+    #[test]
+    fn register_after_cancel() {
+        let handle = CancelHandle::default();
+        handle.cancel();
+        handle.register(Command::new("sleep").arg("30").spawn().unwrap());
+        assert!(!handle.take().unwrap().wait().unwrap().success());
+    }
+}
@@ -0,0 +1,146 @@
+// SPDX-FileCopyrightText: 2023 Klarälvdalens Datakonsult AB, a KDAB Group company <info@kdab.com>
+// SPDX-FileContributor: Andrew Hayzen <andrew.hayzen@kdab.com>
+//
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Formatting of generated C++, with cleanup of common code generation artifacts
+
+use crate::{ClangFormat, ClangFormatError, ClangFormatStyle};
+
+/// The cleanup passes applied by `format_codegen_with` after clang-format
+///
+/// clang-format does not change the contents of regions between `// clang-format off` and
+/// `// clang-format on`, raw strings, or comments, which is where generated code often has
+/// artifacts left over from templates. Each pass can be disabled, by default all are enabled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct CodegenCleanup {
+    /// Collapse runs of blank lines into a single blank line
+    pub collapse_blank_lines: bool,
+    /// Remove spaces and tabs at the end of each line
+    pub trim_trailing_whitespace: bool,
+    /// Remove blank lines at the end and ensure non-empty output ends with a single newline
+    pub single_final_newline: bool,
+}
+
+impl Default for CodegenCleanup {
+    fn default() -> Self {
+        Self {
+            collapse_blank_lines: true,
+            trim_trailing_whitespace: true,
+            single_final_newline: true,
+        }
+    }
+}
+
+impl CodegenCleanup {
+    /// Apply the enabled cleanup passes to the text
+    ///
+    /// Trailing whitespace is trimmed before blank lines are collapsed,
+    /// so that lines containing only whitespace count as blank lines.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use clang_format::CodegenCleanup;
+    /// # fn main() {
+    /// let cleaned = CodegenCleanup::default().apply("int a; \n\n\n\nint b;\n\n");
+    /// assert_eq!(cleaned, "int a;\n\nint b;\n");
+    /// # }
+    /// ```
+    pub fn apply(&self, text: &str) -> String {
+        let mut lines: Vec<&str> = text.lines().collect();
+        if self.trim_trailing_whitespace {
+            for line in &mut lines {
+                *line = line.trim_end_matches([' ', '\t']);
+            }
+        }
+        if self.collapse_blank_lines {
+            lines.dedup_by(|line, previous| line.is_empty() && previous.is_empty());
+        }
+        if self.single_final_newline {
+            while lines.last().is_some_and(|line| line.is_empty()) {
+                lines.pop();
+            }
+        }
+
+        let mut cleaned = lines.join("\n");
+        let final_newline = if self.single_final_newline {
+            !cleaned.is_empty()
+        } else {
+            text.ends_with('\n')
+        };
+        if final_newline {
+            cleaned.push('\n');
+        }
+        cleaned
+    }
+}
+
+/// Execute clang-format with the given input and style, then apply the default cleanup passes
+///
+/// This is intended for C++ generated by build scripts or macros, see `CodegenCleanup`
+/// for the cleanup passes.
+///
+/// # Example
+///
+/// ```
+/// # use clang_format::{format_codegen, ClangFormatStyle};
+/// # fn main() {
+/// let output = format_codegen("int  a;\n\n\n\nint  b;\n", &ClangFormatStyle::Default);
+/// assert!(output.is_ok());
+/// assert_eq!(output.unwrap(), "int a;\n\nint b;\n");
+/// # }
+/// ```
+pub fn format_codegen(
+    input: &str,
+    style: impl Into<ClangFormatStyle>,
+) -> Result<String, ClangFormatError> {
+    format_codegen_with(input, style, &CodegenCleanup::default())
+}
+
+/// Execute clang-format with the given input and style, then apply the given cleanup passes
+pub fn format_codegen_with(
+    input: &str,
+    style: impl Into<ClangFormatStyle>,
+    cleanup: &CodegenCleanup,
+) -> Result<String, ClangFormatError> {
+    let output = ClangFormat::new().style(style).format(input)?;
+    Ok(cleanup.apply(&output))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cleanup_default() {
+        let cleanup = CodegenCleanup::default();
+        assert_eq!(
+            cleanup
+                .apply("// clang-format off\nint a;  \t\n \n\n\nint b;\n// clang-format on\n\n\n"),
+            "// clang-format off\nint a;\n\nint b;\n// clang-format on\n"
+        );
+        assert_eq!(cleanup.apply("int a;"), "int a;\n");
+        assert_eq!(cleanup.apply("\n\n"), "");
+    }
+
+    #[test]
+    fn cleanup_disabled_passes() {
+        let cleanup = CodegenCleanup {
+            collapse_blank_lines: false,
+            single_final_newline: false,
+            ..CodegenCleanup::default()
+        };
+        assert_eq!(cleanup.apply("int a; \n\n\nint b;"), "int a;\n\n\nint b;");
+
+        let cleanup = CodegenCleanup {
+            trim_trailing_whitespace: false,
+            ..CodegenCleanup::default()
+        };
+        assert_eq!(
+            cleanup.apply("int a; \n \n\nint b;\n"),
+            "int a; \n \n\nint b;\n"
+        );
+    }
+}
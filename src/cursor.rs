@@ -0,0 +1,321 @@
+// SPDX-FileCopyrightText: 2023 Klarälvdalens Datakonsult AB, a KDAB Group company <info@kdab.com>
+// SPDX-FileContributor: Andrew Hayzen <andrew.hayzen@kdab.com>
+//
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Tracking a cursor through formatting, with `--cursor` or estimated by diffing
+
+use std::ops::Range;
+
+use crate::diff;
+
+/// The formatted text of a region of lines and the cursor after formatting
+///
+/// The line ranges are 0-based indexes of lines, and the text includes the trailing newlines
+/// of its lines. The lines before the region are unchanged, so the region starts at the same
+/// line in the input and the formatted output.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FormattedRegion {
+    /// The lines of the input which are replaced by the text
+    ///
+    /// This can be larger than the requested lines when clang-format also changed
+    /// neighbouring lines, for example when joining a line onto the previous one.
+    pub line_range: Range<usize>,
+    /// The lines of the formatted output which the text spans
+    pub formatted_line_range: Range<usize>,
+    /// The formatted text of the region
+    pub text: String,
+    /// The byte offset of the cursor in the whole formatted output
+    pub cursor: usize,
+}
+
+/// Find the lines of the input and output which contain the requested lines and any changes
+///
+/// Lines outside of the requested lines are only included when they changed,
+/// the region is the smallest which leaves the common leading and trailing lines out.
+pub(crate) fn region(
+    input: &str,
+    output: &str,
+    lines: Range<usize>,
+) -> (Range<usize>, Range<usize>) {
+    let input_lines: Vec<&str> = input.split_inclusive('\n').collect();
+    let output_lines: Vec<&str> = output.split_inclusive('\n').collect();
+    let end = lines.end.min(input_lines.len());
+    let start = lines.start.min(end);
+
+    let prefix = input_lines
+        .iter()
+        .zip(&output_lines)
+        .take(start)
+        .take_while(|(input, output)| input == output)
+        .count();
+    let suffix = input_lines[prefix..]
+        .iter()
+        .rev()
+        .zip(output_lines[prefix..].iter().rev())
+        .take(input_lines.len() - end)
+        .take_while(|(input, output)| input == output)
+        .count();
+    (
+        prefix..input_lines.len() - suffix,
+        prefix..output_lines.len() - suffix,
+    )
+}
+
+/// Estimate where the byte offset of the cursor in the original text is in the formatted text
+///
+/// The lines are diffed to find the changed region containing the cursor. Within that region
+/// the cursor stays before the same non-whitespace character, or when the region changed more
+/// than whitespace, the bytes of the region are diffed.
+pub(crate) fn estimate(original: &str, formatted: &str, cursor: usize) -> usize {
+    let cursor = floor_char_boundary(original, cursor);
+    let original_lines: Vec<&str> = original.split_inclusive('\n').collect();
+    let formatted_lines: Vec<&str> = formatted.split_inclusive('\n').collect();
+    let original_starts = line_starts(&original_lines);
+    let formatted_starts = line_starts(&formatted_lines);
+
+    let mut delta = 0;
+    for change in diff::diff(&original_lines, &formatted_lines) {
+        let old = original_starts[change.old.start]..original_starts[change.old.end];
+        let new = formatted_starts[change.new.start]..formatted_starts[change.new.end];
+        if cursor < old.start {
+            break;
+        }
+        if cursor < old.end {
+            let offset = estimate_in_region(
+                &original[old.clone()],
+                &formatted[new.clone()],
+                cursor - old.start,
+            );
+            return floor_char_boundary(formatted, new.start + offset);
+        }
+        delta = new.end as isize - old.end as isize;
+    }
+    floor_char_boundary(formatted, (cursor as isize + delta) as usize)
+}
+
+/// Estimate the cursor within a changed region of the original and formatted text
+fn estimate_in_region(original: &str, formatted: &str, cursor: usize) -> usize {
+    let non_whitespace = |text: &str| {
+        text.chars()
+            .filter(|c| !c.is_whitespace())
+            .collect::<String>()
+    };
+    if non_whitespace(original) != non_whitespace(formatted) {
+        return estimate_bytes(original.as_bytes(), formatted.as_bytes(), cursor);
+    }
+
+    // Keep the cursor before the same non-whitespace character,
+    // or after the preceding one if the cursor is in whitespace
+    let count = original[..cursor]
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .count();
+    let in_whitespace = original[cursor..]
+        .chars()
+        .next()
+        .is_some_and(char::is_whitespace);
+    let mut seen = 0;
+    for (index, c) in formatted.char_indices() {
+        if c.is_whitespace() {
+            continue;
+        }
+        if seen == count && !in_whitespace {
+            return index;
+        }
+        seen += 1;
+        if seen == count && in_whitespace {
+            return index + c.len_utf8();
+        }
+    }
+    if count == 0 {
+        0
+    } else {
+        formatted.len()
+    }
+}
+
+/// Estimate the cursor by diffing the bytes, a cursor in a changed range moves to the same
+/// distance into the replacement, limited to its length
+fn estimate_bytes(original: &[u8], formatted: &[u8], cursor: usize) -> usize {
+    let mut delta = 0;
+    for change in diff::diff(original, formatted) {
+        if cursor < change.old.start {
+            break;
+        }
+        if cursor < change.old.end {
+            return change.new.start + (cursor - change.old.start).min(change.new.len());
+        }
+        delta = change.new.end as isize - change.old.end as isize;
+    }
+    (cursor as isize + delta) as usize
+}
+
+/// Map a byte offset in the original text to the corresponding byte offset in the edited text
+///
+/// The characters of both texts are diffed. An offset before an unchanged character moves
+/// with that character, an offset within removed or replaced characters moves to the start
+/// of their replacement.
+pub(crate) fn map_offset(original: &str, edited: &str, offset: usize) -> usize {
+    let offset = floor_char_boundary(original, offset);
+    let original_chars: Vec<char> = original.chars().collect();
+    let edited_chars: Vec<char> = edited.chars().collect();
+    let edited_starts: Vec<usize> = edited
+        .char_indices()
+        .map(|(index, _)| index)
+        .chain([edited.len()])
+        .collect();
+
+    let index = original[..offset].chars().count();
+    let mut delta = 0;
+    for change in diff::diff(&original_chars, &edited_chars) {
+        if index < change.old.start {
+            break;
+        }
+        if index < change.old.end {
+            return edited_starts[change.new.start];
+        }
+        delta = change.new.end as isize - change.old.end as isize;
+    }
+    let index = (index as isize + delta) as usize;
+    edited_starts[index.min(edited_chars.len())]
+}
+
+/// The byte offset of the start of each line, followed by the total length
+fn line_starts(lines: &[&str]) -> Vec<usize> {
+    let mut starts = Vec::with_capacity(lines.len() + 1);
+    let mut start = 0;
+    starts.push(start);
+    for line in lines {
+        start += line.len();
+        starts.push(start);
+    }
+    starts
+}
+
+/// The largest character boundary of the text which is not after the offset
+fn floor_char_boundary(text: &str, offset: usize) -> usize {
+    let mut offset = offset.min(text.len());
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+/// Split the output of `clang-format --cursor` into the new cursor and the formatted text
+///
+/// The first line is JSON in the form `{ "Cursor": 5, "IncompleteFormat": false }`.
+pub(crate) fn parse_output(output: &str) -> Result<(usize, String), String> {
+    let (header, formatted) = output
+        .split_once('\n')
+        .ok_or_else(|| "missing cursor header".to_string())?;
+    let cursor = header
+        .split_once("\"Cursor\":")
+        .map(|(_, rest)| rest.trim_start())
+        .map(|rest| {
+            rest.split(|c: char| !c.is_ascii_digit())
+                .next()
+                .unwrap_or_default()
+        })
+        .and_then(|digits| digits.parse().ok())
+        .ok_or_else(|| format!("missing cursor in {header}"))?;
+    Ok((cursor, formatted.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimate_unchanged() {
+        assert_eq!(estimate("int a;\n", "int a;\n", 4), 4);
+        assert_eq!(estimate("int a;\n", "int a;\n", 100), 7);
+    }
+
+    #[test]
+    fn estimate_whitespace_changes() {
+        let original = "int  a;\nint    b;\nint c;\n";
+        let formatted = "int a;\nint b;\nint c;\n";
+        // Before the b
+        assert_eq!(estimate(original, formatted, 15), 11);
+        // In the whitespace before the b
+        assert_eq!(estimate(original, formatted, 13), 10);
+        // In an unchanged line after the changes
+        assert_eq!(estimate(original, formatted, 23), 19);
+        // Lines which are joined
+        assert_eq!(estimate("int a;\nint b;\n", "int a; int b;\n", 11), 11);
+    }
+
+    #[test]
+    fn estimate_other_changes() {
+        let original = "#include <b>\n#include <a>\nint c;\n";
+        let formatted = "#include <a>\n#include <b>\nint c;\n";
+        assert_eq!(estimate(original, formatted, 28), 28);
+        assert!(estimate(original, formatted, 10) <= 26);
+        // Never inside a character of the original or formatted text
+        assert_eq!(estimate("é\n", "ü\n", 1), 0);
+    }
+
+    #[test]
+    fn map_offset_unchanged() {
+        assert_eq!(map_offset("int a;\n", "int a;\n", 4), 4);
+        assert_eq!(map_offset("int a;\n", "int a;\n", 100), 7);
+        // The end of the text stays at the end
+        assert_eq!(map_offset("", "int a;\n", 0), 7);
+    }
+
+    #[test]
+    fn map_offset_insertions_and_deletions() {
+        // Offsets move with the character after them
+        assert_eq!(map_offset("a+b;\n", "a + b;\n", 1), 2);
+        assert_eq!(map_offset("a+b;\n", "a + b;\n", 2), 4);
+        assert_eq!(map_offset("int *p;\n", "int p;\n", 5), 4);
+        // An offset in removed text moves to where it was removed
+        assert_eq!(map_offset("int *p;\n", "int p;\n", 4), 4);
+    }
+
+    #[test]
+    fn map_offset_replacements() {
+        // Unlike estimate the offset does not keep its distance into the replacement
+        assert_eq!(map_offset("foo(x);\n", "bar(x);\n", 1), 0);
+        assert_eq!(estimate("foo(x);\n", "bar(x);\n", 1), 1);
+        assert_eq!(map_offset("foo(x);\n", "bar(x);\n", 4), 4);
+        assert_eq!(map_offset("a = foo;\nb;\n", "a = value;\nb;\n", 9), 11);
+    }
+
+    #[test]
+    fn map_offset_character_boundaries() {
+        assert_eq!(map_offset("ü a\n", "ü  a\n", 3), 4);
+        // Within a character of the original text
+        assert_eq!(map_offset("éa\n", "é a\n", 1), 0);
+        assert_eq!(map_offset("é = 1;\n", "ü = 1;\n", 1), 0);
+    }
+
+    #[test]
+    fn region_lines() {
+        let input = "int a;\nint  b;\nint c;\n";
+        // Only the requested line changed
+        assert_eq!(
+            region(input, "int a;\nint b;\nint c;\n", 1..2),
+            (1..2, 1..2)
+        );
+        // The line was joined onto the previous one
+        assert_eq!(region(input, "int a; int b;\nint c;\n", 1..2), (0..2, 0..1));
+        // Out of range lines are limited to the input
+        assert_eq!(region(input, input, 2..10), (2..3, 2..3));
+    }
+
+    #[test]
+    fn parse_cursor_output() {
+        assert_eq!(
+            parse_output("{ \"Cursor\": 5, \"IncompleteFormat\": false }\nint a;\n"),
+            Ok((5, "int a;\n".to_string()))
+        );
+        assert_eq!(
+            parse_output("{ \"Cursor\": 5, \"IncompleteFormat\": false }\n"),
+            Ok((5, String::new()))
+        );
+        assert!(parse_output("int a;\n").is_err());
+        assert!(parse_output("").is_err());
+    }
+}
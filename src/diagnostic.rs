@@ -0,0 +1,152 @@
+// SPDX-FileCopyrightText: 2023 Klarälvdalens Datakonsult AB, a KDAB Group company <info@kdab.com>
+// SPDX-FileContributor: Andrew Hayzen <andrew.hayzen@kdab.com>
+//
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use thiserror::Error;
+
+/// Describes a location where the input is not formatted, as reported by `clang-format --dry-run`
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Diagnostic {
+    /// The 1-based line number of the violation
+    pub line: usize,
+    /// The 1-based column number of the violation
+    pub column: usize,
+    /// The message clang-format reported, such as `code should be clang-formatted`
+    pub message: String,
+}
+
+/// Describes where clang-format failed to parse the YAML of a style
+///
+/// clang-format reports these for a `ClangFormatStyle::Custom` style or a `.clang-format` file
+/// in the form `YAML:1:5: error: unknown key 'Foo'`.
+#[derive(Error, Clone, Debug, PartialEq, Eq, Hash)]
+#[error("line {line}, column {column}: {message}")]
+pub struct StyleError {
+    /// The 1-based line number in the style
+    pub line: usize,
+    /// The 1-based column number in the style
+    pub column: usize,
+    /// The message clang-format reported, such as `unknown key 'Foo'`
+    pub message: String,
+}
+
+/// Parse the first error in a style from the stderr of clang-format
+///
+/// Diagnostics of the input, which are reported for `<stdin>`, are ignored.
+pub(crate) fn parse_style_error(stderr: &str) -> Option<StyleError> {
+    stderr
+        .lines()
+        .filter(|line| line.contains(": error: "))
+        .filter_map(parse_line_with_source)
+        .find(|(source, _)| *source != "<stdin>")
+        .map(|(_, diagnostic)| StyleError {
+            line: diagnostic.line,
+            column: diagnostic.column,
+            message: diagnostic.message,
+        })
+}
+
+/// Parse the diagnostics of `clang-format --dry-run`
+///
+/// Each violation is in the form
+///
+/// ```text
+/// <stdin>:12:5: error: code should be clang-formatted [-Wclang-format-violations]
+/// int  a;
+///     ^
+/// ```
+///
+/// The source and caret lines are ignored, as are any lines which are not diagnostics.
+pub(crate) fn parse(stderr: &str) -> Vec<Diagnostic> {
+    stderr.lines().filter_map(parse_line).collect()
+}
+
+/// Parse a single `file:line:column: severity: message` line
+fn parse_line(line: &str) -> Option<Diagnostic> {
+    parse_line_with_source(line).map(|(_, diagnostic)| diagnostic)
+}
+
+/// Parse a single `file:line:column: severity: message` line, with the file
+fn parse_line_with_source(line: &str) -> Option<(&str, Diagnostic)> {
+    let (location, message) = [": error: ", ": warning: "]
+        .iter()
+        .find_map(|severity| line.split_once(severity))?;
+
+    // The filename may contain colons, so take the line and column from the end
+    let mut location = location.rsplitn(3, ':');
+    let column = location.next()?.parse().ok()?;
+    let line = location.next()?.parse().ok()?;
+    let source = location.next()?;
+
+    // Remove the warning flag, such as [-Wclang-format-violations]
+    let message = match message.rfind(" [-W") {
+        Some(index) if message.ends_with(']') => &message[..index],
+        _ => message,
+    };
+
+    Some((
+        source,
+        Diagnostic {
+            line,
+            column,
+            message: message.to_string(),
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_diagnostics() {
+        let stderr = "<stdin>:1:4: error: code should be clang-formatted [-Wclang-format-violations]\nint  a;\n   ^\nC:\\src\\a.cpp:12:5: warning: code should be clang-formatted [-Wclang-format-violations]\n    int b;\n    ^\n";
+        assert_eq!(
+            parse(stderr),
+            vec![
+                Diagnostic {
+                    line: 1,
+                    column: 4,
+                    message: "code should be clang-formatted".to_string(),
+                },
+                Diagnostic {
+                    line: 12,
+                    column: 5,
+                    message: "code should be clang-formatted".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_style_errors() {
+        let stderr = "YAML:1:22: error: unknown key 'IndentWidht'\n{ BasedOnStyle: LLVM, IndentWidht: 8 }\n                     ^~~~~~~~~~~\nError parsing -style: Invalid argument\n";
+        assert_eq!(
+            parse_style_error(stderr),
+            Some(StyleError {
+                line: 1,
+                column: 22,
+                message: "unknown key 'IndentWidht'".to_string(),
+            })
+        );
+        assert_eq!(
+            parse_style_error("/src/.clang-format:3:1: error: invalid number\n")
+                .map(|error| error.to_string()),
+            Some("line 3, column 1: invalid number".to_string())
+        );
+        assert_eq!(
+            parse_style_error("<stdin>:1:4: error: code should be clang-formatted\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn parse_diagnostics_other_lines() {
+        assert_eq!(parse(""), vec![]);
+        assert_eq!(
+            parse("error: unknown key 'Foo'\n<stdin>:x:1: error: bad\n"),
+            vec![]
+        );
+    }
+}
@@ -0,0 +1,500 @@
+// SPDX-FileCopyrightText: 2023 Klarälvdalens Datakonsult AB, a KDAB Group company <info@kdab.com>
+// SPDX-FileContributor: Andrew Hayzen <andrew.hayzen@kdab.com>
+//
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! A small Myers diff used to compare the input and output of clang-format
+
+use std::ops::Range;
+
+/// A region where the old and new sequences differ
+///
+/// Either range can be empty, for a pure insertion or deletion.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub(crate) struct Change {
+    pub(crate) old: Range<usize>,
+    pub(crate) new: Range<usize>,
+}
+
+/// A region of the input which clang-format changed
+///
+/// The line ranges are 0-based indexes of lines, and the text of each side
+/// includes the trailing newlines of its lines.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Hunk {
+    /// The lines of the input which were changed
+    pub line_range: Range<usize>,
+    /// The lines of the formatted output which replace them
+    pub formatted_line_range: Range<usize>,
+    /// The text of the input lines
+    pub original_text: String,
+    /// The text of the formatted lines
+    pub formatted_text: String,
+}
+
+/// Compute the hunks which differ between the original and formatted text
+pub(crate) fn hunks(original: &str, formatted: &str) -> Vec<Hunk> {
+    let original_lines: Vec<&str> = original.split_inclusive('\n').collect();
+    let formatted_lines: Vec<&str> = formatted.split_inclusive('\n').collect();
+
+    diff(&original_lines, &formatted_lines)
+        .into_iter()
+        .map(|change| Hunk {
+            original_text: original_lines[change.old.clone()].concat(),
+            formatted_text: formatted_lines[change.new.clone()].concat(),
+            line_range: change.old,
+            formatted_line_range: change.new,
+        })
+        .collect()
+}
+
+/// Count the lines which differ between the original and formatted text
+///
+/// Each changed region counts the larger of its original and formatted line counts.
+pub(crate) fn changed_line_count(original: &str, formatted: &str) -> usize {
+    let original_lines: Vec<&str> = original.split_inclusive('\n').collect();
+    let formatted_lines: Vec<&str> = formatted.split_inclusive('\n').collect();
+
+    diff(&original_lines, &formatted_lines)
+        .into_iter()
+        .map(|change| change.old.len().max(change.new.len()))
+        .sum()
+}
+
+/// Render the lines which differ between old and new as a unified diff
+///
+/// Each hunk has up to `context` unchanged lines around it, hunks whose context would
+/// overlap are merged. Equal texts result in an empty string.
+#[cfg(feature = "testing")]
+pub(crate) fn unified(
+    old: &str,
+    new: &str,
+    old_name: &str,
+    new_name: &str,
+    context: usize,
+) -> String {
+    let old_lines: Vec<&str> = old.split_inclusive('\n').collect();
+    let new_lines: Vec<&str> = new.split_inclusive('\n').collect();
+    let changes = diff(&old_lines, &new_lines);
+    if changes.is_empty() {
+        return String::new();
+    }
+
+    // Group the changes whose context overlaps
+    let mut groups: Vec<Vec<Change>> = vec![];
+    for change in changes {
+        match groups.last_mut() {
+            Some(group)
+                if change.old.start
+                    <= group.last().expect("groups are not empty").old.end + 2 * context =>
+            {
+                group.push(change)
+            }
+            _ => groups.push(vec![change]),
+        }
+    }
+
+    let mut output = format!("--- {old_name}\n+++ {new_name}\n");
+    for group in groups {
+        let first = group.first().expect("groups are not empty");
+        let last = group.last().expect("groups are not empty");
+        let old_start = first.old.start.saturating_sub(context);
+        let old_end = (last.old.end + context).min(old_lines.len());
+        let new_start = first.new.start - (first.old.start - old_start);
+        let new_end = last.new.end + (old_end - last.old.end);
+        output.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            hunk_range(old_start, old_end - old_start),
+            hunk_range(new_start, new_end - new_start)
+        ));
+
+        let mut position = old_start;
+        for change in &group {
+            push_lines(&mut output, ' ', &old_lines[position..change.old.start]);
+            push_lines(&mut output, '-', &old_lines[change.old.clone()]);
+            push_lines(&mut output, '+', &new_lines[change.new.clone()]);
+            position = change.old.end;
+        }
+        push_lines(&mut output, ' ', &old_lines[position..old_end]);
+    }
+    output
+}
+
+/// The start and length of a side of a hunk header, where lines are 1-based
+#[cfg(feature = "testing")]
+fn hunk_range(start: usize, len: usize) -> String {
+    match len {
+        0 => format!("{start},0"),
+        1 => format!("{}", start + 1),
+        len => format!("{},{len}", start + 1),
+    }
+}
+
+/// Append each line with the given prefix, marking a missing final newline
+#[cfg(feature = "testing")]
+fn push_lines(output: &mut String, prefix: char, lines: &[&str]) {
+    for line in lines {
+        output.push(prefix);
+        output.push_str(line);
+        if !line.ends_with('\n') {
+            output.push_str("\n\\ No newline at end of file\n");
+        }
+    }
+}
+
+/// A single step of the edit script
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Op {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// Compute the regions that differ between old and new
+///
+/// The changes are returned in order and never overlap or touch each other.
+pub(crate) fn diff<T: PartialEq>(old: &[T], new: &[T]) -> Vec<Change> {
+    // Strip the common prefix and suffix, as in most uses only a small section differs
+    let prefix = old
+        .iter()
+        .zip(new.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let ops = edit_script(
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+
+    // Group consecutive edits into changes
+    let mut changes = vec![];
+    let mut current: Option<Change> = None;
+    let (mut old_index, mut new_index) = (prefix, prefix);
+    for op in ops {
+        match op {
+            Op::Equal => {
+                changes.extend(current.take());
+                old_index += 1;
+                new_index += 1;
+            }
+            Op::Delete => {
+                current
+                    .get_or_insert(Change {
+                        old: old_index..old_index,
+                        new: new_index..new_index,
+                    })
+                    .old
+                    .end = old_index + 1;
+                old_index += 1;
+            }
+            Op::Insert => {
+                current
+                    .get_or_insert(Change {
+                        old: old_index..old_index,
+                        new: new_index..new_index,
+                    })
+                    .new
+                    .end = new_index + 1;
+                new_index += 1;
+            }
+        }
+    }
+    changes.extend(current);
+    changes
+}
+
+/// Find the shortest edit script between old and new using Myers' algorithm
+///
+/// This is the linear space variant, which finds the middle snake of the shortest path
+/// and then recurses on either side of it, so memory stays proportional to the input
+/// even when every line differs.
+fn edit_script<T: PartialEq>(old: &[T], new: &[T]) -> Vec<Op> {
+    // v[k + offset] is the furthest x reached on diagonal k, in either direction
+    let max_d = (old.len() + new.len()).div_ceil(2) + 1;
+    let mut forward = vec![0; 2 * max_d + 1];
+    let mut backward = vec![0; 2 * max_d + 1];
+    let mut ops = Vec::with_capacity(old.len() + new.len());
+    conquer(old, new, &mut forward, &mut backward, &mut ops);
+    ops
+}
+
+/// Append the edit script between old and new, splitting at the middle snake
+fn conquer<T: PartialEq>(
+    old: &[T],
+    new: &[T],
+    forward: &mut [isize],
+    backward: &mut [isize],
+    ops: &mut Vec<Op>,
+) {
+    let prefix = old
+        .iter()
+        .zip(new.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+
+    ops.extend(std::iter::repeat_n(Op::Equal, prefix));
+    if old_middle.is_empty() {
+        ops.extend(std::iter::repeat_n(Op::Insert, new_middle.len()));
+    } else if new_middle.is_empty() {
+        ops.extend(std::iter::repeat_n(Op::Delete, old_middle.len()));
+    } else {
+        let (x, y) = middle_snake(old_middle, new_middle, forward, backward);
+        conquer(&old_middle[..x], &new_middle[..y], forward, backward, ops);
+        conquer(&old_middle[x..], &new_middle[y..], forward, backward, ops);
+    }
+    ops.extend(std::iter::repeat_n(Op::Equal, suffix));
+}
+
+/// Find a point on the shortest path between old and new which splits its edits in half
+///
+/// Both sequences must be non-empty and differ in their first and last elements, so that
+/// each side of the point has at least one edit.
+fn middle_snake<T: PartialEq>(
+    old: &[T],
+    new: &[T],
+    forward: &mut [isize],
+    backward: &mut [isize],
+) -> (usize, usize) {
+    let n = old.len() as isize;
+    let m = new.len() as isize;
+    let delta = n - m;
+    let odd = delta & 1 == 1;
+    let offset = (forward.len() / 2) as isize;
+    let index = |k: isize| (k + offset) as usize;
+    forward[index(1)] = 0;
+    backward[index(1)] = 0;
+
+    for d in 0..=(n + m + 1) / 2 {
+        // Extend the paths from the start, checking for overlap with the backward paths
+        for k in (-d..=d).rev().step_by(2) {
+            let mut x = if k == -d || (k != d && forward[index(k - 1)] < forward[index(k + 1)]) {
+                forward[index(k + 1)]
+            } else {
+                forward[index(k - 1)] + 1
+            };
+            let mut y = x - k;
+            let start = (x, y);
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            forward[index(k)] = x;
+            if odd && (k - delta).abs() < d && x + backward[index(delta - k)] >= n {
+                return (start.0 as usize, start.1 as usize);
+            }
+        }
+
+        // Extend the paths from the end, where x and y count from the end of the sequences
+        for k in (-d..=d).rev().step_by(2) {
+            let mut x = if k == -d || (k != d && backward[index(k - 1)] < backward[index(k + 1)]) {
+                backward[index(k + 1)]
+            } else {
+                backward[index(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && old[(n - x - 1) as usize] == new[(m - y - 1) as usize] {
+                x += 1;
+                y += 1;
+            }
+            backward[index(k)] = x;
+            if !odd && (k - delta).abs() <= d && x + forward[index(delta - k)] >= n {
+                return ((n - x) as usize, (m - y) as usize);
+            }
+        }
+    }
+    unreachable!("the forward and backward paths always overlap")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_equal() {
+        assert_eq!(diff(&["a", "b"], &["a", "b"]), vec![]);
+        assert_eq!(diff::<&str>(&[], &[]), vec![]);
+    }
+
+    #[test]
+    fn diff_changes() {
+        let old = ["a", "b", "c", "d", "e"];
+        let new = ["a", "x", "c", "e", "f"];
+        assert_eq!(
+            diff(&old, &new),
+            vec![
+                Change {
+                    old: 1..2,
+                    new: 1..2
+                },
+                Change {
+                    old: 3..4,
+                    new: 3..3
+                },
+                Change {
+                    old: 5..5,
+                    new: 4..5
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_every_line_changed() {
+        let old: Vec<String> = (0..3000).map(|i| format!("a{i}")).collect();
+        let new: Vec<String> = (0..3000).map(|i| format!("b{i}")).collect();
+        assert_eq!(
+            diff(&old, &new),
+            vec![Change {
+                old: 0..3000,
+                new: 0..3000
+            }]
+        );
+    }
+
+    #[test]
+    fn diff_interleaved() {
+        let old = ["a", "b", "c", "a", "b", "b", "a"];
+        let new = ["c", "b", "a", "b", "a", "c"];
+        let ops = edit_script(&old, &new);
+        // The shortest edit script of this classic example has 5 edits
+        assert_eq!(ops.iter().filter(|op| **op != Op::Equal).count(), 5);
+        let mut result = vec![];
+        let mut old_index = 0;
+        let mut new_index = 0;
+        for op in ops {
+            match op {
+                Op::Equal => {
+                    assert_eq!(old[old_index], new[new_index]);
+                    result.push(old[old_index]);
+                    old_index += 1;
+                    new_index += 1;
+                }
+                Op::Delete => old_index += 1,
+                Op::Insert => {
+                    result.push(new[new_index]);
+                    new_index += 1;
+                }
+            }
+        }
+        assert_eq!(result, new);
+    }
+
+    #[test]
+    fn edit_script_is_shortest() {
+        // Compare with the length of the longest common subsequence on pseudo-random inputs
+        let mut seed = 0x2545_f491_u32;
+        let mut next = |bound: u32| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            (seed >> 16) % bound
+        };
+        for _ in 0..500 {
+            let old: Vec<u32> = (0..next(12)).map(|_| next(3)).collect();
+            let new: Vec<u32> = (0..next(12)).map(|_| next(3)).collect();
+
+            let mut lcs = vec![vec![0; new.len() + 1]; old.len() + 1];
+            for i in 0..old.len() {
+                for j in 0..new.len() {
+                    lcs[i + 1][j + 1] = if old[i] == new[j] {
+                        lcs[i][j] + 1
+                    } else {
+                        lcs[i][j + 1].max(lcs[i + 1][j])
+                    };
+                }
+            }
+
+            let ops = edit_script(&old, &new);
+            let equal = ops.iter().filter(|op| **op == Op::Equal).count();
+            let deleted = ops.iter().filter(|op| **op == Op::Delete).count();
+            let inserted = ops.iter().filter(|op| **op == Op::Insert).count();
+            assert_eq!(equal, lcs[old.len()][new.len()], "{old:?} {new:?}");
+            assert_eq!(equal + deleted, old.len());
+            assert_eq!(equal + inserted, new.len());
+        }
+    }
+
+    #[test]
+    fn diff_insert_delete_all() {
+        assert_eq!(
+            diff(&[], &["a", "b"]),
+            vec![Change {
+                old: 0..0,
+                new: 0..2
+            }]
+        );
+        assert_eq!(
+            diff(&["a", "b"], &[]),
+            vec![Change {
+                old: 0..2,
+                new: 0..0
+            }]
+        );
+    }
+
+    #[cfg(feature = "testing")]
+    #[test]
+    fn unified_diff() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\n";
+        let new = "a\nB\nc\nd\ne\nf\ng\nh\ni";
+        assert_eq!(
+            unified(old, new, "expected", "actual", 1),
+            "--- expected\n+++ actual\n@@ -1,3 +1,3 @@\n a\n-b\n+B\n c\n@@ -8 +8,2 @@\n h\n+i\n\\ No newline at end of file\n"
+        );
+        assert_eq!(
+            unified(old, new, "expected", "actual", 3),
+            "--- expected\n+++ actual\n@@ -1,8 +1,9 @@\n a\n-b\n+B\n c\n d\n e\n f\n g\n h\n+i\n\\ No newline at end of file\n"
+        );
+        assert_eq!(
+            unified("", "a\n", "expected", "actual", 3),
+            "--- expected\n+++ actual\n@@ -0,0 +1 @@\n+a\n"
+        );
+        assert_eq!(unified(old, old, "expected", "actual", 3), "");
+    }
+
+    #[test]
+    fn hunks_text() {
+        let original = "int  a;\nint b;\nint  c;\n";
+        let formatted = "int a;\nint b;\nint c;\n";
+        assert_eq!(
+            hunks(original, formatted),
+            vec![
+                Hunk {
+                    line_range: 0..1,
+                    formatted_line_range: 0..1,
+                    original_text: "int  a;\n".to_string(),
+                    formatted_text: "int a;\n".to_string(),
+                },
+                Hunk {
+                    line_range: 2..3,
+                    formatted_line_range: 2..3,
+                    original_text: "int  c;\n".to_string(),
+                    formatted_text: "int c;\n".to_string(),
+                },
+            ]
+        );
+        assert_eq!(hunks(formatted, formatted), vec![]);
+    }
+
+    #[test]
+    fn count_changed_lines() {
+        assert_eq!(changed_line_count("int a;\n", "int a;\n"), 0);
+        assert_eq!(
+            changed_line_count("int  a;\nint b;\nint  c;\n", "int a;\nint b;\nint c;\n"),
+            2
+        );
+        assert_eq!(changed_line_count("int a; int b;\n", "int a;\nint b;\n"), 2);
+    }
+}
@@ -0,0 +1,208 @@
+// SPDX-FileCopyrightText: 2023 Klarälvdalens Datakonsult AB, a KDAB Group company <info@kdab.com>
+// SPDX-FileContributor: Andrew Hayzen <andrew.hayzen@kdab.com>
+//
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use std::fmt::Debug;
+
+/// Describes how to convert input in another encoding to and from UTF-8
+///
+/// clang-format works best with UTF-8, so when set with `ClangFormat::encoding` the
+/// input is decoded to UTF-8 before formatting and the output is encoded back afterwards.
+///
+/// Encoding libraries such as `encoding_rs` can be used by implementing this trait
+/// on a small wrapper type.
+///
+/// # Example
+///
+/// ```
+/// # use clang_format::TextEncoding;
+/// #[derive(Debug)]
+/// struct Ascii;
+///
+/// impl TextEncoding for Ascii {
+///     fn decode(&self, input: &[u8]) -> String {
+///         input.iter().map(|byte| if byte.is_ascii() { *byte as char } else { '\u{FFFD}' }).collect()
+///     }
+///
+///     fn encode(&self, text: &str) -> Vec<u8> {
+///         text.chars().map(|c| if c.is_ascii() { c as u8 } else { b'?' }).collect()
+///     }
+/// }
+/// ```
+pub trait TextEncoding: Debug + Send + Sync {
+    /// Decode the input into UTF-8, replacing malformed sequences
+    fn decode(&self, input: &[u8]) -> String;
+
+    /// Encode the UTF-8 text back into this encoding, replacing unmappable characters
+    fn encode(&self, text: &str) -> Vec<u8>;
+}
+
+/// The ISO-8859-1 (Latin-1) encoding
+///
+/// Characters which cannot be represented in Latin-1 are encoded as `?`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Latin1;
+
+impl TextEncoding for Latin1 {
+    fn decode(&self, input: &[u8]) -> String {
+        input.iter().map(|byte| char::from(*byte)).collect()
+    }
+
+    fn encode(&self, text: &str) -> Vec<u8> {
+        text.chars()
+            .map(|c| u8::try_from(c).unwrap_or(b'?'))
+            .collect()
+    }
+}
+
+/// An encoding detected from the bytes of the input, see `DetectedEncoding::detect`
+///
+/// Decoding removes any byte order mark and encoding restores it, so that the output
+/// is written back in the same encoding as the input.
+#[cfg(feature = "auto_encoding")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DetectedEncoding {
+    /// UTF-8 without a byte order mark
+    Utf8,
+    /// UTF-8 with a byte order mark
+    Utf8Bom,
+    /// UTF-16 little endian with a byte order mark
+    Utf16Le,
+    /// UTF-16 big endian with a byte order mark
+    Utf16Be,
+    /// ISO-8859-1 (Latin-1), used when the input is not valid UTF-8
+    Latin1,
+}
+
+#[cfg(feature = "auto_encoding")]
+impl DetectedEncoding {
+    /// Detect the encoding of the input
+    ///
+    /// The heuristics are, in order
+    ///
+    /// - a UTF-8, UTF-16 little endian, or UTF-16 big endian byte order mark
+    /// - input which is valid UTF-8
+    /// - otherwise Latin-1, as every byte sequence is valid Latin-1
+    ///
+    /// UTF-16 without a byte order mark is not detected, and other single byte encodings,
+    /// such as Windows-1252, are treated as Latin-1 which differs for some characters.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use clang_format::DetectedEncoding;
+    /// # fn main() {
+    /// assert_eq!(DetectedEncoding::detect(b"int a;\n"), DetectedEncoding::Utf8);
+    /// assert_eq!(DetectedEncoding::detect(b"\xff\xfei\0;\0"), DetectedEncoding::Utf16Le);
+    /// assert_eq!(DetectedEncoding::detect(b"// caf\xe9\n"), DetectedEncoding::Latin1);
+    /// # }
+    /// ```
+    pub fn detect(input: &[u8]) -> Self {
+        if input.starts_with(UTF8_BOM) {
+            Self::Utf8Bom
+        } else if input.starts_with(UTF16_LE_BOM) {
+            Self::Utf16Le
+        } else if input.starts_with(UTF16_BE_BOM) {
+            Self::Utf16Be
+        } else if std::str::from_utf8(input).is_ok() {
+            Self::Utf8
+        } else {
+            Self::Latin1
+        }
+    }
+}
+
+#[cfg(feature = "auto_encoding")]
+const UTF8_BOM: &[u8] = b"\xef\xbb\xbf";
+#[cfg(feature = "auto_encoding")]
+const UTF16_LE_BOM: &[u8] = b"\xff\xfe";
+#[cfg(feature = "auto_encoding")]
+const UTF16_BE_BOM: &[u8] = b"\xfe\xff";
+
+#[cfg(feature = "auto_encoding")]
+impl TextEncoding for DetectedEncoding {
+    fn decode(&self, input: &[u8]) -> String {
+        let utf16 = |input: &[u8], from_bytes: fn([u8; 2]) -> u16| {
+            let units = input
+                .chunks(2)
+                .map(|pair| from_bytes([pair[0], pair.get(1).copied().unwrap_or_default()]));
+            char::decode_utf16(units)
+                .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                .collect()
+        };
+
+        match self {
+            Self::Utf8 => String::from_utf8_lossy(input).into_owned(),
+            Self::Utf8Bom => {
+                String::from_utf8_lossy(input.strip_prefix(UTF8_BOM).unwrap_or(input)).into_owned()
+            }
+            Self::Utf16Le => utf16(
+                input.strip_prefix(UTF16_LE_BOM).unwrap_or(input),
+                u16::from_le_bytes,
+            ),
+            Self::Utf16Be => utf16(
+                input.strip_prefix(UTF16_BE_BOM).unwrap_or(input),
+                u16::from_be_bytes,
+            ),
+            Self::Latin1 => Latin1.decode(input),
+        }
+    }
+
+    fn encode(&self, text: &str) -> Vec<u8> {
+        match self {
+            Self::Utf8 => text.as_bytes().to_vec(),
+            Self::Utf8Bom => [UTF8_BOM, text.as_bytes()].concat(),
+            Self::Utf16Le => UTF16_LE_BOM
+                .iter()
+                .copied()
+                .chain(text.encode_utf16().flat_map(u16::to_le_bytes))
+                .collect(),
+            Self::Utf16Be => UTF16_BE_BOM
+                .iter()
+                .copied()
+                .chain(text.encode_utf16().flat_map(u16::to_be_bytes))
+                .collect(),
+            Self::Latin1 => Latin1.encode(text),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn latin1_round_trip() {
+        let input = b"// caf\xe9\nint a;\n";
+        let decoded = Latin1.decode(input);
+        assert_eq!(decoded, "// café\nint a;\n");
+        assert_eq!(Latin1.encode(&decoded), input);
+        assert_eq!(Latin1.encode("€"), b"?");
+    }
+
+    #[cfg(feature = "auto_encoding")]
+    #[test]
+    fn detected_round_trip() {
+        let cases: [(&[u8], DetectedEncoding, &str); 5] = [
+            (
+                b"int a; // \xc3\xa9\n",
+                DetectedEncoding::Utf8,
+                "int a; // é\n",
+            ),
+            (
+                b"\xef\xbb\xbfint a;\n",
+                DetectedEncoding::Utf8Bom,
+                "int a;\n",
+            ),
+            (b"\xff\xfea\0\xe9\0", DetectedEncoding::Utf16Le, "aé"),
+            (b"\xfe\xff\0a\0\xe9", DetectedEncoding::Utf16Be, "aé"),
+            (b"// caf\xe9\n", DetectedEncoding::Latin1, "// café\n"),
+        ];
+        for (input, encoding, text) in cases {
+            assert_eq!(DetectedEncoding::detect(input), encoding);
+            assert_eq!(encoding.decode(input), text);
+            assert_eq!(encoding.encode(text), input);
+        }
+    }
+}
@@ -0,0 +1,117 @@
+// SPDX-FileCopyrightText: 2023 Klarälvdalens Datakonsult AB, a KDAB Group company <info@kdab.com>
+// SPDX-FileContributor: Andrew Hayzen <andrew.hayzen@kdab.com>
+//
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+
+use crate::cancel::CancelHandle;
+use crate::{ClangFormat, ClangFormatError};
+
+/// The result of a format and the waker of the task waiting for it
+#[derive(Default)]
+struct State {
+    result: Option<Result<String, ClangFormatError>>,
+    waker: Option<Waker>,
+}
+
+/// A future which resolves to the output of `ClangFormat::format_async`
+///
+/// The input is formatted on a background thread, so this future can be awaited
+/// from any async runtime. Dropping the future kills the clang-format process.
+#[must_use = "futures do nothing unless polled"]
+pub struct FormatFuture {
+    state: Arc<Mutex<State>>,
+    handle: Arc<CancelHandle>,
+}
+
+impl FormatFuture {
+    /// Start formatting the input on a background thread
+    pub(crate) fn spawn(format: ClangFormat, input: String, handle: Arc<CancelHandle>) -> Self {
+        let state = Arc::new(Mutex::new(State::default()));
+        let thread_state = state.clone();
+        thread::spawn(move || {
+            let result = format.format(input);
+            let mut state = thread_state.lock().expect("format state poisoned");
+            state.result = Some(result);
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        });
+        Self { state, handle }
+    }
+}
+
+impl Future for FormatFuture {
+    type Output = Result<String, ClangFormatError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.state.lock().expect("format state poisoned");
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl Drop for FormatFuture {
+    fn drop(&mut self) {
+        // Once formatting has finished there is no process left to kill
+        self.handle.cancel();
+    }
+}
+
+impl fmt::Debug for FormatFuture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FormatFuture").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{block_on, UppercaseRunner};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn format_async() {
+        let future = ClangFormat::new()
+            .runner(UppercaseRunner)
+            .format_async("int a;\n");
+        assert_eq!(block_on(future).unwrap(), "INT A;\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn format_async_drop_cancels() {
+        // The clang-format arguments are passed to the script, which never exits by itself
+        let future = ClangFormat::new()
+            .command_prefix(&[
+                "sh".to_string(),
+                "-c".to_string(),
+                "sleep 30 & wait".to_string(),
+            ])
+            .format_async("int a;\n");
+        let state = future.state.clone();
+        thread::sleep(Duration::from_millis(50));
+        drop(future);
+
+        let start = Instant::now();
+        loop {
+            if let Some(result) = state.lock().unwrap().result.take() {
+                assert!(matches!(result, Err(ClangFormatError::Cancelled)));
+                break;
+            }
+            assert!(start.elapsed() < Duration::from_secs(10));
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
@@ -0,0 +1,177 @@
+// SPDX-FileCopyrightText: 2023 Klarälvdalens Datakonsult AB, a KDAB Group company <info@kdab.com>
+// SPDX-FileContributor: Andrew Hayzen <andrew.hayzen@kdab.com>
+//
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use thiserror::Error;
+
+/// Describes a `clang-format off` or `clang-format on` comment without a matching comment
+#[derive(Error, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum GuardError {
+    /// A `clang-format off` comment is not followed by a `clang-format on` comment,
+    /// so formatting is disabled for the rest of the input
+    #[error("clang-format off on line {line} has no matching clang-format on")]
+    UnmatchedOff {
+        /// The 1-based line number of the `clang-format off` comment
+        line: usize,
+    },
+    /// A `clang-format on` comment is not preceded by a `clang-format off` comment
+    #[error("clang-format on on line {line} has no matching clang-format off")]
+    UnmatchedOn {
+        /// The 1-based line number of the `clang-format on` comment
+        line: usize,
+    },
+}
+
+/// A comment which disables or enables formatting
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Guard {
+    Off,
+    On,
+}
+
+/// Check that each `// clang-format off` comment in the input has a matching `// clang-format on`
+///
+/// clang-format does not format the lines between these comments, so a missing
+/// `clang-format on` silently disables formatting for the rest of the input.
+/// Both `//` and `/* */` comments are recognized, optionally followed by a reason such as
+/// `// clang-format off: generated table`.
+///
+/// # Example
+///
+/// ```
+/// # use clang_format::{check_format_guards, GuardError};
+/// # fn main() {
+/// assert!(check_format_guards("// clang-format off\nint  a;\n// clang-format on\n").is_ok());
+/// assert_eq!(
+///     check_format_guards("int a;\n// clang-format off\nint  b;\n"),
+///     Err(GuardError::UnmatchedOff { line: 2 })
+/// );
+/// # }
+/// ```
+pub fn check_format_guards(input: &str) -> Result<(), GuardError> {
+    let mut off_line = None;
+    for (line, guard) in guards(input) {
+        match (guard, off_line) {
+            (Guard::Off, None) => off_line = Some(line),
+            // clang-format ignores repeated comments of the same kind
+            (Guard::Off, Some(_)) => {}
+            (Guard::On, Some(_)) => off_line = None,
+            (Guard::On, None) => return Err(GuardError::UnmatchedOn { line }),
+        }
+    }
+
+    match off_line {
+        Some(line) => Err(GuardError::UnmatchedOff { line }),
+        None => Ok(()),
+    }
+}
+
+/// Find the guard comments in the input with their 1-based line numbers
+pub(crate) fn guards(input: &str) -> impl Iterator<Item = (usize, Guard)> + '_ {
+    input
+        .lines()
+        .enumerate()
+        .filter_map(|(index, line)| Some((index + 1, line_guard(line)?)))
+}
+
+/// Remove the guard comments from the input, so that clang-format formats every line
+///
+/// Lines which only contain a guard comment are removed, otherwise only the comment is.
+pub(crate) fn strip_guards(input: &str) -> String {
+    let mut output = String::with_capacity(input.len());
+    for line in input.split_inclusive('\n') {
+        let content = line.trim_end_matches(['\r', '\n']);
+        if line_guard(content).is_none() {
+            output.push_str(line);
+            continue;
+        }
+
+        let start = comment_start(content).unwrap_or_default();
+        let end = match content[start..].strip_prefix("/*") {
+            Some(rest) => rest
+                .find("*/")
+                .map_or(content.len(), |end| start + 2 + end + 2),
+            None => content.len(),
+        };
+        let rest = format!("{}{}", &content[..start], &content[end..]);
+        if !rest.trim().is_empty() {
+            output.push_str(rest.trim_end());
+            output.push_str(&line[content.len()..]);
+        }
+    }
+    output
+}
+
+/// Find the start of the first comment on the line, if any
+fn comment_start(line: &str) -> Option<usize> {
+    line.find("//").into_iter().chain(line.find("/*")).min()
+}
+
+/// Find a guard comment on the line, if any
+fn line_guard(line: &str) -> Option<Guard> {
+    let start = comment_start(line)?;
+    let comment = &line[start + 2..];
+    let comment = comment.split("*/").next().unwrap_or(comment).trim();
+    let rest = comment.strip_prefix("clang-format")?.trim_start();
+
+    let (guard, rest) = if let Some(rest) = rest.strip_prefix("off") {
+        (Guard::Off, rest)
+    } else {
+        (Guard::On, rest.strip_prefix("on")?)
+    };
+    if rest.is_empty() || rest.starts_with(':') {
+        Some(guard)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_guards() {
+        let input = "// clang-format off\nint a; /* clang-format on */\n// clang-format off: table\n  // clang-format online\n// not clang-format on\n//clang-format on\n";
+        assert_eq!(
+            guards(input).collect::<Vec<_>>(),
+            [
+                (1, Guard::Off),
+                (2, Guard::On),
+                (3, Guard::Off),
+                (6, Guard::On)
+            ]
+        );
+    }
+
+    #[test]
+    fn strip_guard_comments() {
+        let input = "// clang-format off\r\nint  a; /* clang-format on */ int b;\n  // clang-format off: table\nint c; // clang-format on\n// clang-format online\n";
+        assert_eq!(
+            strip_guards(input),
+            "int  a;  int b;\nint c;\n// clang-format online\n"
+        );
+        assert_eq!(strip_guards("int a;"), "int a;");
+    }
+
+    #[test]
+    fn check_guards() {
+        assert_eq!(check_format_guards(""), Ok(()));
+        assert_eq!(
+            check_format_guards("// clang-format off\n// clang-format off\n// clang-format on\n"),
+            Ok(())
+        );
+        assert_eq!(
+            check_format_guards("int a;\n// clang-format on\n"),
+            Err(GuardError::UnmatchedOn { line: 2 })
+        );
+        assert_eq!(
+            check_format_guards(
+                "// clang-format off\n// clang-format on\n/* clang-format off */\n"
+            ),
+            Err(GuardError::UnmatchedOff { line: 3 })
+        );
+    }
+}
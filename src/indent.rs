@@ -0,0 +1,137 @@
+// SPDX-FileCopyrightText: 2023 Klarälvdalens Datakonsult AB, a KDAB Group company <info@kdab.com>
+// SPDX-FileContributor: Andrew Hayzen <andrew.hayzen@kdab.com>
+//
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Detecting the indentation which is used by existing code
+
+/// The indentation which is predominantly used by the input, see `detect_indent`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum IndentStyle {
+    /// Lines are indented with tabs
+    Tabs,
+    /// Lines are indented with spaces, each level by the given width
+    Spaces(u32),
+    /// No line is indented, so the indentation cannot be detected
+    Unknown,
+}
+
+impl IndentStyle {
+    /// The style options which reproduce this indentation
+    pub(crate) fn key_values(&self) -> Vec<(String, String)> {
+        let pairs = match self {
+            // One tab for each level of indentation, with spaces for alignment
+            Self::Tabs => vec![
+                ("UseTab", "ForIndentation".to_string()),
+                ("IndentWidth", "4".to_string()),
+                ("TabWidth", "4".to_string()),
+            ],
+            Self::Spaces(width) => vec![
+                ("UseTab", "Never".to_string()),
+                ("IndentWidth", width.to_string()),
+            ],
+            Self::Unknown => vec![],
+        };
+        pairs
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value))
+            .collect()
+    }
+}
+
+/// Detect whether the input is predominantly indented with tabs or spaces
+///
+/// Each indented line counts towards tabs or spaces by its first character. For spaces the
+/// width is the most common increase of indentation between consecutive lines. Blank lines
+/// and the continuation lines of block comments, such as ` * text`, are ignored.
+///
+/// # Example
+///
+/// ```
+/// # use clang_format::{detect_indent, IndentStyle};
+/// # fn main() {
+/// assert_eq!(detect_indent("void f() {\n\tint a;\n}\n"), IndentStyle::Tabs);
+/// assert_eq!(
+///     detect_indent("void f() {\n    if (a) {\n        b();\n    }\n}\n"),
+///     IndentStyle::Spaces(4)
+/// );
+/// assert_eq!(detect_indent("int a;\n"), IndentStyle::Unknown);
+/// # }
+/// ```
+pub fn detect_indent(input: &str) -> IndentStyle {
+    let mut tab_lines = 0usize;
+    let mut space_lines = 0usize;
+    // The number of times each increase of 1 to 8 spaces occurs
+    let mut increases = [0usize; 8];
+    let mut previous = 0usize;
+
+    for line in input.lines() {
+        let content = line.trim_start_matches([' ', '\t']);
+        if content.is_empty() || content.starts_with('*') {
+            continue;
+        }
+        let indent = &line[..line.len() - content.len()];
+        match indent.chars().next() {
+            Some('\t') => tab_lines += 1,
+            Some(_) => {
+                space_lines += 1;
+                let spaces = indent.len();
+                if let Some(count) = spaces
+                    .checked_sub(previous)
+                    .and_then(|increase| increase.checked_sub(1))
+                    .and_then(|index| increases.get_mut(index))
+                {
+                    *count += 1;
+                }
+            }
+            None => {}
+        }
+        previous = if indent.starts_with(' ') {
+            indent.len()
+        } else {
+            0
+        };
+    }
+
+    if tab_lines == 0 && space_lines == 0 {
+        IndentStyle::Unknown
+    } else if tab_lines >= space_lines {
+        IndentStyle::Tabs
+    } else {
+        // Prefer the smaller width when increases are equally common
+        let width = (1..=8)
+            .rev()
+            .max_by_key(|width| increases[width - 1])
+            .filter(|width| increases[width - 1] > 0)
+            .unwrap_or(4);
+        IndentStyle::Spaces(width as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_spaces() {
+        let input = "namespace a {\n  class B {\n    /**\n     * Doc\n     */\n    int c;\n\n    int d;\n  };\n}\n";
+        assert_eq!(detect_indent(input), IndentStyle::Spaces(2));
+        assert_eq!(
+            detect_indent("int a(int b,\n      int c);\n"),
+            IndentStyle::Spaces(6)
+        );
+    }
+
+    #[test]
+    fn detect_mixed() {
+        assert_eq!(
+            detect_indent("{\n\ta;\n\tb;\n    c;\n}\n"),
+            IndentStyle::Tabs
+        );
+        assert_eq!(
+            detect_indent("{\n\ta;\n    b;\n    c;\n}\n"),
+            IndentStyle::Spaces(4)
+        );
+        assert_eq!(detect_indent("\n\n"), IndentStyle::Unknown);
+    }
+}
@@ -0,0 +1,97 @@
+// SPDX-FileCopyrightText: 2023 Klarälvdalens Datakonsult AB, a KDAB Group company <info@kdab.com>
+// SPDX-FileContributor: Andrew Hayzen <andrew.hayzen@kdab.com>
+//
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+/// Describes the language clang-format uses to format the input
+///
+/// This list is created from
+/// <https://clang.llvm.org/docs/ClangFormatStyleOptions.html#language>
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Language {
+    /// Formatting is disabled for this language
+    None,
+    /// C, C++, and CUDA
+    Cpp,
+    /// C#
+    ///
+    /// Since clang-format 9
+    CSharp,
+    /// Java
+    Java,
+    /// JavaScript and TypeScript
+    JavaScript,
+    /// JSON
+    ///
+    /// Since clang-format 14
+    Json,
+    /// Objective-C and Objective-C++
+    ObjC,
+    /// Protocol Buffers
+    Proto,
+    /// TableGen
+    TableGen,
+    /// Protocol Buffer messages in text format
+    ///
+    /// Since clang-format 6
+    TextProto,
+    /// Verilog and SystemVerilog
+    ///
+    /// Since clang-format 16
+    Verilog,
+}
+
+impl Language {
+    /// Every language, in the order of the clang-format documentation
+    pub(crate) const ALL: [Self; 11] = [
+        Self::None,
+        Self::Cpp,
+        Self::CSharp,
+        Self::Java,
+        Self::JavaScript,
+        Self::Json,
+        Self::ObjC,
+        Self::Proto,
+        Self::TableGen,
+        Self::TextProto,
+        Self::Verilog,
+    ];
+
+    /// Converts the enum Language to the string used in clang-format configuration
+    pub(crate) fn as_str(&self) -> &str {
+        match self {
+            Self::None => "None",
+            Self::Cpp => "Cpp",
+            Self::CSharp => "CSharp",
+            Self::Java => "Java",
+            Self::JavaScript => "JavaScript",
+            Self::Json => "Json",
+            Self::ObjC => "ObjC",
+            Self::Proto => "Proto",
+            Self::TableGen => "TableGen",
+            Self::TextProto => "TextProto",
+            Self::Verilog => "Verilog",
+        }
+    }
+
+    /// Converts the string used in clang-format configuration to the enum Language
+    pub(crate) fn from_config_str(value: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|language| language.as_str() == value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn language_config_str() {
+        assert_eq!(Language::from_config_str("Cpp"), Some(Language::Cpp));
+        assert_eq!(Language::from_config_str("ObjC"), Some(Language::ObjC));
+        assert_eq!(Language::from_config_str("Rust"), None);
+        assert_eq!(Language::CSharp.as_str(), "CSharp");
+    }
+}
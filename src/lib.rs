@@ -10,21 +10,106 @@
 //!
 //! This allows for formatting a given input using `clang-format` from the system.
 
-use std::env;
-use std::io::Write;
-use std::process::{Command, Stdio};
+use std::ffi::OsString;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Output};
+use std::sync::Arc;
+use std::time::Duration;
 use thiserror::Error;
 
+#[cfg(feature = "async")]
+mod batch_future;
+mod builder;
+mod cancel;
+#[cfg(feature = "codegen")]
+mod codegen;
+mod cursor;
+mod diagnostic;
+mod diff;
+#[cfg(feature = "encoding")]
+mod encoding;
+#[cfg(feature = "async")]
+mod format_future;
+mod guards;
+mod hash;
+mod indent;
+mod language;
+mod pool;
+#[cfg(feature = "debug_log")]
+mod process_log;
+mod replacements;
+#[cfg(feature = "resource_usage")]
+mod resource_usage;
+mod runner;
+mod structure;
+mod style;
+mod style_config;
+#[cfg(unix)]
+mod sys;
+#[cfg(test)]
+mod test_utils;
+#[cfg(feature = "testing")]
+pub mod testing;
+mod version;
+mod yaml;
+
+#[cfg(feature = "async")]
+pub use batch_future::{FormatBatchFuture, FormatBatchStream};
+pub use builder::ClangFormat;
+#[cfg(feature = "codegen")]
+pub use codegen::{format_codegen, format_codegen_with, CodegenCleanup};
+pub use cursor::FormattedRegion;
+pub use diagnostic::{Diagnostic, StyleError};
+pub use diff::Hunk;
+#[cfg(feature = "auto_encoding")]
+pub use encoding::DetectedEncoding;
+#[cfg(feature = "encoding")]
+pub use encoding::{Latin1, TextEncoding};
+#[cfg(feature = "async")]
+pub use format_future::FormatFuture;
+pub use guards::{check_format_guards, GuardError};
+pub use indent::{detect_indent, IndentStyle};
+pub use language::Language;
+pub use pool::FormatterPool;
+#[cfg(feature = "debug_log")]
+pub use process_log::{LogEntry, LogStream, ProcessLog};
+pub use replacements::{apply_replacements, Replacement};
+#[cfg(feature = "resource_usage")]
+pub use resource_usage::ResourceUsage;
+pub use runner::{FormatRunner, ProcessRunner};
+pub use style_config::{StyleConfig, UseTab};
+pub use version::ClangFormatVersion;
+
+/// Re-exports the commonly used items of this crate
+///
+/// # Example
+///
+/// ```
+/// use clang_format::prelude::*;
+/// # fn main() {
+/// let output = ClangFormat::new()
+///     .style(ClangFormatStyle::Mozilla)
+///     .format("struct Test {};\n");
+/// assert!(output.is_ok());
+/// # }
+/// ```
+pub mod prelude {
+    pub use crate::{clang_format, clang_format_with_style};
+    pub use crate::{ClangFormat, ClangFormatError, ClangFormatStyle};
+}
+
 /// Describes the style to pass to clang-format
 ///
 /// This list is created from
 /// <https://clang.llvm.org/docs/ClangFormatStyleOptions.html#basedonstyle>
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, Default, PartialEq)]
 #[non_exhaustive]
 pub enum ClangFormatStyle {
     /// A style complying with [Chromium’s style guide](https://chromium.googlesource.com/chromium/src/+/refs/heads/main/styleguide/styleguide.md)
     Chromium,
     /// Use the default clang-format style
+    #[default]
     Default,
     /// clang-format will try to find the .clang-format file located in the closest parent directory of the current directory.
     File,
@@ -44,6 +129,12 @@ pub enum ClangFormatStyle {
     Mozilla,
     /// A style complying with [WebKit’s style guide](https://www.webkit.org/coding/coding-style.html)
     WebKit,
+    /// Do not pass the `--style` argument, so that clang-format's own default behavior applies
+    ///
+    /// This differs from `Default`, which forces the built-in default options with `--style={}`,
+    /// and from `File`, which always searches for a `.clang-format` file. clang-format itself
+    /// defaults to `file` with a fallback of `LLVM`, but this may differ between versions or builds.
+    Unspecified,
     /// Specify a custom input to the `--style` argument of clang-format
     ///
     /// # Example
@@ -67,7 +158,7 @@ pub enum ClangFormatStyle {
 
 impl ClangFormatStyle {
     /// Converts the enum ClangFormatStyle to a string that clang-format expects
-    fn as_str(&self) -> &str {
+    pub(crate) fn as_str(&self) -> &str {
         match self {
             Self::Chromium => "Chromium",
             // Will use clang-format default options
@@ -80,28 +171,295 @@ impl ClangFormatStyle {
             Self::Microsoft => "Microsoft",
             Self::Mozilla => "Mozilla",
             Self::WebKit => "WebKit",
+            // No --style argument is passed
+            Self::Unspecified => "",
             // Custom style arguments
             Self::Custom(custom) => custom.as_str(),
         }
     }
 }
 
+/// Describes how to arrange qualifiers such as `const` and `volatile`
+///
+/// This is passed to the `--qualifier-alignment` argument of clang-format,
+/// overriding the `QualifierAlignment` of the style.
+///
+/// Since clang-format 14
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum QualifierAlignment {
+    /// Don't change the alignment of qualifiers
+    Leave,
+    /// Move qualifiers to the left of the type, eg `const int`
+    Left,
+    /// Move qualifiers to the right of the type, eg `int const`
+    Right,
+}
+
+impl QualifierAlignment {
+    /// Converts the enum QualifierAlignment to a string that clang-format expects
+    pub(crate) fn as_str(&self) -> &str {
+        match self {
+            Self::Leave => "Leave",
+            Self::Left => "Left",
+            Self::Right => "Right",
+        }
+    }
+}
+
+/// Describes the command line clang-format was executed with
+///
+/// This is included in errors to help diagnose why formatting differs between machines.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CommandLine {
+    /// The binary which was executed
+    pub binary: OsString,
+    /// The arguments passed to the binary
+    pub args: Vec<OsString>,
+}
+
+impl CommandLine {
+    pub(crate) fn new(command: &Command) -> Self {
+        Self {
+            binary: command.get_program().to_os_string(),
+            args: command.get_args().map(|arg| arg.to_os_string()).collect(),
+        }
+    }
+
+    /// The exact value of the `--style` argument, if any
+    ///
+    /// This is the style after any merging or conversion, which helps to diagnose
+    /// why a `Custom` style did not apply as expected.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use clang_format::{ClangFormat, ClangFormatStyle};
+    /// # fn main() {
+    /// let style = ClangFormatStyle::Mozilla.merge(ClangFormatStyle::Custom("{ IndentWidth: 8 }".to_string()));
+    /// let command_line = ClangFormat::new().style(style).command_line().unwrap();
+    /// assert_eq!(command_line.style(), Some("{ BasedOnStyle: Mozilla, IndentWidth: 8 }"));
+    /// # }
+    /// ```
+    pub fn style(&self) -> Option<&str> {
+        self.args
+            .iter()
+            .find_map(|arg| arg.to_str()?.strip_prefix("--style="))
+    }
+}
+
+impl fmt::Display for CommandLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.binary.to_string_lossy())?;
+        for arg in &self.args {
+            write!(f, " {}", arg.to_string_lossy())?;
+        }
+        Ok(())
+    }
+}
+
+/// Describes whether formatting changed the input
+///
+/// # Example
+///
+/// ```
+/// # use clang_format::{format_outcome, ClangFormatStyle, FormatOutcome};
+/// # fn main() {
+/// match format_outcome("int  a;\n", &ClangFormatStyle::Default) {
+///     Ok(FormatOutcome::Unchanged) => println!("already formatted"),
+///     Ok(FormatOutcome::Changed(output)) => assert_eq!(output, "int a;\n"),
+///     Err(err) => panic!("{err}"),
+/// }
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FormatOutcome {
+    /// The output is identical to the input
+    Unchanged,
+    /// The output differs from the input
+    Changed(String),
+}
+
+/// Describes which configuration `ClangFormatStyle::File` resolves to
+///
+/// See `explain_file_style` for an example.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FileStyleResolution {
+    /// A configuration file was found at the given path
+    Found(PathBuf),
+    /// No configuration file was found, so clang-format uses the fallback style
+    NotFound {
+        /// The fallback style which clang-format uses
+        fallback: ClangFormatStyle,
+    },
+}
+
+/// The formatted output of a successful run with the warnings clang-format emitted
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FormatOutputWithWarnings {
+    /// The formatted output
+    pub output: String,
+    /// Each line of stderr which is a warning, such as a deprecated style option
+    pub warnings: Vec<String>,
+}
+
+/// The formatted output with the edits clang-format made to produce it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FormatOutputWithReplacements {
+    /// The formatted output
+    pub output: String,
+    /// The edits which turn the input into the output
+    pub replacements: Vec<Replacement>,
+}
+
+/// Describes the amount of data and time formatting took
+///
+/// These can be recorded as metrics by services which format on request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FormatStats {
+    /// The number of bytes written to clang-format
+    pub input_bytes: usize,
+    /// The number of bytes read from clang-format
+    pub output_bytes: usize,
+    /// The time from spawning clang-format until it exited
+    pub duration: Duration,
+    /// The time from spawning clang-format until the first byte of output was read,
+    /// or `None` if there was no output
+    pub time_to_first_byte: Option<Duration>,
+    /// The CPU time and memory clang-format used, or `None` if it could not be measured
+    ///
+    /// This is measured with `getrusage(RUSAGE_CHILDREN)` before and after formatting, so it
+    /// is only measured on Unix and includes any other child processes which exited meanwhile,
+    /// such as from formatting on other threads.
+    #[cfg(feature = "resource_usage")]
+    pub resource_usage: Option<ResourceUsage>,
+}
+
 /// Describes which error spawning clang-format failed with
 #[derive(Error, Debug)]
-enum ClangFormatError {
+#[non_exhaustive]
+pub enum ClangFormatError {
+    /// Spawning or communicating with the clang-format process failed
     #[error(transparent)]
     Io(#[from] std::io::Error),
+    /// The output of clang-format was not valid UTF-8
     #[error(transparent)]
     FromUtf8Error(#[from] std::string::FromUtf8Error),
+    /// The clang-format process could not be spawned
+    #[error("Failed to spawn clang-format: {source}")]
+    SpawnFailure {
+        /// The command line which was attempted
+        command: CommandLine,
+        /// The error from spawning the process
+        #[source]
+        source: std::io::Error,
+    },
     // TODO: use ExitStatusError once it is a stable feature
     // https://doc.rust-lang.org/stable/std/process/struct.ExitStatusError.html
     // https://github.com/rust-lang/rust/issues/84908
-    #[error("Clang format process exited with a non-zero status")]
-    NonZeroExitStatus,
+    /// The clang-format process exited with a non-zero status
+    #[error("Clang format process exited with a non-zero status ({status})")]
+    NonZeroExitStatus {
+        /// The command line which was executed
+        command: CommandLine,
+        /// The exit status of the process
+        status: ExitStatus,
+    },
+    /// The style contained a key which clang-format does not know
+    #[error("Unknown clang-format style key: {0}")]
+    UnknownStyleKey(String),
+    /// clang-format succeeded but returned no output for non-empty input
+    #[error("Clang format returned empty output for non-empty input")]
+    UnexpectedEmptyOutput,
+    /// The output of `clang-format --version` could not be parsed
+    #[error("Unable to parse clang-format version: {0}")]
+    UnparseableVersion(String),
+    /// The style requires a newer version of clang-format than the one found
+    #[error("Style {style:?} requires clang-format {required_version} but found {found_version}")]
+    UnsupportedStyle {
+        /// The style which was requested
+        style: ClangFormatStyle,
+        /// The first version of clang-format which supports the style
+        required_version: ClangFormatVersion,
+        /// The version of clang-format which was found
+        found_version: ClangFormatVersion,
+    },
+    /// The version of clang-format is older than the version required
+    #[error("Requires clang-format {required_version} but found {found_version}")]
+    VersionTooOld {
+        /// The minimum version of clang-format which is required
+        required_version: ClangFormatVersion,
+        /// The version of clang-format which was found
+        found_version: ClangFormatVersion,
+    },
+    /// The input was larger than the configured limit
+    #[error("Input of {size} bytes exceeds the limit of {limit} bytes")]
+    InputTooLarge {
+        /// The size of the input in bytes
+        size: usize,
+        /// The maximum size of the input in bytes
+        limit: usize,
+    },
+    /// The output was larger than the configured limit, so clang-format was stopped
+    #[error("Output exceeds the limit of {limit} bytes")]
+    OutputTooLarge {
+        /// The maximum size of the output in bytes
+        limit: usize,
+    },
+    /// clang-format resolved a language which is not known
+    #[error("Unknown clang-format language: {0}")]
+    UnknownLanguage(String),
+    /// The style configuration could not be converted into an inline style
+    #[error("Invalid clang-format style configuration: {0}")]
+    InvalidStyleConfig(String),
+    /// The string is not the name of a style, see the `FromStr` implementation of `ClangFormatStyle`
+    #[error("Unknown clang-format style name: {0}")]
+    UnknownStyleName(String),
+    /// clang-format succeeded but printed diagnostics, see `ClangFormat::reject_diagnostics`
+    #[error("Clang format printed diagnostics: {0}")]
+    Diagnostics(String),
+    /// The formatted output does not have the same structure as the input,
+    /// see `ClangFormat::format_and_sanity_check`
+    #[error("Formatted output does not match the structure of the input: {0}")]
+    StructuralMismatch(String),
+    /// The replacements output by clang-format could not be parsed
+    #[error("Unable to parse clang-format replacements: {0}")]
+    UnparseableReplacements(String),
+    /// The replacements could not be applied to the input, see `apply_replacements`
+    #[error("Unable to apply replacements: {0}")]
+    InvalidReplacements(String),
+    /// The cursor output by clang-format could not be parsed
+    #[error("Unable to parse clang-format cursor: {0}")]
+    UnparseableCursor(String),
+    /// clang-format could not parse the style, see `ClangFormat::strict`
+    #[error("Invalid clang-format style: {0}")]
+    InvalidStyle(StyleError),
+    /// The format was cancelled and clang-format was killed, see `ClangFormat::format_async`
+    #[error("Formatting was cancelled")]
+    Cancelled,
+    /// No definition of the function was found, see `ClangFormat::format_function`
+    #[error("No definition of function {0} was found")]
+    FunctionNotFound(String),
+    /// The output still changed after formatting it the maximum number of times,
+    /// see `ClangFormat::format_to_fixpoint`
+    #[error("Formatting did not stabilize within {iterations} iterations")]
+    NotConverged {
+        /// The number of times the input was formatted
+        iterations: usize,
+    },
+    /// Formatting the same input produced different outputs, see `ClangFormat::assert_deterministic`
+    #[error("Formatting run {run} produced a different output than the first run")]
+    NondeterministicOutput {
+        /// The 1-based number of the run which differed
+        run: usize,
+    },
 }
 
 /// Execute clang-format with the given input, using the given style, and collect the output
 ///
+/// The style can be a `ClangFormatStyle`, a reference to one, or a string which is
+/// converted with `From<&str>` for `ClangFormatStyle`, such as `"Google"`.
+///
 /// # Example
 ///
 /// ```
@@ -118,40 +476,815 @@ enum ClangFormatError {
 /// # }
 /// ```
 pub fn clang_format_with_style(
+    input: impl AsRef<str>,
+    style: impl Into<ClangFormatStyle>,
+) -> Result<String, ClangFormatError> {
+    ClangFormat::new().style(style).format(input)
+}
+
+/// Format the input with the given style the given number of times and check that every output is the same
+///
+/// See `ClangFormat::assert_deterministic` for details.
+///
+/// # Example
+///
+/// ```
+/// # use clang_format::{assert_deterministic, ClangFormatStyle};
+/// # fn main() {
+/// assert!(assert_deterministic("int  a;\n", &ClangFormatStyle::Default, 3).is_ok());
+/// # }
+/// ```
+pub fn assert_deterministic(
     input: &str,
-    style: &ClangFormatStyle,
-) -> Result<String, impl std::error::Error> {
-    // Create and try to spawn the command with the specified style
-    let clang_binary = env::var("CLANG_FORMAT_BINARY").unwrap_or("clang-format".to_string());
-    let mut child = Command::new(clang_binary.as_str())
-        .arg(format!("--style={}", style.as_str()))
-        .stdin(Stdio::piped())
-        .stdout(Stdio::piped())
-        .spawn()?;
-
-    // Write the input to stdin
-    //
-    // Note we place inside a scope to ensure that stdin is closed
-    {
-        let mut stdin = child.stdin.take().expect("no stdin handle");
-        write!(stdin, "{}", input)?;
-    }
+    style: impl Into<ClangFormatStyle>,
+    runs: usize,
+) -> Result<(), ClangFormatError> {
+    ClangFormat::new()
+        .style(style)
+        .assert_deterministic(input, runs)
+}
 
-    // Wait for the output and parse it
-    let output = child.wait_with_output()?;
-    // TODO: use exit_ok() once it is a stable feature
-    // https://doc.rust-lang.org/stable/std/process/struct.ExitStatus.html#method.exit_ok
-    // https://github.com/rust-lang/rust/issues/84908
-    if output.status.success() {
-        Ok(String::from_utf8(output.stdout)?)
-    } else {
-        Err(ClangFormatError::NonZeroExitStatus)
+/// Execute clang-format with the given input and style, and collect the output as an `Arc<str>`
+///
+/// See `ClangFormat::format_shared` for details.
+///
+/// # Example
+///
+/// ```
+/// # use clang_format::{clang_format_shared, ClangFormatStyle};
+/// # use std::sync::Arc;
+/// # fn main() {
+/// let output: Arc<str> = clang_format_shared("int  a;\n", &ClangFormatStyle::Default).unwrap();
+/// assert_eq!(&*output, "int a;\n");
+/// # }
+/// ```
+pub fn clang_format_shared(
+    input: impl AsRef<str>,
+    style: impl Into<ClangFormatStyle>,
+) -> Result<Arc<str>, ClangFormatError> {
+    ClangFormat::new().style(style).format_shared(input)
+}
+
+/// Execute clang-format with the contents of the buffer, using the given style, and replace them with the output
+///
+/// See `ClangFormat::format_bytes_in_place` for details.
+///
+/// # Example
+///
+/// ```
+/// # use clang_format::{clang_format_in_place_bytes, ClangFormatStyle};
+/// # fn main() {
+/// let mut buf = b"int  a;\n".to_vec();
+/// assert!(clang_format_in_place_bytes(&mut buf, &ClangFormatStyle::Default).is_ok());
+/// assert_eq!(buf, b"int a;\n");
+/// # }
+/// ```
+pub fn clang_format_in_place_bytes(
+    buf: &mut Vec<u8>,
+    style: impl Into<ClangFormatStyle>,
+) -> Result<(), ClangFormatError> {
+    ClangFormat::new().style(style).format_bytes_in_place(buf)
+}
+
+/// Execute clang-format on the bodies of the functions with the given name only, using the given style
+///
+/// See `ClangFormat::format_function` for details and the limitations of finding functions.
+///
+/// # Example
+///
+/// ```
+/// # use clang_format::{format_function, ClangFormatStyle};
+/// # fn main() {
+/// let input = "int  a;\nvoid run()\n{\n  int    b;\n}\n";
+/// let output = format_function(input, "run", &ClangFormatStyle::Default);
+/// assert!(output.is_ok());
+/// assert_eq!(output.unwrap(), "int  a;\nvoid run() { int b; }\n");
+/// # }
+/// ```
+pub fn format_function(
+    input: &str,
+    name: &str,
+    style: impl Into<ClangFormatStyle>,
+) -> Result<String, ClangFormatError> {
+    ClangFormat::new().style(style).format_function(input, name)
+}
+
+/// Execute clang-format with the given input and style repeatedly until the output no longer changes
+///
+/// See `ClangFormat::format_to_fixpoint` for details.
+///
+/// # Example
+///
+/// ```
+/// # use clang_format::{format_to_fixpoint, ClangFormatStyle};
+/// # fn main() {
+/// let output = format_to_fixpoint("int  a;\n", &ClangFormatStyle::Default, 5);
+/// assert!(output.is_ok());
+/// assert_eq!(output.unwrap(), "int a;\n");
+/// # }
+/// ```
+pub fn format_to_fixpoint(
+    input: &str,
+    style: impl Into<ClangFormatStyle>,
+    max_iters: usize,
+) -> Result<String, ClangFormatError> {
+    ClangFormat::new()
+        .style(style)
+        .format_to_fixpoint(input, max_iters)
+}
+
+/// Execute clang-format with the given input and style, failing if any diagnostic was printed
+///
+/// When clang-format prints anything to stderr, even when it succeeds,
+/// `ClangFormatError::Diagnostics` is returned. See `ClangFormat::reject_diagnostics`.
+///
+/// # Example
+///
+/// ```
+/// # use clang_format::{format_strict, ClangFormatStyle};
+/// # fn main() {
+/// let output = format_strict("int  a;\n", &ClangFormatStyle::Mozilla);
+/// assert!(output.is_ok());
+/// assert_eq!(output.unwrap(), "int a;\n");
+/// # }
+/// ```
+pub fn format_strict(
+    input: impl AsRef<str>,
+    style: impl Into<ClangFormatStyle>,
+) -> Result<String, ClangFormatError> {
+    ClangFormat::new()
+        .style(style)
+        .reject_diagnostics(true)
+        .format(input)
+}
+
+/// Execute clang-format with the given input and the style configuration as a string
+///
+/// The configuration is the contents of a `.clang-format` file, see
+/// `ClangFormat::format_with_config_string` for details.
+///
+/// Since clang-format 14
+///
+/// # Example
+///
+/// ```
+/// # use clang_format::format_with_config_string;
+/// # fn main() {
+/// let output = format_with_config_string("struct Test {};\n", "BasedOnStyle: Mozilla\n");
+/// assert!(output.is_ok());
+/// assert_eq!(output.unwrap(), "struct Test\n{};\n");
+/// # }
+/// ```
+pub fn format_with_config_string(
+    input: &str,
+    config_yaml: &str,
+) -> Result<String, ClangFormatError> {
+    ClangFormat::new().format_with_config_string(input, config_yaml)
+}
+
+/// Execute clang-format with the given input and style, as if it had no `clang-format off` regions
+///
+/// This is intended for analysis and the output should not be used to overwrite the source,
+/// see `ClangFormat::format_ignoring_guards` for details.
+///
+/// # Example
+///
+/// ```
+/// # use clang_format::{format_ignoring_guards, ClangFormatStyle};
+/// # fn main() {
+/// let input = "// clang-format off\nint  a;\n// clang-format on\n";
+/// let output = format_ignoring_guards(input, &ClangFormatStyle::Default);
+/// assert!(output.is_ok());
+/// assert_eq!(output.unwrap(), "int a;\n");
+/// # }
+/// ```
+pub fn format_ignoring_guards(
+    input: &str,
+    style: impl Into<ClangFormatStyle>,
+) -> Result<String, ClangFormatError> {
+    ClangFormat::new()
+        .style(style)
+        .format_ignoring_guards(input)
+}
+
+/// Execute clang-format with the given input and style, and indent each line of the output
+///
+/// See `ClangFormat::format_indented_by` for details.
+///
+/// # Example
+///
+/// ```
+/// # use clang_format::{format_indented_by, ClangFormatStyle};
+/// # fn main() {
+/// let output = format_indented_by("int  a;\n\nint  b;\n", &ClangFormatStyle::Default, 2);
+/// assert!(output.is_ok());
+/// assert_eq!(output.unwrap(), "  int a;\n\n  int b;\n");
+/// # }
+/// ```
+pub fn format_indented_by(
+    input: &str,
+    style: impl Into<ClangFormatStyle>,
+    spaces: usize,
+) -> Result<String, ClangFormatError> {
+    ClangFormat::new()
+        .style(style)
+        .format_indented_by(input, spaces)
+}
+
+/// Execute clang-format with the given input and style, and split the output into lines
+///
+/// See `ClangFormat::format_lines` for how lines are split.
+///
+/// # Example
+///
+/// ```
+/// # use clang_format::{clang_format_lines, ClangFormatStyle};
+/// # fn main() {
+/// let lines = clang_format_lines("struct Test {\n\n};\n", &ClangFormatStyle::Mozilla);
+/// assert!(lines.is_ok());
+/// assert_eq!(lines.unwrap(), ["struct Test", "{};"]);
+/// # }
+/// ```
+pub fn clang_format_lines(
+    input: &str,
+    style: impl Into<ClangFormatStyle>,
+) -> Result<Vec<String>, ClangFormatError> {
+    ClangFormat::new().style(style).format_lines(input)
+}
+
+/// Explain which configuration `ClangFormatStyle::File` resolves to for the given path
+///
+/// See `ClangFormat::explain_file_style` for details.
+///
+/// # Example
+///
+/// ```no_run
+/// # use clang_format::{explain_file_style, FileStyleResolution};
+/// # fn main() {
+/// match explain_file_style("src/main.cpp").unwrap() {
+///     FileStyleResolution::Found(path) => println!("using {}", path.display()),
+///     FileStyleResolution::NotFound { fallback } => println!("falling back to {fallback:?}"),
+/// }
+/// # }
+/// ```
+pub fn explain_file_style(
+    start: impl AsRef<Path>,
+) -> Result<FileStyleResolution, ClangFormatError> {
+    ClangFormat::new().explain_file_style(start)
+}
+
+/// Execute clang-format with the given input and style, and check that the structure is preserved
+///
+/// See `ClangFormat::format_and_sanity_check` for details.
+///
+/// # Example
+///
+/// ```
+/// # use clang_format::{format_and_sanity_check, ClangFormatStyle};
+/// # fn main() {
+/// let output = format_and_sanity_check("struct Test {\n\n};\n", &ClangFormatStyle::Mozilla);
+/// assert!(output.is_ok());
+/// assert_eq!(output.unwrap(), "struct Test\n{};\n");
+/// # }
+/// ```
+pub fn format_and_sanity_check(
+    input: &str,
+    style: impl Into<ClangFormatStyle>,
+) -> Result<String, ClangFormatError> {
+    ClangFormat::new()
+        .style(style)
+        .format_and_sanity_check(input)
+}
+
+/// Execute clang-format with the given input as if it was run from the given directory
+///
+/// This is useful with `ClangFormatStyle::File` for input that does not exist on disk yet,
+/// as clang-format searches for a `.clang-format` file starting from `dir`.
+///
+/// # Example
+///
+/// ```no_run
+/// # use clang_format::{format_in_dir, ClangFormatStyle};
+/// # fn main() {
+/// let output = format_in_dir("struct Test {};", "src/generated", &ClangFormatStyle::File);
+/// assert!(output.is_ok());
+/// # }
+/// ```
+pub fn format_in_dir(
+    input: &str,
+    dir: impl AsRef<Path>,
+    style: impl Into<ClangFormatStyle>,
+) -> Result<String, ClangFormatError> {
+    ClangFormat::new()
+        .style(style)
+        .current_dir(dir)
+        .format(input)
+}
+
+/// Execute clang-format on the file at the given path, using the given style, and replace it
+///
+/// The file is streamed through clang-format into a temporary file next to it, which is
+/// then atomically renamed over the original, so the file is never partially written.
+/// The language is detected from the path, to format the file as if it had another
+/// name use `ClangFormat::assume_filename` with `ClangFormat::format_file_streamed`.
+///
+/// # Example
+///
+/// ```no_run
+/// # use clang_format::{clang_format_file_streamed, ClangFormatStyle};
+/// # fn main() {
+/// let result = clang_format_file_streamed("src/test.cpp", &ClangFormatStyle::Mozilla);
+/// assert!(result.is_ok());
+/// # }
+/// ```
+pub fn clang_format_file_streamed(
+    path: impl AsRef<Path>,
+    style: impl Into<ClangFormatStyle>,
+) -> Result<(), ClangFormatError> {
+    ClangFormat::new().style(style).format_file_streamed(path)
+}
+
+/// Execute clang-format on the lines of `new` that differ from `old`, using the given style
+///
+/// The lines are compared with a line-based diff and only the inserted or modified
+/// lines are passed to clang-format via `--lines`, so untouched regions are preserved exactly.
+/// Lines which were only removed leave nothing to reformat. If nothing changed `new` is
+/// returned as is without running clang-format.
+///
+/// # Example
+///
+/// ```
+/// # use clang_format::{format_changed_lines, ClangFormatStyle};
+/// # fn main() {
+/// let old = "int  a;\nint b;\n";
+/// let new = "int  a;\nint  b;\n";
+/// let output = format_changed_lines(old, new, &ClangFormatStyle::Default);
+/// assert!(output.is_ok());
+/// assert_eq!(output.unwrap(), "int  a;\nint b;\n");
+/// # }
+/// ```
+pub fn format_changed_lines(
+    old: &str,
+    new: &str,
+    style: impl Into<ClangFormatStyle>,
+) -> Result<String, ClangFormatError> {
+    let old_lines: Vec<&str> = old.split_inclusive('\n').collect();
+    let new_lines: Vec<&str> = new.split_inclusive('\n').collect();
+
+    // clang-format lines are 1-based and the range is inclusive
+    let args: Vec<String> = diff::diff(&old_lines, &new_lines)
+        .into_iter()
+        .filter(|change| !change.new.is_empty())
+        .map(|change| format!("--lines={}:{}", change.new.start + 1, change.new.end))
+        .collect();
+
+    // Without any --lines clang-format would format the whole input
+    if args.is_empty() {
+        return Ok(new.to_string());
     }
+
+    ClangFormat::new().style(style).format_with_args(new, &args)
+}
+
+/// Execute clang-format with the given input, using the given style, and return the regions which changed
+///
+/// Each `Hunk` contains the original and formatted text of a changed region, which is
+/// useful for showing side-by-side changes without parsing a diff.
+///
+/// # Example
+///
+/// ```
+/// # use clang_format::{format_hunks, ClangFormatStyle};
+/// # fn main() {
+/// let input = "int  a;\nint b;\n";
+/// let hunks = format_hunks(input, &ClangFormatStyle::Default).unwrap();
+/// assert_eq!(hunks.len(), 1);
+/// assert_eq!(hunks[0].line_range, 0..1);
+/// assert_eq!(hunks[0].original_text, "int  a;\n");
+/// assert_eq!(hunks[0].formatted_text, "int a;\n");
+/// # }
+/// ```
+pub fn format_hunks(
+    input: &str,
+    style: impl Into<ClangFormatStyle>,
+) -> Result<Vec<Hunk>, ClangFormatError> {
+    ClangFormat::new().style(style).format_hunks(input)
+}
+
+/// Execute clang-format with the given input, using the given style, and count the lines which would change
+///
+/// This is useful for tracking how much of a codebase is not yet formatted.
+/// See `ClangFormat::changed_line_count` for how lines are counted.
+///
+/// # Example
+///
+/// ```
+/// # use clang_format::{changed_line_count, ClangFormatStyle};
+/// # fn main() {
+/// let count = changed_line_count("int  a;\nint b;\n", &ClangFormatStyle::Default);
+/// assert!(count.is_ok());
+/// assert_eq!(count.unwrap(), 1);
+/// # }
+/// ```
+pub fn changed_line_count(
+    input: &str,
+    style: impl Into<ClangFormatStyle>,
+) -> Result<usize, ClangFormatError> {
+    ClangFormat::new().style(style).changed_line_count(input)
+}
+
+/// Execute clang-format with the given input and style, and describe whether it changed
+///
+/// See `FormatOutcome` for an example.
+pub fn format_outcome(
+    input: impl AsRef<str>,
+    style: impl Into<ClangFormatStyle>,
+) -> Result<FormatOutcome, ClangFormatError> {
+    ClangFormat::new().style(style).format_outcome(input)
+}
+
+/// Execute clang-format for each of the inputs in parallel using the given style
+///
+/// See `ClangFormat::format_batch` for details.
+pub fn clang_format_batch<I: AsRef<str> + Sync>(
+    inputs: &[I],
+    style: impl Into<ClangFormatStyle>,
+) -> Vec<Result<String, ClangFormatError>> {
+    ClangFormat::new().style(style).format_batch(inputs)
+}
+
+/// Execute clang-format with the given input and style, and collect the output with any warnings
+///
+/// See `ClangFormat::format_with_warnings` for details.
+pub fn clang_format_with_warnings(
+    input: impl AsRef<str>,
+    style: impl Into<ClangFormatStyle>,
+) -> Result<FormatOutputWithWarnings, ClangFormatError> {
+    ClangFormat::new().style(style).format_with_warnings(input)
+}
+
+/// Execute clang-format with the given input and style, and collect the output with `FormatStats`
+///
+/// # Example
+///
+/// ```
+/// # use clang_format::{clang_format_with_stats, ClangFormatStyle};
+/// # fn main() {
+/// let output = clang_format_with_stats("int  a;\n", &ClangFormatStyle::Default);
+/// assert!(output.is_ok());
+/// let (output, stats) = output.unwrap();
+/// assert_eq!(output, "int a;\n");
+/// assert_eq!(stats.input_bytes, 8);
+/// assert_eq!(stats.output_bytes, 7);
+/// # }
+/// ```
+pub fn clang_format_with_stats(
+    input: impl AsRef<str>,
+    style: impl Into<ClangFormatStyle>,
+) -> Result<(String, FormatStats), ClangFormatError> {
+    ClangFormat::new().style(style).format_with_stats(input)
+}
+
+/// Execute clang-format with the given input and style, and return the edits it would make
+///
+/// See `ClangFormat::replacements` for details.
+pub fn clang_format_replacements(
+    input: &str,
+    style: impl Into<ClangFormatStyle>,
+) -> Result<Vec<Replacement>, ClangFormatError> {
+    ClangFormat::new().style(style).replacements(input)
+}
+
+/// Estimate where the byte offset of a cursor in the input is in the formatted output
+///
+/// This is a pure Rust fallback for versions of clang-format without `--cursor`, which
+/// `ClangFormat::format_with_cursor` uses automatically. It is an approximation, the input and
+/// output are diffed and the cursor stays before the same non-whitespace character within
+/// a changed region. When formatting changes more than whitespace, such as sorting includes,
+/// the estimate may differ from the exact result of `--cursor`.
+///
+/// # Example
+///
+/// ```
+/// # use clang_format::estimate_cursor;
+/// # fn main() {
+/// assert_eq!(estimate_cursor("int    a;\nint b;\n", "int a;\nint b;\n", 7), 4);
+/// assert_eq!(estimate_cursor("int    a;\nint b;\n", "int a;\nint b;\n", 14), 11);
+/// # }
+/// ```
+pub fn estimate_cursor(input: &str, output: &str, cursor: usize) -> usize {
+    cursor::estimate(input, output, cursor)
+}
+
+/// Map a byte offset in the input to the corresponding byte offset in the output
+///
+/// This is a pure diff of the two texts, so it does not require clang-format and works for
+/// any pair of texts before and after an edit, such as for revealing where a symbol moved to.
+/// The characters of the whole texts are diffed, an offset before an unchanged character maps
+/// to the same character and an offset within removed or replaced text maps to the start of
+/// its replacement. Unlike `estimate_cursor`, which aligns the non-whitespace characters of
+/// changed lines as formatting only changes whitespace, nothing is assumed about the edit.
+/// The result is always a character boundary of the output.
+///
+/// # Example
+///
+/// ```
+/// # use clang_format::map_offset;
+/// # fn main() {
+/// let input = "int  a;\nint    b;\n";
+/// let output = "int a;\nint b;\n";
+/// // The offset of b
+/// assert_eq!(map_offset(input, output, 15), 11);
+/// // The offset within a renamed symbol is at the start of the new name
+/// assert_eq!(map_offset("foo(x);\n", "bar(x);\n", 1), 0);
+/// # }
+/// ```
+pub fn map_offset(input: &str, output: &str, offset: usize) -> usize {
+    cursor::map_offset(input, output, offset)
+}
+
+/// Execute clang-format with the given input and style, and track the byte offset of a cursor
+///
+/// See `ClangFormat::format_with_cursor` for details.
+pub fn clang_format_with_cursor(
+    input: &str,
+    style: impl Into<ClangFormatStyle>,
+    cursor: usize,
+) -> Result<(String, usize), ClangFormatError> {
+    ClangFormat::new()
+        .style(style)
+        .format_with_cursor(input, cursor)
+}
+
+/// Execute clang-format on a region of lines, using the given style, and track the byte offset of a cursor
+///
+/// See `ClangFormat::format_region_with_cursor` for details.
+pub fn clang_format_region_with_cursor(
+    input: &str,
+    style: impl Into<ClangFormatStyle>,
+    lines: std::ops::Range<usize>,
+    cursor: usize,
+) -> Result<FormattedRegion, ClangFormatError> {
+    ClangFormat::new()
+        .style(style)
+        .format_region_with_cursor(input, lines, cursor)
+}
+
+/// Execute clang-format with the given input and style, and return both the edits and the formatted output
+///
+/// See `ClangFormat::format_with_replacements` for details.
+pub fn clang_format_with_replacements(
+    input: &str,
+    style: impl Into<ClangFormatStyle>,
+) -> Result<FormatOutputWithReplacements, ClangFormatError> {
+    ClangFormat::new()
+        .style(style)
+        .format_with_replacements(input)
+}
+
+/// Execute clang-format with `--dry-run` and the given style, and return where the input is not formatted
+///
+/// See `ClangFormat::dry_run` for details.
+pub fn clang_format_dry_run(
+    input: &str,
+    style: impl Into<ClangFormatStyle>,
+) -> Result<Vec<Diagnostic>, ClangFormatError> {
+    ClangFormat::new().style(style).dry_run(input)
+}
+
+/// Execute clang-format in place with the given style on each of the files listed in the given file
+///
+/// Returns the listed paths which do not exist and were skipped.
+/// See `ClangFormat::format_filelist` for details.
+pub fn clang_format_filelist(
+    list_path: impl AsRef<Path>,
+    style: impl Into<ClangFormatStyle>,
+) -> Result<Vec<PathBuf>, ClangFormatError> {
+    ClangFormat::new().style(style).format_filelist(list_path)
+}
+
+/// Check whether the file at the given path needs formatting with the given style
+///
+/// See `ClangFormat::file_needs_formatting` for details.
+pub fn file_needs_formatting(
+    path: impl AsRef<Path>,
+    style: impl Into<ClangFormatStyle>,
+) -> Result<bool, ClangFormatError> {
+    ClangFormat::new().style(style).file_needs_formatting(path)
+}
+
+/// Execute clang-format with the given input and style, and find the output lines longer than the `ColumnLimit`
+///
+/// See `ClangFormat::lines_over_column_limit` for details.
+///
+/// # Example
+///
+/// ```
+/// # use clang_format::{lines_over_column_limit, ClangFormatStyle};
+/// # fn main() {
+/// let input = "const char *text = \"a string literal which cannot be wrapped by clang-format\";\n";
+/// let lines = lines_over_column_limit(input, &ClangFormatStyle::Llvm);
+/// assert!(lines.is_ok());
+/// assert_eq!(lines.unwrap(), [1]);
+/// # }
+/// ```
+pub fn lines_over_column_limit(
+    input: &str,
+    style: impl Into<ClangFormatStyle>,
+) -> Result<Vec<usize>, ClangFormatError> {
+    ClangFormat::new()
+        .style(style)
+        .lines_over_column_limit(input)
+}
+
+/// Execute clang-format with the given input and style, and compute the fraction of lines within the `ColumnLimit`
+///
+/// See `ClangFormat::column_compliance` for details.
+pub fn column_compliance(
+    input: &str,
+    style: impl Into<ClangFormatStyle>,
+) -> Result<f64, ClangFormatError> {
+    ClangFormat::new().style(style).column_compliance(input)
+}
+
+/// Check whether the built-in default of clang-format has the same configuration as the style
+///
+/// See `ClangFormat::default_matches` for details.
+///
+/// # Example
+///
+/// ```
+/// # use clang_format::{default_matches, ClangFormatStyle};
+/// # fn main() {
+/// let matches = default_matches(&ClangFormatStyle::Llvm);
+/// assert!(matches.is_ok());
+/// assert!(matches.unwrap());
+/// # }
+/// ```
+pub fn default_matches(style: impl Into<ClangFormatStyle>) -> Result<bool, ClangFormatError> {
+    ClangFormat::new().default_matches(style)
+}
+
+/// Check that two styles format the given input identically
+///
+/// This supports migrating to a new style by checking that it is equivalent to the
+/// old style on real code. Use `ClangFormat::style_differences` to find where they differ.
+///
+/// # Example
+///
+/// ```
+/// # use clang_format::{styles_agree, ClangFormatStyle};
+/// # fn main() {
+/// let agree = styles_agree(
+///     "struct Test {};\n",
+///     &ClangFormatStyle::Mozilla,
+///     &ClangFormatStyle::Custom("{ BasedOnStyle: Mozilla, IndentWidth: 2 }".to_string()),
+/// );
+/// assert!(agree.is_ok());
+/// assert!(agree.unwrap());
+/// # }
+/// ```
+pub fn styles_agree(
+    input: &str,
+    a: impl Into<ClangFormatStyle>,
+    b: impl Into<ClangFormatStyle>,
+) -> Result<bool, ClangFormatError> {
+    ClangFormat::new().styles_agree(input, a, b)
+}
+
+/// Check that formatting the formatted output again does not change it
+///
+/// See `ClangFormat::is_idempotent` for details.
+///
+/// # Example
+///
+/// ```
+/// # use clang_format::{is_idempotent, ClangFormatStyle};
+/// # fn main() {
+/// let idempotent = is_idempotent("struct Test {\n\n};\n", &ClangFormatStyle::Mozilla);
+/// assert!(idempotent.is_ok());
+/// assert!(idempotent.unwrap());
+/// # }
+/// ```
+pub fn is_idempotent(
+    input: &str,
+    style: impl Into<ClangFormatStyle>,
+) -> Result<bool, ClangFormatError> {
+    ClangFormat::new().style(style).is_idempotent(input)
+}
+
+/// Execute clang-format with the given input and style, and check whether only whitespace changes
+///
+/// See `ClangFormat::changes_are_whitespace_only` for details.
+///
+/// # Example
+///
+/// ```
+/// # use clang_format::{changes_are_whitespace_only, ClangFormatStyle};
+/// # fn main() {
+/// let whitespace_only = changes_are_whitespace_only("int  a;\n", &ClangFormatStyle::Default);
+/// assert!(whitespace_only.is_ok());
+/// assert!(whitespace_only.unwrap());
+/// # }
+/// ```
+pub fn changes_are_whitespace_only(
+    input: &str,
+    style: impl Into<ClangFormatStyle>,
+) -> Result<bool, ClangFormatError> {
+    ClangFormat::new()
+        .style(style)
+        .changes_are_whitespace_only(input)
+}
+
+/// Query the version of the clang-format binary
+///
+/// # Example
+///
+/// ```
+/// # use clang_format::clang_format_version;
+/// # fn main() {
+/// let version = clang_format_version();
+/// assert!(version.is_ok());
+/// assert!(version.unwrap().major >= 9);
+/// # }
+/// ```
+pub fn clang_format_version() -> Result<ClangFormatVersion, ClangFormatError> {
+    ClangFormat::new().version()
+}
+
+/// Query the languages which the clang-format binary supports
+///
+/// See `ClangFormat::supported_languages` for details.
+pub fn supported_languages() -> Result<Vec<Language>, ClangFormatError> {
+    ClangFormat::new().supported_languages()
+}
+
+/// Compute a stable key for caching the output of formatting the input with the style
+///
+/// See `ClangFormat::cache_key` for details.
+pub fn cache_key(input: &str, style: impl Into<ClangFormatStyle>) -> Result<u64, ClangFormatError> {
+    ClangFormat::new().style(style).cache_key(input)
+}
+
+/// Query the configuration clang-format resolves for the style as a typed `StyleConfig`
+///
+/// See `ClangFormat::dump_config_typed` for details.
+pub fn dump_config_typed(
+    style: impl Into<ClangFormatStyle>,
+) -> Result<StyleConfig, ClangFormatError> {
+    ClangFormat::new().style(style).dump_config_typed()
+}
+
+/// Compare the configuration of the style between two clang-format binaries
+///
+/// Returns `(key, value_a, value_b)` for each top level key whose value differs,
+/// see `ClangFormat::config_diff` for details.
+pub fn config_diff(
+    style: impl Into<ClangFormatStyle>,
+    binary_a: impl AsRef<Path>,
+    binary_b: impl AsRef<Path>,
+) -> Result<Vec<(String, String, String)>, ClangFormatError> {
+    ClangFormat::new()
+        .style(style)
+        .binary(binary_a)
+        .config_diff(binary_b)
+}
+
+/// Check that the clang-format binary is at least the given version, such as `(14, 0, 0)`
+///
+/// See `ClangFormat::require_version` for details.
+pub fn require_version(min: impl Into<ClangFormatVersion>) -> Result<(), ClangFormatError> {
+    ClangFormat::new().require_version(min)
+}
+
+/// Query the names of the predefined styles the clang-format binary supports
+///
+/// See `ClangFormat::available_builtin_styles` for details.
+pub fn available_builtin_styles() -> Result<Vec<String>, ClangFormatError> {
+    ClangFormat::new().available_builtin_styles()
+}
+
+/// Execute clang-format with exactly the given arguments and return the raw output
+///
+/// See `ClangFormat::run_raw` for details.
+pub fn run_clang_format(
+    args: &[OsString],
+    input: Option<&[u8]>,
+) -> Result<Output, ClangFormatError> {
+    ClangFormat::new().run_raw(args, input)
+}
+
+/// Check whether the clang-format binary supports the given command line flag
+///
+/// See `ClangFormat::supports_flag` for details.
+pub fn supports_flag(flag: &str) -> bool {
+    ClangFormat::new().supports_flag(flag)
 }
 
 /// Execute clang-format with the given input and collect the output
 ///
-/// Note that this uses `ClangFormatStyle::Default` as the style.
+/// Note that this uses the style registered with `ClangFormat::set_default_style`,
+/// otherwise `ClangFormatStyle::Default` as the style.
 ///
 /// # Example
 ///
@@ -168,8 +1301,33 @@ pub fn clang_format_with_style(
 /// assert_eq!(output.unwrap(), "\nstruct Test {};\n");
 /// # }
 /// ```
-pub fn clang_format(input: &str) -> Result<String, impl std::error::Error> {
-    clang_format_with_style(input, &ClangFormatStyle::Default)
+pub fn clang_format(input: impl AsRef<str>) -> Result<String, ClangFormatError> {
+    ClangFormat::new().format(input)
+}
+
+/// Execute clang-format with the given input and return the output, panicking on any error
+///
+/// This is intended for tests and scripts where a failure to format should abort, the panic
+/// message contains the error. The style is the same as `clang_format`.
+///
+/// # Panics
+///
+/// If clang-format cannot be executed or fails to format the input.
+///
+/// # Example
+///
+/// ```
+/// # use clang_format::clang_format_or_panic;
+/// # fn main() {
+/// assert_eq!(clang_format_or_panic("int  a;\n"), "int a;\n");
+/// # }
+/// ```
+#[track_caller]
+pub fn clang_format_or_panic(input: impl AsRef<str>) -> String {
+    match clang_format(input) {
+        Ok(output) => output,
+        Err(err) => panic!("clang-format failed: {err}"),
+    }
 }
 
 #[cfg(test)]
@@ -188,6 +1346,24 @@ mod tests {
         assert_eq!(output.unwrap(), "\nstruct Test {};\n");
     }
 
+    #[test]
+    fn format_owned_input() {
+        let input = "struct Test {\n\n};\n".to_string();
+        let output = clang_format(&input);
+        assert!(output.is_ok());
+        assert_eq!(output.unwrap(), "struct Test {};\n");
+
+        let output = clang_format_with_style(
+            std::borrow::Cow::Borrowed(input.as_str()),
+            &ClangFormatStyle::Mozilla,
+        );
+        assert!(output.is_ok());
+        assert_eq!(output.unwrap(), "struct Test\n{};\n");
+
+        let output = clang_format(input);
+        assert!(output.is_ok());
+    }
+
     #[test]
     fn format_mozilla() {
         let input = r#"
@@ -198,9 +1374,15 @@ mod tests {
         let output = clang_format_with_style(input, &ClangFormatStyle::Mozilla);
         assert!(output.is_ok());
         assert_eq!(output.unwrap(), "\nstruct Test\n{};\n");
+
+        let output = clang_format_with_style(input, "mozilla");
+        assert!(output.is_ok());
+        assert_eq!(output.unwrap(), "\nstruct Test\n{};\n");
     }
 
     #[test]
+    // Passing a reference is kept to check that borrowed styles are still accepted
+    #[allow(clippy::needless_borrows_for_generic_args)]
     fn format_custom() {
         let input = r#"
             struct Test {
@@ -237,4 +1419,36 @@ mod tests {
             assert_eq!(output.unwrap(), "\nstruct Test\n{\n    bool field;\n};\n");
         }
     }
+
+    #[test]
+    fn format_custom_by_value() {
+        let input = r#"
+            struct Test {
+                bool field;
+            };
+        "#;
+        let output = clang_format_with_style(
+            input,
+            ClangFormatStyle::Custom("{ BasedOnStyle: Mozilla, IndentWidth: 2 }".to_string()),
+        );
+        assert!(output.is_ok());
+        assert_eq!(output.unwrap(), "\nstruct Test\n{\n  bool field;\n};\n");
+    }
+
+    #[test]
+    fn format_changed_lines_only() {
+        let old = "int  a;\nint  b;\n";
+        let new = "int  a;\nint  b;\nint  c;\n";
+        let output = format_changed_lines(old, new, &ClangFormatStyle::Default);
+        assert!(output.is_ok());
+        assert_eq!(output.unwrap(), "int  a;\nint  b;\nint c;\n");
+    }
+
+    #[test]
+    fn format_changed_lines_unchanged() {
+        let input = "int  a;\n";
+        let output = format_changed_lines(input, input, &ClangFormatStyle::Default);
+        assert!(output.is_ok());
+        assert_eq!(output.unwrap(), input);
+    }
 }
@@ -0,0 +1,114 @@
+// SPDX-FileCopyrightText: 2023 Klarälvdalens Datakonsult AB, a KDAB Group company <info@kdab.com>
+// SPDX-FileContributor: Andrew Hayzen <andrew.hayzen@kdab.com>
+//
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use std::sync::{Condvar, Mutex};
+
+use crate::{ClangFormat, ClangFormatError, ClangFormatStyle};
+
+/// Limits the number of clang-format processes running at once across many formats
+///
+/// clang-format reads a single input from stdin and exits once it has written the output,
+/// so processes cannot be spawned ahead of time and reused for another input. Instead the
+/// pool limits concurrency, a call to `FormatterPool::format` blocks while `size` processes
+/// are already running. This keeps large runs from many threads from overloading the machine.
+///
+/// # Example
+///
+/// ```
+/// # use clang_format::{ClangFormatStyle, FormatterPool};
+/// # use std::thread;
+/// # fn main() {
+/// let pool = FormatterPool::new(ClangFormatStyle::Mozilla, 2);
+/// thread::scope(|scope| {
+///     for _ in 0..4 {
+///         scope.spawn(|| {
+///             let output = pool.format("struct Test {\n\n};\n");
+///             assert_eq!(output.unwrap(), "struct Test\n{};\n");
+///         });
+///     }
+/// });
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct FormatterPool {
+    format: ClangFormat,
+    size: usize,
+    running: Mutex<usize>,
+    available: Condvar,
+}
+
+impl FormatterPool {
+    /// Create a pool which formats with the given style, running at most `size` processes at once
+    ///
+    /// A size of zero is treated as one.
+    pub fn new(style: impl Into<ClangFormatStyle>, size: usize) -> Self {
+        Self::with_format(ClangFormat::new().style(style), size)
+    }
+
+    /// Create a pool which formats with the given builder, running at most `size` processes at once
+    ///
+    /// A size of zero is treated as one.
+    pub fn with_format(format: ClangFormat, size: usize) -> Self {
+        Self {
+            format,
+            size: size.max(1),
+            running: Mutex::new(0),
+            available: Condvar::new(),
+        }
+    }
+
+    /// Execute clang-format with the given input once fewer than `size` processes are running
+    pub fn format(&self, input: impl AsRef<str>) -> Result<String, ClangFormatError> {
+        let _slot = self.acquire();
+        self.format.format(input)
+    }
+
+    /// Wait for a free slot, which is released when the returned guard is dropped
+    fn acquire(&self) -> Slot<'_> {
+        let mut running = self.running.lock().expect("pool state poisoned");
+        while *running >= self.size {
+            running = self.available.wait(running).expect("pool state poisoned");
+        }
+        *running += 1;
+        Slot { pool: self }
+    }
+}
+
+/// A running process of a `FormatterPool`
+struct Slot<'a> {
+    pool: &'a FormatterPool,
+}
+
+impl Drop for Slot<'_> {
+    fn drop(&mut self) {
+        *self.pool.running.lock().expect("pool state poisoned") -= 1;
+        self.pool.available.notify_one();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::ConcurrencyRunner;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn pool_limits_concurrency() {
+        let runner = Arc::new(ConcurrencyRunner::default());
+        let pool = FormatterPool::with_format(ClangFormat::new().runner(runner.clone()), 2);
+        thread::scope(|scope| {
+            for index in 0..6 {
+                let pool = &pool;
+                scope.spawn(move || {
+                    let input = format!("int a{index};\n");
+                    assert_eq!(pool.format(&input).unwrap(), input);
+                });
+            }
+        });
+        assert!(runner.max_active() <= 2);
+        assert_eq!(*pool.running.lock().unwrap(), 0);
+    }
+}
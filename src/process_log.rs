@@ -0,0 +1,95 @@
+// SPDX-FileCopyrightText: 2023 Klarälvdalens Datakonsult AB, a KDAB Group company <info@kdab.com>
+// SPDX-FileContributor: Andrew Hayzen <andrew.hayzen@kdab.com>
+//
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! A timestamped log of the output of a clang-format process, for debugging
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// The stream of the clang-format process which a `LogEntry` was read from
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LogStream {
+    /// The formatted output
+    Stdout,
+    /// Diagnostics and errors
+    Stderr,
+}
+
+/// A chunk of output which was read from the clang-format process
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LogEntry {
+    /// The time since the log was started, from a monotonic clock
+    pub elapsed: Duration,
+    /// The stream the chunk was read from
+    pub stream: LogStream,
+    /// The bytes of the chunk
+    pub bytes: Vec<u8>,
+}
+
+/// The chunks read from stdout and stderr of clang-format, interleaved in the order they arrived
+///
+/// The `Display` implementation writes one line per chunk with its timestamp and stream,
+/// which can be attached to a CI report.
+#[derive(Clone, Debug)]
+pub struct ProcessLog {
+    start: Instant,
+    /// Each chunk in the order it was read
+    pub entries: Vec<LogEntry>,
+}
+
+impl ProcessLog {
+    /// Start an empty log, timestamps are relative to now
+    pub(crate) fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            entries: vec![],
+        }
+    }
+
+    /// Record a chunk which was read from the stream
+    pub(crate) fn record(&mut self, stream: LogStream, bytes: &[u8]) {
+        self.entries.push(LogEntry {
+            elapsed: self.start.elapsed(),
+            stream,
+            bytes: bytes.to_vec(),
+        });
+    }
+}
+
+impl fmt::Display for ProcessLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for entry in &self.entries {
+            let stream = match entry.stream {
+                LogStream::Stdout => "stdout",
+                LogStream::Stderr => "stderr",
+            };
+            writeln!(
+                f,
+                "[{:>10.6}s] {stream}: {:?}",
+                entry.elapsed.as_secs_f64(),
+                String::from_utf8_lossy(&entry.bytes)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_log() {
+        let mut log = ProcessLog::new();
+        log.record(LogStream::Stdout, b"int a;\n");
+        log.record(LogStream::Stderr, b"warning: x\n");
+        log.entries[0].elapsed = Duration::from_millis(1);
+        log.entries[1].elapsed = Duration::from_millis(2);
+        assert_eq!(
+            log.to_string(),
+            "[  0.001000s] stdout: \"int a;\\n\"\n[  0.002000s] stderr: \"warning: x\\n\"\n"
+        );
+    }
+}
@@ -0,0 +1,238 @@
+// SPDX-FileCopyrightText: 2023 Klarälvdalens Datakonsult AB, a KDAB Group company <info@kdab.com>
+// SPDX-FileContributor: Andrew Hayzen <andrew.hayzen@kdab.com>
+//
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::ClangFormatError;
+
+/// Describes a single edit clang-format would make to the input
+///
+/// Offsets and lengths are in bytes of the UTF-8 input.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Replacement {
+    /// The byte offset in the input where the replaced text starts
+    pub offset: usize,
+    /// The number of bytes of the input which are replaced
+    pub length: usize,
+    /// The text which replaces the bytes
+    pub text: String,
+}
+
+/// Apply the replacements to the input, for example those returned by `ClangFormat::replacements`
+///
+/// The replacements must be sorted by offset, must not overlap, and must start and end on
+/// character boundaries within the input, as those from clang-format do. Otherwise
+/// `ClangFormatError::InvalidReplacements` is returned.
+///
+/// # Example
+///
+/// ```
+/// # use clang_format::{apply_replacements, Replacement};
+/// # fn main() {
+/// let replacements = [Replacement { offset: 3, length: 2, text: " ".to_string() }];
+/// assert_eq!(apply_replacements("int  a;\n", &replacements).unwrap(), "int a;\n");
+/// # }
+/// ```
+pub fn apply_replacements(
+    input: &str,
+    replacements: &[Replacement],
+) -> Result<String, ClangFormatError> {
+    let mut output = String::with_capacity(input.len());
+    let mut position = 0;
+    for replacement in replacements {
+        let end = replacement.offset + replacement.length;
+        if replacement.offset < position {
+            return Err(ClangFormatError::InvalidReplacements(format!(
+                "replacement at offset {} overlaps or is before the previous replacement",
+                replacement.offset
+            )));
+        }
+        if end > input.len()
+            || !input.is_char_boundary(replacement.offset)
+            || !input.is_char_boundary(end)
+        {
+            return Err(ClangFormatError::InvalidReplacements(format!(
+                "replacement of {}..{end} is not within the input",
+                replacement.offset
+            )));
+        }
+        output.push_str(&input[position..replacement.offset]);
+        output.push_str(&replacement.text);
+        position = end;
+    }
+    output.push_str(&input[position..]);
+    Ok(output)
+}
+
+/// Parse the output of `clang-format --output-replacements-xml`
+///
+/// This is in the form
+///
+/// ```xml
+/// <?xml version='1.0'?>
+/// <replacements xml:space='preserve' incomplete_format='false'>
+/// <replacement offset='3' length='2'>&#10;</replacement>
+/// </replacements>
+/// ```
+///
+/// This is not a full XML parser, only the elements and entities clang-format writes are supported.
+pub(crate) fn parse_xml(xml: &str) -> Result<Vec<Replacement>, String> {
+    const OPEN: &str = "<replacement ";
+    const CLOSE: &str = "</replacement>";
+
+    if !xml.contains("<replacements") {
+        return Err("missing <replacements> element".to_string());
+    }
+
+    let mut replacements = vec![];
+    let mut rest = xml;
+    while let Some(start) = rest.find(OPEN) {
+        rest = &rest[start + OPEN.len()..];
+        let end = rest
+            .find('>')
+            .ok_or_else(|| "unterminated <replacement> element".to_string())?;
+        let attributes = &rest[..end];
+        let offset = attribute(attributes, "offset")?;
+        let length = attribute(attributes, "length")?;
+        rest = &rest[end + 1..];
+
+        let text = if attributes.ends_with('/') {
+            String::new()
+        } else {
+            let end = rest
+                .find(CLOSE)
+                .ok_or_else(|| "missing </replacement>".to_string())?;
+            let text = unescape(&rest[..end])?;
+            rest = &rest[end + CLOSE.len()..];
+            text
+        };
+
+        replacements.push(Replacement {
+            offset,
+            length,
+            text,
+        });
+    }
+    Ok(replacements)
+}
+
+/// Find the value of a numeric attribute such as `offset='3'`
+fn attribute(attributes: &str, name: &str) -> Result<usize, String> {
+    attributes
+        .split_whitespace()
+        .find_map(|attribute| attribute.strip_prefix(name)?.strip_prefix('='))
+        .map(|value| {
+            value
+                .trim_end_matches('/')
+                .trim_matches(|c| c == '\'' || c == '"')
+        })
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| format!("missing or invalid {name} attribute"))
+}
+
+/// Replace the XML entities in the text, such as `&#10;` and `&lt;`
+fn unescape(text: &str) -> Result<String, String> {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        unescaped.push_str(&rest[..start]);
+        rest = &rest[start + 1..];
+        let end = rest
+            .find(';')
+            .ok_or_else(|| "unterminated entity".to_string())?;
+        let entity = &rest[..end];
+        let c = match entity {
+            "lt" => '<',
+            "gt" => '>',
+            "amp" => '&',
+            "apos" => '\'',
+            "quot" => '"',
+            _ => entity
+                .strip_prefix("#x")
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(str::parse))
+                .and_then(Result::ok)
+                .and_then(char::from_u32)
+                .ok_or_else(|| format!("unknown entity &{entity};"))?,
+        };
+        unescaped.push(c);
+        rest = &rest[end + 1..];
+    }
+    unescaped.push_str(rest);
+    Ok(unescaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_replacements_xml() {
+        let xml = "<?xml version='1.0'?>\n<replacements xml:space='preserve' incomplete_format='false'>\n<replacement offset='3' length='2'> </replacement>\n<replacement offset='13' length='0'>&#10;&lt;&amp;&gt;</replacement>\n<replacement offset='20' length='1'></replacement>\n</replacements>\n";
+        assert_eq!(
+            parse_xml(xml).unwrap(),
+            vec![
+                Replacement {
+                    offset: 3,
+                    length: 2,
+                    text: " ".to_string(),
+                },
+                Replacement {
+                    offset: 13,
+                    length: 0,
+                    text: "\n<&>".to_string(),
+                },
+                Replacement {
+                    offset: 20,
+                    length: 1,
+                    text: String::new(),
+                },
+            ]
+        );
+
+        let xml = "<?xml version='1.0'?>\n<replacements xml:space='preserve' incomplete_format='false'>\n</replacements>\n";
+        assert_eq!(parse_xml(xml).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn apply_replacements_in_order() {
+        let replacement = |offset, length, text: &str| Replacement {
+            offset,
+            length,
+            text: text.to_string(),
+        };
+        let input = "int  a;int b;\n";
+        assert_eq!(
+            apply_replacements(input, &[replacement(3, 2, " "), replacement(7, 0, "\n")]).unwrap(),
+            "int a;\nint b;\n"
+        );
+        assert_eq!(apply_replacements(input, &[]).unwrap(), input);
+        assert!(matches!(
+            apply_replacements(input, &[replacement(3, 2, " "), replacement(4, 0, "")]),
+            Err(ClangFormatError::InvalidReplacements(_))
+        ));
+        assert!(matches!(
+            apply_replacements(input, &[replacement(14, 1, "")]),
+            Err(ClangFormatError::InvalidReplacements(_))
+        ));
+        assert!(matches!(
+            apply_replacements("é", &[replacement(1, 0, "")]),
+            Err(ClangFormatError::InvalidReplacements(_))
+        ));
+    }
+
+    #[test]
+    fn parse_replacements_invalid() {
+        assert!(parse_xml("int a;\n").is_err());
+        assert_eq!(
+            parse_xml("<replacements>\n<replacement offset='x' length='1'></replacement>")
+                .unwrap_err(),
+            "missing or invalid offset attribute"
+        );
+        assert_eq!(
+            parse_xml("<replacements>\n<replacement offset='1' length='1'>&bad;</replacement>")
+                .unwrap_err(),
+            "unknown entity &bad;"
+        );
+    }
+}
@@ -0,0 +1,84 @@
+// SPDX-FileCopyrightText: 2023 Klarälvdalens Datakonsult AB, a KDAB Group company <info@kdab.com>
+// SPDX-FileContributor: Andrew Hayzen <andrew.hayzen@kdab.com>
+//
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Measuring the resources used by clang-format processes, for benchmarking
+
+use std::time::Duration;
+
+/// The resources used by the clang-format process, see `FormatStats::resource_usage`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ResourceUsage {
+    /// The maximum resident set size of the largest child process which has exited so far
+    ///
+    /// This is not a delta, so it may be of an earlier child which used more memory.
+    pub max_rss_bytes: u64,
+    /// The CPU time spent in user mode
+    pub user_time: Duration,
+    /// The CPU time spent in kernel mode
+    pub system_time: Duration,
+}
+
+impl ResourceUsage {
+    /// The usage of all child processes which have been waited for
+    #[cfg(unix)]
+    pub(crate) fn children() -> Option<Self> {
+        use crate::sys::{getrusage, RUsage, TimeVal, RUSAGE_CHILDREN};
+
+        let mut usage = std::mem::MaybeUninit::<RUsage>::zeroed();
+        // SAFETY: getrusage only writes to the given struct, which has the layout of rusage
+        if unsafe { getrusage(RUSAGE_CHILDREN, usage.as_mut_ptr()) } != 0 {
+            return None;
+        }
+        // SAFETY: the struct was zeroed and then filled in by getrusage
+        let usage = unsafe { usage.assume_init() };
+
+        let duration = |time: &TimeVal| {
+            Duration::from_secs(u64::try_from(time.tv_sec).unwrap_or_default())
+                + Duration::from_micros(u64::try_from(time.tv_usec).unwrap_or_default())
+        };
+        // macOS reports bytes, other platforms report kilobytes
+        let max_rss = u64::try_from(usage.ru_maxrss).unwrap_or_default();
+        let max_rss_bytes = if cfg!(target_os = "macos") {
+            max_rss
+        } else {
+            max_rss * 1024
+        };
+        Some(Self {
+            max_rss_bytes,
+            user_time: duration(&usage.ru_utime),
+            system_time: duration(&usage.ru_stime),
+        })
+    }
+
+    /// Resource usage is only measured on Unix
+    #[cfg(not(unix))]
+    pub(crate) fn children() -> Option<Self> {
+        None
+    }
+
+    /// The CPU time used since the earlier usage, with the current maximum resident set size
+    pub(crate) fn since(&self, earlier: &Self) -> Self {
+        Self {
+            max_rss_bytes: self.max_rss_bytes,
+            user_time: self.user_time.saturating_sub(earlier.user_time),
+            system_time: self.system_time.saturating_sub(earlier.system_time),
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    #[test]
+    fn children_usage() {
+        let before = ResourceUsage::children().unwrap();
+        assert!(Command::new("true").status().unwrap().success());
+        let usage = ResourceUsage::children().unwrap().since(&before);
+        assert!(usage.max_rss_bytes > 0);
+        assert!(usage.user_time <= Duration::from_secs(10));
+    }
+}
@@ -0,0 +1,269 @@
+// SPDX-FileCopyrightText: 2023 Klarälvdalens Datakonsult AB, a KDAB Group company <info@kdab.com>
+// SPDX-FileContributor: Andrew Hayzen <andrew.hayzen@kdab.com>
+//
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use std::fmt::Debug;
+use std::io::{self, Read, Write};
+use std::process::{Child, Command, Output, Stdio};
+#[cfg(feature = "debug_log")]
+use std::sync::Mutex;
+use std::thread;
+
+use crate::cancel::CancelHandle;
+use crate::{ClangFormatError, CommandLine};
+#[cfg(feature = "debug_log")]
+use crate::{LogStream, ProcessLog};
+
+/// Executes a clang-format command and collects its output
+///
+/// By default `ClangFormat` uses `ProcessRunner`, which spawns the command. Another runner
+/// can be given to `ClangFormat::runner`, which allows for testing code that formats with
+/// clang-format deterministically and without clang-format installed.
+///
+/// # Example
+///
+/// ```
+/// # use clang_format::{ClangFormat, ClangFormatError, FormatRunner};
+/// # use std::process::{Command, ExitStatus, Output};
+/// # fn main() {
+/// #[derive(Debug)]
+/// struct Uppercase;
+///
+/// impl FormatRunner for Uppercase {
+///     fn run(&self, _command: &mut Command, input: &[u8]) -> Result<Output, ClangFormatError> {
+///         Ok(Output {
+///             status: ExitStatus::default(),
+///             stdout: input.to_ascii_uppercase(),
+///             stderr: vec![],
+///         })
+///     }
+/// }
+///
+/// let output = ClangFormat::new().runner(Uppercase).format("int a;\n");
+/// assert!(output.is_ok());
+/// assert_eq!(output.unwrap(), "INT A;\n");
+/// # }
+/// ```
+pub trait FormatRunner: Debug + Send + Sync {
+    /// Execute the command with the given input on stdin and collect stdout and stderr
+    ///
+    /// The command has all of the arguments, environment, and working directory configured
+    /// by the `ClangFormat` builder. A non-zero exit status should be returned as part of
+    /// the `Output`, it is then reported as `ClangFormatError::NonZeroExitStatus`.
+    fn run(&self, command: &mut Command, input: &[u8]) -> Result<Output, ClangFormatError>;
+}
+
+/// The default number of bytes written to stdin of clang-format per write
+pub(crate) const DEFAULT_WRITE_CHUNK_SIZE: usize = 64 * 1024;
+
+/// How `run_process` communicates with the process
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct ProcessOptions<'a> {
+    /// The input is written in chunks of at most this many bytes
+    pub(crate) write_chunk_size: usize,
+    /// Allows for killing the process from another thread
+    pub(crate) cancel: Option<&'a CancelHandle>,
+    /// Records each chunk of stdout and stderr
+    #[cfg(feature = "debug_log")]
+    pub(crate) log: Option<&'a Mutex<ProcessLog>>,
+}
+
+impl Default for ProcessOptions<'_> {
+    fn default() -> Self {
+        Self {
+            write_chunk_size: DEFAULT_WRITE_CHUNK_SIZE,
+            cancel: None,
+            #[cfg(feature = "debug_log")]
+            log: None,
+        }
+    }
+}
+
+/// The default `FormatRunner`, which spawns the clang-format process
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ProcessRunner;
+
+impl FormatRunner for ProcessRunner {
+    fn run(&self, command: &mut Command, input: &[u8]) -> Result<Output, ClangFormatError> {
+        run_process_collect(command, input, ProcessOptions::default())
+    }
+}
+
+/// Spawn the command writing the input to stdin, collecting stdout
+pub(crate) fn run_process_collect(
+    command: &mut Command,
+    input: &[u8],
+    options: ProcessOptions<'_>,
+) -> Result<Output, ClangFormatError> {
+    let mut stdout = vec![];
+    let output = run_process(command, input, options, |chunk| {
+        stdout.extend_from_slice(chunk);
+        Ok(())
+    })?;
+    Ok(Output { stdout, ..output })
+}
+
+/// Spawn the command, describing the command line if it fails
+pub(crate) fn spawn(command: &mut Command) -> Result<Child, ClangFormatError> {
+    command
+        .spawn()
+        .map_err(|source| ClangFormatError::SpawnFailure {
+            command: CommandLine::new(command),
+            source,
+        })
+}
+
+/// Spawn the command writing the input to stdin, passing chunks of stdout to the callback
+///
+/// The input is written in chunks of at most `write_chunk_size` bytes. While the output is
+/// read the child can be killed with the cancel handle, `ClangFormatError::Cancelled` is then
+/// returned. The returned output contains the exit status and any piped stderr, stdout is
+/// always empty.
+pub(crate) fn run_process(
+    command: &mut Command,
+    input: &[u8],
+    options: ProcessOptions<'_>,
+    mut on_chunk: impl FnMut(&[u8]) -> io::Result<()>,
+) -> Result<Output, ClangFormatError> {
+    command.stdin(Stdio::piped()).stdout(Stdio::piped());
+    let mut child = spawn(command)?;
+    let mut stdin = child.stdin.take().expect("no stdin handle");
+    let mut stdout = child.stdout.take().expect("no stdout handle");
+    let stderr = child.stderr.take();
+
+    let local_handle;
+    let handle = match options.cancel {
+        Some(handle) => handle,
+        None => {
+            local_handle = CancelHandle::default();
+            &local_handle
+        }
+    };
+    handle.register(child);
+
+    thread::scope(|scope| {
+        // Write the input and read stderr on other threads, otherwise clang-format could
+        // block on a full stdout or stderr pipe while we are blocked writing to stdin
+        //
+        // Note that stdin is moved into the thread to ensure that it is closed
+        let writer =
+            scope.spawn(move || write_chunked(&mut stdin, input, options.write_chunk_size));
+        let stderr_reader = stderr.map(|mut stderr| {
+            scope.spawn(move || {
+                let mut buffer = vec![];
+                #[cfg(feature = "debug_log")]
+                if let Some(log) = options.log {
+                    let mut chunk = [0; 8 * 1024];
+                    loop {
+                        match stderr.read(&mut chunk) {
+                            Ok(0) => return Ok(buffer),
+                            Ok(len) => {
+                                log.lock()
+                                    .expect("process log poisoned")
+                                    .record(LogStream::Stderr, &chunk[..len]);
+                                buffer.extend_from_slice(&chunk[..len]);
+                            }
+                            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                            Err(err) => return Err(err),
+                        }
+                    }
+                }
+                stderr.read_to_end(&mut buffer).map(|_| buffer)
+            })
+        });
+
+        // Pass the output to the callback as it arrives
+        let mut buffer = [0; 8 * 1024];
+        let read_result = loop {
+            match stdout.read(&mut buffer) {
+                Ok(0) => break Ok(()),
+                Ok(len) => {
+                    #[cfg(feature = "debug_log")]
+                    if let Some(log) = options.log {
+                        log.lock()
+                            .expect("process log poisoned")
+                            .record(LogStream::Stdout, &buffer[..len]);
+                    }
+                    if let Err(err) = on_chunk(&buffer[..len]) {
+                        break Err(err);
+                    }
+                }
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => break Err(err),
+            }
+        };
+
+        // If we stopped reading early ensure that clang-format does not block forever
+        if read_result.is_err() {
+            handle.kill();
+        }
+        drop(stdout);
+
+        let write_result = writer.join().expect("stdin writer panicked");
+        let stderr = match stderr_reader {
+            Some(reader) => reader.join().expect("stderr reader panicked"),
+            None => Ok(vec![]),
+        };
+        let status = handle.take().expect("child was not registered").wait()?;
+        if handle.is_cancelled() {
+            return Err(ClangFormatError::Cancelled);
+        }
+        let stderr = stderr?;
+        read_result?;
+
+        // A failure of clang-format is more useful than the broken pipe it causes
+        if status.success() {
+            write_result?;
+        }
+        Ok(Output {
+            status,
+            stdout: vec![],
+            stderr,
+        })
+    })
+}
+
+/// Write all of the input in chunks of at most the given size, a size of zero is treated as one
+fn write_chunked(output: &mut impl Write, input: &[u8], chunk_size: usize) -> io::Result<()> {
+    for chunk in input.chunks(chunk_size.max(1)) {
+        output.write_all(chunk)?;
+    }
+    output.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Records the size of each write
+    #[derive(Default)]
+    struct RecordingWriter {
+        writes: Vec<usize>,
+        bytes: Vec<u8>,
+    }
+
+    impl Write for RecordingWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.writes.push(buf.len());
+            self.bytes.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn write_in_chunks() {
+        let mut writer = RecordingWriter::default();
+        write_chunked(&mut writer, b"int a;\nint b;\n", 5).unwrap();
+        assert_eq!(writer.writes, [5, 5, 4]);
+        assert_eq!(writer.bytes, b"int a;\nint b;\n");
+
+        let mut writer = RecordingWriter::default();
+        write_chunked(&mut writer, b"ab", 0).unwrap();
+        assert_eq!(writer.writes, [1, 1]);
+    }
+}
@@ -0,0 +1,233 @@
+// SPDX-FileCopyrightText: 2023 Klarälvdalens Datakonsult AB, a KDAB Group company <info@kdab.com>
+// SPDX-FileContributor: Andrew Hayzen <andrew.hayzen@kdab.com>
+//
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! A cheap heuristic to check that formatting preserved the structure of the code
+//!
+//! This is not a parser, it only looks at brackets outside of comments and literals.
+
+use std::ops::Range;
+
+/// The brackets which are counted
+const BRACKETS: [char; 6] = ['{', '}', '(', ')', '[', ']'];
+
+/// Replace comments, string literals, and character literals with spaces
+///
+/// Newlines are kept and every replaced character becomes the same number of spaces as its
+/// length in bytes, so byte offsets and line numbers in the result match the code.
+pub(crate) fn mask_comments_and_literals(code: &str) -> String {
+    let mut masked = String::with_capacity(code.len());
+    let mask = |masked: &mut String, c: char| {
+        if c == '\n' {
+            masked.push('\n');
+        } else {
+            masked.extend(std::iter::repeat_n(' ', c.len_utf8()));
+        }
+    };
+    let mut chars = code.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '/' if chars.peek() == Some(&'/') => {
+                // Mask to the end of the line comment
+                mask(&mut masked, c);
+                while let Some(c) = chars.next_if(|c| *c != '\n') {
+                    mask(&mut masked, c);
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                mask(&mut masked, c);
+                mask(&mut masked, chars.next().unwrap_or_default());
+                let mut previous = ' ';
+                for c in chars.by_ref() {
+                    mask(&mut masked, c);
+                    if previous == '*' && c == '/' {
+                        break;
+                    }
+                    previous = c;
+                }
+            }
+            '"' | '\'' => {
+                mask(&mut masked, c);
+                while let Some(next) = chars.next() {
+                    match next {
+                        '\\' => {
+                            mask(&mut masked, next);
+                            if let Some(escaped) = chars.next() {
+                                mask(&mut masked, escaped);
+                            }
+                        }
+                        '\n' => {
+                            masked.push('\n');
+                            break;
+                        }
+                        next => {
+                            mask(&mut masked, next);
+                            if next == c {
+                                break;
+                            }
+                        }
+                    }
+                }
+            }
+            c => masked.push(c),
+        }
+    }
+    masked
+}
+
+/// Count each of the brackets outside of comments, string literals, and character literals
+fn bracket_counts(code: &str) -> [usize; 6] {
+    let mut counts = [0; 6];
+    for c in mask_comments_and_literals(code).chars() {
+        if let Some(index) = BRACKETS.iter().position(|bracket| *bracket == c) {
+            counts[index] += 1;
+        }
+    }
+    counts
+}
+
+/// Find the 0-based line ranges of the bodies of the functions defined with the given name
+///
+/// A definition is the name, at a word boundary, followed by parentheses and then an opening
+/// brace before any semicolon. The range covers the lines from the opening brace to the
+/// matching closing brace.
+pub(crate) fn function_body_lines(code: &str, name: &str) -> Vec<Range<usize>> {
+    let masked = mask_comments_and_literals(code);
+    let bytes = masked.as_bytes();
+    let is_identifier = |byte: u8| byte.is_ascii_alphanumeric() || byte == b'_';
+    let line_of = |offset: usize| bytes[..offset].iter().filter(|b| **b == b'\n').count();
+
+    let mut ranges: Vec<Range<usize>> = vec![];
+    for (start, _) in masked.match_indices(name) {
+        let end = start + name.len();
+        if name.is_empty()
+            || (start > 0 && is_identifier(bytes[start - 1]))
+            || bytes.get(end).is_some_and(|byte| is_identifier(*byte))
+        {
+            continue;
+        }
+        if let Some(body) = body_after_name(&masked, end) {
+            let lines = line_of(body.start)..line_of(body.end) + 1;
+            if !ranges.iter().any(|range| range.contains(&lines.start)) {
+                ranges.push(lines);
+            }
+        }
+    }
+    ranges
+}
+
+/// Find the byte range of a function body following the name of the function, if any
+///
+/// The parameters follow the name, and anything at the top level that is not part of
+/// a signature, such as a semicolon or an unmatched bracket, means that it is not a definition.
+fn body_after_name(masked: &str, name_end: usize) -> Option<Range<usize>> {
+    let rest = &masked[name_end..];
+    let rest_start = name_end + rest.len() - rest.trim_start().len();
+    if !masked[rest_start..].starts_with('(') {
+        return None;
+    }
+
+    // Skip the parameters and any qualifiers, trailing return type, or initializer list
+    let mut depth = 0usize;
+    let mut body_start = None;
+    for (index, c) in masked[rest_start..].char_indices() {
+        match c {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth = depth.checked_sub(1)?,
+            '{' if depth == 0 => {
+                body_start = Some(rest_start + index);
+                break;
+            }
+            ';' | '}' | '=' if depth == 0 => return None,
+            _ => {}
+        }
+    }
+    let body_start = body_start?;
+
+    let mut depth = 0usize;
+    for (index, c) in masked[body_start..].char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(body_start..body_start + index);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Check that the output has the same number of each bracket as the input
+///
+/// On failure the error describes the first bracket which differs.
+pub(crate) fn compare(input: &str, output: &str) -> Result<(), String> {
+    let input_counts = bracket_counts(input);
+    let output_counts = bracket_counts(output);
+    for (index, bracket) in BRACKETS.iter().enumerate() {
+        if input_counts[index] != output_counts[index] {
+            return Err(format!(
+                "input has {} '{bracket}' but output has {}",
+                input_counts[index], output_counts[index]
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn count_brackets() {
+        let code = "struct A { int b[2]; void c() { d(\"}\", '{'); } }; // }\n/* ( */ int e;\n";
+        assert_eq!(bracket_counts(code), [2, 2, 2, 2, 1, 1]);
+    }
+
+    #[test]
+    fn mask_code() {
+        let code = "a(\"x}\"); // é\n/* b\n */ c;\n'\\'';";
+        let masked = mask_comments_and_literals(code);
+        assert_eq!(masked.len(), code.len());
+        assert_eq!(masked, "a(    );      \n    \n    c;\n    ;");
+    }
+
+    #[test]
+    fn find_function_bodies() {
+        let code = "int add(int a, int b);\nint add(int a, int b)\n{\n  return a + b;\n}\nint add_one(int a) { return add(a, 1); }\nvoid run() {\n  if (add(1, 2)) {\n  }\n}\nA::A(int a) : b(add(a, 1)), c(a) {\n}\n";
+        assert_eq!(
+            function_body_lines(code, "add"),
+            [Range { start: 2, end: 5 }]
+        );
+        assert_eq!(
+            function_body_lines(code, "run"),
+            [Range { start: 6, end: 10 }]
+        );
+        assert_eq!(
+            function_body_lines(code, "A::A"),
+            [Range { start: 10, end: 12 }]
+        );
+        assert_eq!(function_body_lines(code, "missing"), []);
+        // Names in comments and strings are ignored
+        assert_eq!(
+            function_body_lines("// add(int a) {\nconst char *s = \"add() {}\";\n", "add"),
+            []
+        );
+    }
+
+    #[test]
+    fn compare_structure() {
+        assert_eq!(
+            compare("struct A {\n\n};\n", "struct A\n{\n};\n// }\n"),
+            Ok(())
+        );
+        assert_eq!(
+            compare("if (a) { b(); }\n", "if (a)\n  b();\n"),
+            Err("input has 1 '{' but output has 0".to_string())
+        );
+    }
+}
@@ -0,0 +1,914 @@
+// SPDX-FileCopyrightText: 2023 Klarälvdalens Datakonsult AB, a KDAB Group company <info@kdab.com>
+// SPDX-FileContributor: Andrew Hayzen <andrew.hayzen@kdab.com>
+//
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::version::key_required_version;
+use crate::yaml::{self, unquote};
+use crate::{
+    detect_indent, ClangFormat, ClangFormatError, ClangFormatStyle, ClangFormatVersion, IndentStyle,
+};
+
+impl ClangFormatStyle {
+    /// Read a style configuration, such as the contents of a `.clang-format` file, into an inline `Custom` style
+    ///
+    /// This allows for style configuration from any source without writing it to a file.
+    /// The configuration is converted from block YAML into the inline `{ key: value, ... }` form,
+    /// configuration which is already inline is used as is.
+    ///
+    /// Only a single YAML document is supported, so configuration with sections for
+    /// multiple languages results in `ClangFormatError::InvalidStyleConfig`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use clang_format::ClangFormatStyle;
+    /// # fn main() {
+    /// let config = "BasedOnStyle: Mozilla\nIndentWidth: 8\n";
+    /// let style = ClangFormatStyle::from_reader(config.as_bytes());
+    /// assert!(style.is_ok());
+    /// assert_eq!(
+    ///     style.unwrap(),
+    ///     ClangFormatStyle::Custom("{ BasedOnStyle: Mozilla, IndentWidth: 8 }".to_string())
+    /// );
+    /// # }
+    /// ```
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<ClangFormatStyle, ClangFormatError> {
+        let mut config = String::new();
+        reader.read_to_string(&mut config)?;
+
+        if config.trim_start().starts_with('{') {
+            return Ok(Self::Custom(config.trim().to_string()));
+        }
+
+        yaml::parse(&config)
+            .map(|node| Self::Custom(node.to_flow()))
+            .map_err(ClangFormatError::InvalidStyleConfig)
+    }
+
+    /// Merge another style on top of this style, with keys of `other` overriding this style
+    ///
+    /// Both styles are converted to their key value pairs and combined into a single
+    /// inline `ClangFormatStyle::Custom` style.
+    ///
+    /// - Built-in styles are represented as `BasedOnStyle: <name>`
+    /// - `Custom` styles in the inline `{ key: value, ... }` form contribute their keys,
+    ///   any other `Custom` string is treated as the name of a style to base on
+    /// - `Default`, `File`, and `Unspecified` have no keys, so they contribute nothing
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use clang_format::ClangFormatStyle;
+    /// # fn main() {
+    /// let style = ClangFormatStyle::Mozilla
+    ///     .merge(ClangFormatStyle::Custom("{ IndentWidth: 4, ColumnLimit: 100 }".to_string()))
+    ///     .merge(ClangFormatStyle::Custom("{ IndentWidth: 8 }".to_string()));
+    /// assert_eq!(
+    ///     style,
+    ///     ClangFormatStyle::Custom("{ BasedOnStyle: Mozilla, IndentWidth: 8, ColumnLimit: 100 }".to_string())
+    /// );
+    /// # }
+    /// ```
+    pub fn merge(self, other: ClangFormatStyle) -> ClangFormatStyle {
+        let mut pairs = self.key_values();
+        for (key, value) in other.key_values() {
+            match pairs.iter_mut().find(|(existing, _)| *existing == key) {
+                Some(pair) => pair.1 = value,
+                None => pairs.push((key, value)),
+            }
+        }
+        Self::from_key_values(&pairs)
+    }
+
+    /// Create an inline style which is the base style with the given indent width
+    ///
+    /// This is a shortcut for merging `{ IndentWidth: <indent> }` on top of the base style.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use clang_format::ClangFormatStyle;
+    /// # fn main() {
+    /// assert_eq!(
+    ///     ClangFormatStyle::indented(ClangFormatStyle::Google, 4),
+    ///     ClangFormatStyle::Custom("{ BasedOnStyle: Google, IndentWidth: 4 }".to_string())
+    /// );
+    /// # }
+    /// ```
+    pub fn indented(base: ClangFormatStyle, indent: u32) -> ClangFormatStyle {
+        base.merge(Self::from_key_values(&[(
+            "IndentWidth".to_string(),
+            indent.to_string(),
+        )]))
+    }
+
+    /// Create an inline style which is the base style with the given column limit
+    ///
+    /// This is a shortcut for merging `{ ColumnLimit: <limit> }` on top of the base style.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use clang_format::ClangFormatStyle;
+    /// # fn main() {
+    /// assert_eq!(
+    ///     ClangFormatStyle::with_column_limit(ClangFormatStyle::Llvm, 120),
+    ///     ClangFormatStyle::Custom("{ BasedOnStyle: LLVM, ColumnLimit: 120 }".to_string())
+    /// );
+    /// # }
+    /// ```
+    pub fn with_column_limit(base: ClangFormatStyle, limit: u32) -> ClangFormatStyle {
+        base.merge(Self::from_key_values(&[(
+            "ColumnLimit".to_string(),
+            limit.to_string(),
+        )]))
+    }
+
+    /// Create an inline style which is the base style with an explicit include order
+    ///
+    /// The defaults for sorting includes differ between versions of clang-format and between
+    /// the built-in styles, so contributors with different versions get different orders.
+    /// This sets `SortIncludes`, `IncludeBlocks`, `IncludeCategories`, and `IncludeIsMainRegex`
+    /// so that the order is the same regardless of those defaults. Includes are sorted
+    /// case sensitively within each block, with the main header first, followed by
+    /// `"local"` headers, then `<headers.h>`, then any other `<headers>`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use clang_format::ClangFormatStyle;
+    /// # fn main() {
+    /// let style = ClangFormatStyle::pin_include_order(ClangFormatStyle::Google);
+    /// assert!(matches!(
+    ///     style,
+    ///     ClangFormatStyle::Custom(config) if config.starts_with("{ BasedOnStyle: Google, SortIncludes: true")
+    /// ));
+    /// # }
+    /// ```
+    pub fn pin_include_order(base: ClangFormatStyle) -> ClangFormatStyle {
+        let pairs = [
+            ("SortIncludes", "true"),
+            ("IncludeBlocks", "Preserve"),
+            (
+                "IncludeCategories",
+                "[{ Regex: '^\"', Priority: 1 }, { Regex: '^<.*\\.h>', Priority: 2 }, { Regex: '^<', Priority: 3 }]",
+            ),
+            ("IncludeIsMainRegex", "'(Test)?$'"),
+        ];
+        base.merge(Self::from_key_values(
+            &pairs.map(|(key, value)| (key.to_string(), value.to_string())),
+        ))
+    }
+
+    /// The keys of the style which are not supported by the given version of clang-format
+    ///
+    /// Only the top level keys of inline `Custom` styles are checked against an internal table
+    /// of the version which introduced each option, unknown keys are assumed to be supported.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use clang_format::ClangFormatStyle;
+    /// # fn main() {
+    /// let style = ClangFormatStyle::Custom("{ IndentWidth: 4, InsertBraces: true }".to_string());
+    /// assert_eq!(style.unsupported_keys((14, 0, 0)), ["InsertBraces"]);
+    /// assert!(style.unsupported_keys((15, 0, 0)).is_empty());
+    /// # }
+    /// ```
+    pub fn unsupported_keys(&self, version: impl Into<ClangFormatVersion>) -> Vec<String> {
+        let version = version.into();
+        self.key_values()
+            .into_iter()
+            .map(|(key, _)| key)
+            .filter(|key| key_required_version(key).is_some_and(|required| required > version))
+            .collect()
+    }
+
+    /// Remove the keys of the style which are not supported by the given version of clang-format
+    ///
+    /// This allows for a style which uses newer options to be used with an older clang-format,
+    /// which would otherwise fail on the unknown keys. The style is unchanged when all of its
+    /// keys are supported, see `ClangFormatStyle::unsupported_keys`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use clang_format::ClangFormatStyle;
+    /// # fn main() {
+    /// let style = ClangFormatStyle::Custom("{ IndentWidth: 4, InsertBraces: true }".to_string());
+    /// assert_eq!(
+    ///     style.compatible_with((14, 0, 0)),
+    ///     ClangFormatStyle::Custom("{ IndentWidth: 4 }".to_string())
+    /// );
+    /// # }
+    /// ```
+    pub fn compatible_with(&self, version: impl Into<ClangFormatVersion>) -> ClangFormatStyle {
+        let unsupported = self.unsupported_keys(version);
+        if unsupported.is_empty() {
+            return self.clone();
+        }
+        let pairs: Vec<(String, String)> = self
+            .key_values()
+            .into_iter()
+            .filter(|(key, _)| !unsupported.contains(key))
+            .collect();
+        Self::from_key_values(&pairs)
+    }
+
+    /// Create an inline style which is the base style with the indentation detected from the input
+    ///
+    /// This respects the existing choice of tabs or spaces, see `detect_indent`. Tabs use
+    /// `UseTab: ForIndentation` with an `IndentWidth` and `TabWidth` of 4, so that each level is
+    /// a single tab, spaces use `UseTab: Never` with the detected `IndentWidth`. The base style
+    /// is unchanged when no indentation is detected.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use clang_format::ClangFormatStyle;
+    /// # fn main() {
+    /// let style = ClangFormatStyle::preserve_indent(ClangFormatStyle::Llvm, "{\n   a();\n}\n");
+    /// assert_eq!(
+    ///     style,
+    ///     ClangFormatStyle::Custom("{ BasedOnStyle: LLVM, UseTab: Never, IndentWidth: 3 }".to_string())
+    /// );
+    /// # }
+    /// ```
+    pub fn preserve_indent(base: ClangFormatStyle, input: &str) -> ClangFormatStyle {
+        match detect_indent(input) {
+            IndentStyle::Unknown => base,
+            indent => base.merge(Self::from_key_values(&indent.key_values())),
+        }
+    }
+
+    /// Create an inline style which applies the overrides on top of the parent `.clang-format` file
+    ///
+    /// This is `BasedOnStyle: InheritParentConfig` with the keys of `overrides`, a `BasedOnStyle`
+    /// of the overrides is ignored. clang-format uses the `.clang-format` file of the parent
+    /// directory, or the fallback style if there is none, and applies the overrides to it.
+    ///
+    /// Since clang-format 12
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use clang_format::ClangFormatStyle;
+    /// # fn main() {
+    /// let style = ClangFormatStyle::inherit_parent_config("{ ColumnLimit: 20 }");
+    /// assert_eq!(
+    ///     style,
+    ///     ClangFormatStyle::Custom("{ BasedOnStyle: InheritParentConfig, ColumnLimit: 20 }".to_string())
+    /// );
+    /// # }
+    /// ```
+    pub fn inherit_parent_config(overrides: impl Into<ClangFormatStyle>) -> ClangFormatStyle {
+        let mut pairs = vec![(
+            "BasedOnStyle".to_string(),
+            "InheritParentConfig".to_string(),
+        )];
+        pairs.extend(
+            overrides
+                .into()
+                .key_values()
+                .into_iter()
+                .filter(|(key, _)| key != "BasedOnStyle"),
+        );
+        Self::from_key_values(&pairs)
+    }
+
+    /// Write the style as a `.clang-format` file in the given directory
+    ///
+    /// Built-in styles, including a `Custom` style which is the name of one, are written as
+    /// `BasedOnStyle: <name>`. For `ClangFormatStyle::File` the configuration file which
+    /// clang-format would find from the directory is copied, if there is none an error of
+    /// kind `std::io::ErrorKind::NotFound` is returned. Any other style is expanded into its
+    /// full configuration with `clang-format --dump-config`, executed in the directory.
+    /// An existing file is replaced.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use clang_format::ClangFormatStyle;
+    /// # fn main() {
+    /// let dir = std::env::temp_dir().join("clang-format-rs-example");
+    /// # std::fs::create_dir_all(&dir).unwrap();
+    /// assert!(ClangFormatStyle::Mozilla.write_dot_clang_format(&dir).is_ok());
+    /// assert_eq!(
+    ///     std::fs::read_to_string(dir.join(".clang-format")).unwrap(),
+    ///     "BasedOnStyle: Mozilla\n"
+    /// );
+    /// # std::fs::remove_dir_all(&dir).unwrap();
+    /// # }
+    /// ```
+    pub fn write_dot_clang_format(&self, dir: &Path) -> Result<(), ClangFormatError> {
+        let path = dir.join(".clang-format");
+        if *self == Self::File {
+            let found = find_config_file(dir).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!(
+                        "no .clang-format or _clang-format file found from {}",
+                        dir.display()
+                    ),
+                )
+            })?;
+            // Copying a file onto itself would truncate it
+            if found != path {
+                fs::copy(found, path)?;
+            }
+            return Ok(());
+        }
+
+        let config = match self.canonical_name() {
+            "" => ClangFormat::new()
+                .style(self)
+                .current_dir(dir)
+                .dump_config()?,
+            name => format!("BasedOnStyle: {name}\n"),
+        };
+        fs::write(path, config)?;
+        Ok(())
+    }
+
+    /// The name of the style with the exact casing clang-format expects, eg `LLVM` or `WebKit`
+    ///
+    /// This can be given to `--style` or used as `BasedOnStyle` in a `.clang-format` file.
+    /// A `Custom` style which is the name of a built-in style in any casing, such as `llvm`,
+    /// returns the name of that style. Styles without a name, such as `Default`, `Unspecified`,
+    /// or an inline `Custom` style, return an empty string.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use clang_format::ClangFormatStyle;
+    /// # fn main() {
+    /// assert_eq!(ClangFormatStyle::Llvm.canonical_name(), "LLVM");
+    /// assert_eq!(ClangFormatStyle::Custom("webkit".to_string()).canonical_name(), "WebKit");
+    /// # }
+    /// ```
+    pub fn canonical_name(&self) -> &'static str {
+        match self {
+            Self::Chromium => "Chromium",
+            Self::File => "file",
+            Self::GNU => "GNU",
+            Self::Google => "Google",
+            Self::Llvm => "LLVM",
+            Self::Microsoft => "Microsoft",
+            Self::Mozilla => "Mozilla",
+            Self::WebKit => "WebKit",
+            Self::Default | Self::Unspecified => "",
+            Self::Custom(custom) => Self::named()
+                .iter()
+                .map(Self::canonical_name)
+                .find(|name| name.eq_ignore_ascii_case(custom.trim()))
+                .unwrap_or_default(),
+        }
+    }
+
+    /// The keys which this style sets, other than `BasedOnStyle`
+    ///
+    /// Only the top level keys of an inline `Custom` style in the `{ key: value, ... }` form
+    /// are returned, in the order they appear. Any other style does not override any keys.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use clang_format::ClangFormatStyle;
+    /// # fn main() {
+    /// let style = ClangFormatStyle::Custom(
+    ///     "{ BasedOnStyle: Mozilla, IndentWidth: 8, ColumnLimit: 100 }".to_string(),
+    /// );
+    /// assert_eq!(style.overridden_keys(), ["IndentWidth", "ColumnLimit"]);
+    /// # }
+    /// ```
+    pub fn overridden_keys(&self) -> Vec<String> {
+        match self {
+            Self::Custom(custom) => flow_mapping_entries(custom)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(key, _)| key)
+                .filter(|key| key != "BasedOnStyle")
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    /// The styles which have a name, in the order they are documented
+    pub(crate) fn named() -> [ClangFormatStyle; 8] {
+        [
+            Self::Chromium,
+            Self::File,
+            Self::GNU,
+            Self::Google,
+            Self::Llvm,
+            Self::Microsoft,
+            Self::Mozilla,
+            Self::WebKit,
+        ]
+    }
+
+    /// Converts the style into the key value pairs of an inline style
+    pub(crate) fn key_values(&self) -> Vec<(String, String)> {
+        match self {
+            Self::Default | Self::File | Self::Unspecified => vec![],
+            Self::Custom(custom) => match flow_mapping_entries(custom) {
+                Some(entries) => entries,
+                None => vec![("BasedOnStyle".to_string(), custom.trim().to_string())],
+            },
+            named => vec![("BasedOnStyle".to_string(), named.as_str().to_string())],
+        }
+    }
+
+    /// Create an inline `Custom` style from key value pairs
+    pub(crate) fn from_key_values(pairs: &[(String, String)]) -> ClangFormatStyle {
+        let entries: Vec<String> = pairs
+            .iter()
+            .map(|(key, value)| format!("{key}: {value}"))
+            .collect();
+        if entries.is_empty() {
+            Self::Custom("{}".to_string())
+        } else {
+            Self::Custom(format!("{{ {} }}", entries.join(", ")))
+        }
+    }
+}
+
+impl FromStr for ClangFormatStyle {
+    type Err = ClangFormatError;
+
+    /// Parse a style as given to the `--style` argument of clang-format
+    ///
+    /// Names of built-in styles are accepted in any casing, such as `llvm` or `WebKit`,
+    /// as is `file`. Inline configuration such as `{ IndentWidth: 8 }` and `none`, which
+    /// disables formatting, are parsed as `ClangFormatStyle::Custom`.
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let name = name.trim();
+        if name.starts_with('{') || name.eq_ignore_ascii_case("none") {
+            return Ok(Self::Custom(name.to_string()));
+        }
+
+        Self::named()
+            .into_iter()
+            .find(|style| style.canonical_name().eq_ignore_ascii_case(name))
+            .ok_or_else(|| ClangFormatError::UnknownStyleName(name.to_string()))
+    }
+}
+
+impl From<&str> for ClangFormatStyle {
+    /// Convert a string into a style, names of built-in styles in any casing and `file` are
+    /// parsed with `FromStr`, anything else is used as a `ClangFormatStyle::Custom` style
+    fn from(style: &str) -> Self {
+        style
+            .parse()
+            .unwrap_or_else(|_| Self::Custom(style.to_string()))
+    }
+}
+
+impl From<String> for ClangFormatStyle {
+    /// Convert a string into a style, see `From<&str>`
+    fn from(style: String) -> Self {
+        match style.parse() {
+            Ok(parsed) => parsed,
+            Err(_) => Self::Custom(style),
+        }
+    }
+}
+
+impl From<&ClangFormatStyle> for ClangFormatStyle {
+    fn from(style: &ClangFormatStyle) -> Self {
+        style.clone()
+    }
+}
+
+/// Parse the top level entries of a YAML flow mapping such as `{ IndentWidth: 8, ... }`
+///
+/// This is not a full YAML parser, values are kept as their raw text including any
+/// quotes or nested mappings and sequences. Returns `None` if the input is not a mapping.
+pub(crate) fn flow_mapping_entries(input: &str) -> Option<Vec<(String, String)>> {
+    let inner = input.trim().strip_prefix('{')?.strip_suffix('}')?;
+
+    split_top_level(inner, ',')
+        .into_iter()
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            // The key is everything before the first top level colon
+            let key = split_top_level(entry, ':')[0];
+            let value = entry.get(key.len() + 1..)?;
+            Some((unquote(key.trim()).to_string(), value.trim().to_string()))
+        })
+        .collect()
+}
+
+/// Find the configuration file clang-format uses for `--style=file`, starting at the given directory
+///
+/// Like clang-format, each directory up to the root is searched for a `.clang-format` file,
+/// then a `_clang-format` file.
+pub(crate) fn find_config_file(start: &Path) -> Option<PathBuf> {
+    start.ancestors().find_map(|dir| {
+        [".clang-format", "_clang-format"]
+            .into_iter()
+            .map(|name| dir.join(name))
+            .find(|path| path.is_file())
+    })
+}
+
+/// The top level keys whose values differ between two outputs of `clang-format --dump-config`
+///
+/// Values are compared in YAML flow style, a key which is missing from one of the outputs
+/// has an empty value. Keys are in the order of the first output, then any only in the second.
+pub(crate) fn config_diff(
+    config_a: &str,
+    config_b: &str,
+) -> Result<Vec<(String, String, String)>, ClangFormatError> {
+    let entries =
+        |config| match yaml::parse(config).map_err(ClangFormatError::InvalidStyleConfig)? {
+            yaml::Node::Mapping(entries) => Ok(entries
+                .into_iter()
+                .map(|(key, value)| (unquote(&key).to_string(), value.to_flow()))
+                .collect::<Vec<_>>()),
+            node => Err(ClangFormatError::InvalidStyleConfig(format!(
+                "expected a mapping, found {}",
+                node.to_flow()
+            ))),
+        };
+    let entries_a = entries(config_a)?;
+    let entries_b = entries(config_b)?;
+    let value = |entries: &[(String, String)], key: &str| {
+        entries
+            .iter()
+            .find(|(candidate, _)| candidate == key)
+            .map(|(_, value)| value.clone())
+            .unwrap_or_default()
+    };
+
+    let mut keys: Vec<&String> = entries_a.iter().map(|(key, _)| key).collect();
+    keys.extend(
+        entries_b
+            .iter()
+            .map(|(key, _)| key)
+            .filter(|key| entries_a.iter().all(|(candidate, _)| candidate != *key)),
+    );
+    Ok(keys
+        .into_iter()
+        .map(|key| (key.clone(), value(&entries_a, key), value(&entries_b, key)))
+        .filter(|(_, value_a, value_b)| value_a != value_b)
+        .collect())
+}
+
+/// Find the value of a top level key in the output of `clang-format --dump-config`
+pub(crate) fn config_value<'a>(config: &'a str, key: &str) -> Option<&'a str> {
+    config
+        .lines()
+        .find_map(|line| line.strip_prefix(key)?.strip_prefix(':').map(str::trim))
+}
+
+/// Split the input on the separator, ignoring separators inside quotes or nested collections
+fn split_top_level(input: &str, separator: char) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut depth = 0usize;
+    let mut quote: Option<char> = None;
+    let mut start = 0;
+
+    for (index, c) in input.char_indices() {
+        match (quote, c) {
+            (Some(open), c) if c == open => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"') => quote = Some(c),
+            (None, '{' | '[') => depth += 1,
+            (None, '}' | ']') => depth = depth.saturating_sub(1),
+            (None, c) if c == separator && depth == 0 => {
+                parts.push(&input[start..index]);
+                start = index + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&input[start..]);
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(entries: &[(&str, &str)]) -> Vec<(String, String)> {
+        entries
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn parse_flow_mapping() {
+        assert_eq!(
+            flow_mapping_entries(
+                "{BasedOnStyle: 'Mozilla',
+                    IndentWidth: 8, BraceWrapping: { AfterClass: true, AfterEnum: false }, 'Key': \"a, b\"}"
+            ),
+            Some(pairs(&[
+                ("BasedOnStyle", "'Mozilla'"),
+                ("IndentWidth", "8"),
+                ("BraceWrapping", "{ AfterClass: true, AfterEnum: false }"),
+                ("Key", "\"a, b\""),
+            ]))
+        );
+        assert_eq!(flow_mapping_entries("{}"), Some(vec![]));
+        assert_eq!(flow_mapping_entries("Mozilla"), None);
+        assert_eq!(flow_mapping_entries("{ IndentWidth }"), None);
+    }
+
+    #[test]
+    fn diff_configs() {
+        let config_a = "---\nLanguage: Cpp\nColumnLimit: 80\nBraceWrapping:\n  AfterClass: false\nSortIncludes: true\n...\n";
+        let config_b = "---\nLanguage: Cpp\nColumnLimit: 80\nBraceWrapping:\n  AfterClass: true\nSortIncludes: CaseSensitive\nInsertBraces: false\n...\n";
+        let diff = |a, b, c| (String::from(a), String::from(b), String::from(c));
+        assert_eq!(
+            config_diff(config_a, config_b).unwrap(),
+            [
+                diff(
+                    "BraceWrapping",
+                    "{ AfterClass: false }",
+                    "{ AfterClass: true }"
+                ),
+                diff("SortIncludes", "true", "CaseSensitive"),
+                diff("InsertBraces", "", "false"),
+            ]
+        );
+        assert!(config_diff(config_a, config_a).unwrap().is_empty());
+    }
+
+    #[test]
+    fn parse_config_value() {
+        let config = "---\nLanguage:        Cpp\nBraceWrapping:\n  AfterClass:      false\nColumnLimit:     80\nCommentPragmas:  '^ IWYU pragma:'\n...\n";
+        assert_eq!(config_value(config, "Language"), Some("Cpp"));
+        assert_eq!(config_value(config, "ColumnLimit"), Some("80"));
+        assert_eq!(
+            config_value(config, "CommentPragmas"),
+            Some("'^ IWYU pragma:'")
+        );
+        assert_eq!(config_value(config, "AfterClass"), None);
+        assert_eq!(config_value(config, "Column"), None);
+    }
+
+    #[test]
+    fn style_compatible_with() {
+        let style = ClangFormatStyle::Custom(
+            "{ BasedOnStyle: LLVM, InsertBraces: true, 'LineEnding': LF, IndentWidth: 4 }"
+                .to_string(),
+        );
+        assert_eq!(style.unsupported_keys((15, 0, 0)), ["LineEnding"]);
+        assert_eq!(
+            style.compatible_with((14, 0, 0)),
+            ClangFormatStyle::Custom("{ BasedOnStyle: LLVM, IndentWidth: 4 }".to_string())
+        );
+        assert_eq!(style.compatible_with((16, 0, 0)), style);
+        assert_eq!(
+            ClangFormatStyle::Mozilla.compatible_with((6, 0, 0)),
+            ClangFormatStyle::Mozilla
+        );
+    }
+
+    #[test]
+    fn style_preserve_indent() {
+        assert_eq!(
+            ClangFormatStyle::preserve_indent(ClangFormatStyle::Mozilla, "{\n\ta();\n}\n"),
+            ClangFormatStyle::Custom(
+                "{ BasedOnStyle: Mozilla, UseTab: ForIndentation, IndentWidth: 4, TabWidth: 4 }"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            ClangFormatStyle::preserve_indent(ClangFormatStyle::Mozilla, "int a;\n"),
+            ClangFormatStyle::Mozilla
+        );
+    }
+
+    #[test]
+    fn style_from_reader() {
+        let config = "---\nBasedOnStyle: LLVM\nBraceWrapping:\n  AfterClass: true\n...\n";
+        assert_eq!(
+            ClangFormatStyle::from_reader(config.as_bytes()).unwrap(),
+            ClangFormatStyle::Custom(
+                "{ BasedOnStyle: LLVM, BraceWrapping: { AfterClass: true } }".to_string()
+            )
+        );
+
+        let config = "  { BasedOnStyle: LLVM }\n";
+        assert_eq!(
+            ClangFormatStyle::from_reader(config.as_bytes()).unwrap(),
+            ClangFormatStyle::Custom("{ BasedOnStyle: LLVM }".to_string())
+        );
+
+        let config = "Language: Cpp\n---\nLanguage: Java\n";
+        assert!(matches!(
+            ClangFormatStyle::from_reader(config.as_bytes()),
+            Err(ClangFormatError::InvalidStyleConfig(_))
+        ));
+    }
+
+    #[test]
+    fn style_canonical_name() {
+        for name in ["LLVM", "llvm", "Llvm", " LLVM "] {
+            assert_eq!(
+                ClangFormatStyle::Custom(name.to_string()).canonical_name(),
+                "LLVM"
+            );
+        }
+        assert_eq!(ClangFormatStyle::Microsoft.canonical_name(), "Microsoft");
+        assert_eq!(ClangFormatStyle::File.canonical_name(), "file");
+        assert_eq!(ClangFormatStyle::Default.canonical_name(), "");
+        assert_eq!(
+            ClangFormatStyle::Custom("{ BasedOnStyle: LLVM }".to_string()).canonical_name(),
+            ""
+        );
+    }
+
+    #[test]
+    fn style_from_str() {
+        assert_eq!(
+            "LLVM".parse::<ClangFormatStyle>().unwrap(),
+            ClangFormatStyle::Llvm
+        );
+        assert_eq!(
+            "gnu".parse::<ClangFormatStyle>().unwrap(),
+            ClangFormatStyle::GNU
+        );
+        assert_eq!(
+            "File".parse::<ClangFormatStyle>().unwrap(),
+            ClangFormatStyle::File
+        );
+        assert_eq!(
+            "none".parse::<ClangFormatStyle>().unwrap(),
+            ClangFormatStyle::Custom("none".to_string())
+        );
+        assert_eq!(
+            "{ IndentWidth: 8 }".parse::<ClangFormatStyle>().unwrap(),
+            ClangFormatStyle::Custom("{ IndentWidth: 8 }".to_string())
+        );
+        assert!(matches!(
+            "".parse::<ClangFormatStyle>(),
+            Err(ClangFormatError::UnknownStyleName(_))
+        ));
+    }
+
+    #[test]
+    fn style_from_string() {
+        assert_eq!(ClangFormatStyle::from("Google"), ClangFormatStyle::Google);
+        assert_eq!(
+            ClangFormatStyle::from("mozilla".to_string()),
+            ClangFormatStyle::Mozilla
+        );
+        assert_eq!(
+            ClangFormatStyle::from("{ IndentWidth: 8 }"),
+            ClangFormatStyle::Custom("{ IndentWidth: 8 }".to_string())
+        );
+        assert_eq!(
+            ClangFormatStyle::from("Unknown".to_string()),
+            ClangFormatStyle::Custom("Unknown".to_string())
+        );
+        assert_eq!(
+            ClangFormatStyle::from(&ClangFormatStyle::WebKit),
+            ClangFormatStyle::WebKit
+        );
+    }
+
+    #[test]
+    fn style_overridden_keys() {
+        let style = ClangFormatStyle::Custom(
+            "{BasedOnStyle: LLVM, 'IndentWidth': 8, BraceWrapping: { AfterClass: true }}"
+                .to_string(),
+        );
+        assert_eq!(style.overridden_keys(), ["IndentWidth", "BraceWrapping"]);
+        assert!(
+            ClangFormatStyle::Custom("{ BasedOnStyle: LLVM }".to_string())
+                .overridden_keys()
+                .is_empty()
+        );
+        assert!(ClangFormatStyle::Custom("WebKit".to_string())
+            .overridden_keys()
+            .is_empty());
+        assert!(ClangFormatStyle::Mozilla.overridden_keys().is_empty());
+    }
+
+    #[test]
+    fn style_inherit_parent_config() {
+        assert_eq!(
+            ClangFormatStyle::inherit_parent_config(ClangFormatStyle::Custom(
+                "{ BasedOnStyle: LLVM, IndentWidth: 8 }".to_string()
+            )),
+            ClangFormatStyle::Custom(
+                "{ BasedOnStyle: InheritParentConfig, IndentWidth: 8 }".to_string()
+            )
+        );
+        assert_eq!(
+            ClangFormatStyle::inherit_parent_config(ClangFormatStyle::Default),
+            ClangFormatStyle::Custom("{ BasedOnStyle: InheritParentConfig }".to_string())
+        );
+    }
+
+    #[test]
+    fn indentation_shortcuts() {
+        let style = ClangFormatStyle::with_column_limit(
+            ClangFormatStyle::indented(ClangFormatStyle::Mozilla, 4),
+            100,
+        );
+        assert_eq!(
+            style,
+            ClangFormatStyle::Custom(
+                "{ BasedOnStyle: Mozilla, IndentWidth: 4, ColumnLimit: 100 }".to_string()
+            )
+        );
+        assert_eq!(
+            ClangFormatStyle::indented(
+                ClangFormatStyle::Custom("{ IndentWidth: 2 }".to_string()),
+                8
+            ),
+            ClangFormatStyle::Custom("{ IndentWidth: 8 }".to_string())
+        );
+    }
+
+    #[test]
+    fn pin_include_order() {
+        let style = ClangFormatStyle::pin_include_order(ClangFormatStyle::Custom(
+            "{ BasedOnStyle: LLVM, SortIncludes: false, IncludeBlocks: Regroup }".to_string(),
+        ));
+        let pairs = style.key_values();
+        assert_eq!(pairs[0], ("BasedOnStyle".to_string(), "LLVM".to_string()));
+        assert_eq!(pairs[1], ("SortIncludes".to_string(), "true".to_string()));
+        assert_eq!(
+            pairs[2],
+            ("IncludeBlocks".to_string(), "Preserve".to_string())
+        );
+        assert_eq!(pairs[3].0, "IncludeCategories");
+        assert!(pairs[3].1.starts_with("[{ Regex: '^\"', Priority: 1 }"));
+        assert_eq!(pairs.len(), 5);
+    }
+
+    #[test]
+    fn write_dot_clang_format_named() {
+        let dir =
+            std::env::temp_dir().join(format!("clang-format-rs-write-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        ClangFormatStyle::Custom("webkit".to_string())
+            .write_dot_clang_format(&dir)
+            .unwrap();
+        let config = fs::read_to_string(dir.join(".clang-format")).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(config, "BasedOnStyle: WebKit\n");
+    }
+
+    #[test]
+    fn write_dot_clang_format_file() {
+        let dir =
+            std::env::temp_dir().join(format!("clang-format-rs-write-file-{}", std::process::id()));
+        let nested = dir.join("src");
+        fs::create_dir_all(&nested).unwrap();
+        let missing = ClangFormatStyle::File.write_dot_clang_format(&nested);
+
+        fs::write(dir.join("_clang-format"), "IndentWidth: 8\n").unwrap();
+        ClangFormatStyle::File
+            .write_dot_clang_format(&nested)
+            .unwrap();
+        let copied = fs::read_to_string(nested.join(".clang-format")).unwrap();
+        // The file which is found is already in place
+        ClangFormatStyle::File
+            .write_dot_clang_format(&nested)
+            .unwrap();
+        let in_place = fs::read_to_string(nested.join(".clang-format")).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(matches!(
+            missing,
+            Err(ClangFormatError::Io(err)) if err.kind() == io::ErrorKind::NotFound
+        ));
+        assert_eq!(copied, "IndentWidth: 8\n");
+        assert_eq!(in_place, "IndentWidth: 8\n");
+    }
+
+    #[test]
+    fn merge_styles() {
+        let style =
+            ClangFormatStyle::Custom("{ BasedOnStyle: Mozilla, IndentWidth: 4 }".to_string())
+                .merge(ClangFormatStyle::Google);
+        assert_eq!(
+            style,
+            ClangFormatStyle::Custom("{ BasedOnStyle: Google, IndentWidth: 4 }".to_string())
+        );
+
+        let style = ClangFormatStyle::Default.merge(ClangFormatStyle::Custom("WebKit".to_string()));
+        assert_eq!(
+            style,
+            ClangFormatStyle::Custom("{ BasedOnStyle: WebKit }".to_string())
+        );
+
+        let style = ClangFormatStyle::Default.merge(ClangFormatStyle::File);
+        assert_eq!(style, ClangFormatStyle::Custom("{}".to_string()));
+    }
+}
@@ -0,0 +1,180 @@
+// SPDX-FileCopyrightText: 2023 Klarälvdalens Datakonsult AB, a KDAB Group company <info@kdab.com>
+// SPDX-FileContributor: Andrew Hayzen <andrew.hayzen@kdab.com>
+//
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! A typed view of the configuration from `--dump-config`
+
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use crate::yaml::{self, unquote, Node};
+use crate::{ClangFormatError, Language};
+
+/// When clang-format uses tab characters, the values of the `UseTab` option
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum UseTab {
+    /// Never use tabs
+    Never,
+    /// Use tabs only for indentation
+    ForIndentation,
+    /// Use tabs for line continuation and indentation
+    ForContinuationAndIndentation,
+    /// Use tabs for indentation, and spaces for alignment
+    AlignWithSpaces,
+    /// Use tabs whenever at least one tab stop is filled
+    Always,
+}
+
+impl UseTab {
+    /// Converts the string used in clang-format configuration to the enum UseTab
+    fn from_config_str(value: &str) -> Option<Self> {
+        match value {
+            "Never" | "false" => Some(Self::Never),
+            "ForIndentation" => Some(Self::ForIndentation),
+            "ForContinuationAndIndentation" => Some(Self::ForContinuationAndIndentation),
+            "AlignWithSpaces" => Some(Self::AlignWithSpaces),
+            "Always" | "true" => Some(Self::Always),
+            _ => None,
+        }
+    }
+}
+
+/// The configuration clang-format uses for a style, see `ClangFormat::dump_config_typed`
+///
+/// The common options are parsed into typed fields, which are `None` when clang-format
+/// did not report the option. Every other option is kept in `other` as its YAML flow value,
+/// such as `{ AfterClass: false }` for `BraceWrapping`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct StyleConfig {
+    /// The `Language` option
+    pub language: Option<Language>,
+    /// The `ColumnLimit` option, where 0 means there is no limit
+    pub column_limit: Option<u32>,
+    /// The `IndentWidth` option
+    pub indent_width: Option<u32>,
+    /// The `ContinuationIndentWidth` option
+    pub continuation_indent_width: Option<u32>,
+    /// The `TabWidth` option
+    pub tab_width: Option<u32>,
+    /// The `UseTab` option
+    pub use_tab: Option<UseTab>,
+    /// The `AccessModifierOffset` option
+    pub access_modifier_offset: Option<i32>,
+    /// The `MaxEmptyLinesToKeep` option
+    pub max_empty_lines_to_keep: Option<u32>,
+    /// The remaining options, with their values in YAML flow style
+    pub other: BTreeMap<String, String>,
+}
+
+impl StyleConfig {
+    /// Parse the block YAML output of `--dump-config`
+    pub(crate) fn parse(config: &str) -> Result<Self, ClangFormatError> {
+        let entries = match yaml::parse(config).map_err(ClangFormatError::InvalidStyleConfig)? {
+            Node::Mapping(entries) => entries,
+            node => {
+                return Err(ClangFormatError::InvalidStyleConfig(format!(
+                    "expected a mapping, found {}",
+                    node.to_flow()
+                )))
+            }
+        };
+
+        let mut style = Self::default();
+        for (key, node) in entries {
+            let key = unquote(&key).to_string();
+            let value = match &node {
+                Node::Scalar(scalar) => unquote(scalar),
+                _ => {
+                    style.other.insert(key, node.to_flow());
+                    continue;
+                }
+            };
+            match key.as_str() {
+                "Language" => {
+                    style.language = Some(
+                        Language::from_config_str(value)
+                            .ok_or_else(|| ClangFormatError::UnknownLanguage(value.to_string()))?,
+                    )
+                }
+                "ColumnLimit" => style.column_limit = Some(number(&key, value)?),
+                "IndentWidth" => style.indent_width = Some(number(&key, value)?),
+                "ContinuationIndentWidth" => {
+                    style.continuation_indent_width = Some(number(&key, value)?)
+                }
+                "TabWidth" => style.tab_width = Some(number(&key, value)?),
+                "UseTab" => {
+                    style.use_tab = Some(
+                        UseTab::from_config_str(value).ok_or_else(|| invalid_value(&key, value))?,
+                    )
+                }
+                "AccessModifierOffset" => style.access_modifier_offset = Some(number(&key, value)?),
+                "MaxEmptyLinesToKeep" => style.max_empty_lines_to_keep = Some(number(&key, value)?),
+                _ => {
+                    style.other.insert(key, node.to_flow());
+                }
+            }
+        }
+        Ok(style)
+    }
+}
+
+/// Parse the value of a numeric option
+fn number<T: FromStr>(key: &str, value: &str) -> Result<T, ClangFormatError> {
+    value.parse().map_err(|_| invalid_value(key, value))
+}
+
+fn invalid_value(key: &str, value: &str) -> ClangFormatError {
+    ClangFormatError::InvalidStyleConfig(format!("invalid value for {key}: {value}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_dump_config() {
+        let config = r#"---
+Language:        Cpp
+AccessModifierOffset: -2
+BraceWrapping:
+  AfterClass:      false
+  AfterEnum:       true
+ColumnLimit:     80
+CommentPragmas:  '^ IWYU pragma:'
+IndentWidth:     4
+TabWidth:        8
+UseTab:          ForIndentation
+...
+"#;
+        let style = StyleConfig::parse(config).unwrap();
+        assert_eq!(style.language, Some(Language::Cpp));
+        assert_eq!(style.access_modifier_offset, Some(-2));
+        assert_eq!(style.column_limit, Some(80));
+        assert_eq!(style.indent_width, Some(4));
+        assert_eq!(style.tab_width, Some(8));
+        assert_eq!(style.use_tab, Some(UseTab::ForIndentation));
+        assert_eq!(style.continuation_indent_width, None);
+        assert_eq!(
+            style.other.get("BraceWrapping").map(String::as_str),
+            Some("{ AfterClass: false, AfterEnum: true }")
+        );
+        assert_eq!(
+            style.other.get("CommentPragmas").map(String::as_str),
+            Some("'^ IWYU pragma:'")
+        );
+    }
+
+    #[test]
+    fn parse_invalid_value() {
+        assert!(matches!(
+            StyleConfig::parse("IndentWidth: four\n"),
+            Err(ClangFormatError::InvalidStyleConfig(message)) if message == "invalid value for IndentWidth: four"
+        ));
+        assert!(matches!(
+            StyleConfig::parse("UseTab: Sometimes\n"),
+            Err(ClangFormatError::InvalidStyleConfig(_))
+        ));
+    }
+}
@@ -0,0 +1,59 @@
+// SPDX-FileCopyrightText: 2023 Klarälvdalens Datakonsult AB, a KDAB Group company <info@kdab.com>
+// SPDX-FileContributor: Andrew Hayzen <andrew.hayzen@kdab.com>
+//
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Declarations of the Unix C library functions which are used, as there is no libc dependency
+
+use std::os::raw::c_int;
+#[cfg(feature = "resource_usage")]
+use std::os::raw::c_long;
+
+/// The type of the process id passed to `getpriority` and `setpriority`
+#[cfg(any(target_os = "freebsd", target_os = "dragonfly"))]
+pub(crate) type Id = i64;
+#[cfg(not(any(target_os = "freebsd", target_os = "dragonfly")))]
+pub(crate) type Id = u32;
+
+/// The type of the microseconds of a `TimeVal`
+#[cfg(all(feature = "resource_usage", target_os = "macos"))]
+type Microseconds = i32;
+#[cfg(all(feature = "resource_usage", not(target_os = "macos")))]
+type Microseconds = c_long;
+
+/// The layout of `struct timeval`
+#[cfg(feature = "resource_usage")]
+#[repr(C)]
+pub(crate) struct TimeVal {
+    pub(crate) tv_sec: c_long,
+    pub(crate) tv_usec: Microseconds,
+}
+
+/// The layout of `struct rusage`
+#[cfg(feature = "resource_usage")]
+#[repr(C)]
+pub(crate) struct RUsage {
+    pub(crate) ru_utime: TimeVal,
+    pub(crate) ru_stime: TimeVal,
+    pub(crate) ru_maxrss: c_long,
+    // The remaining fields are not used
+    _ru_other: [c_long; 13],
+}
+
+/// Select the priority of a process for `getpriority` and `setpriority`
+pub(crate) const PRIO_PROCESS: c_int = 0;
+
+/// Select the usage of the children which have been waited for in `getrusage`
+#[cfg(feature = "resource_usage")]
+pub(crate) const RUSAGE_CHILDREN: c_int = -1;
+
+/// The signal which cannot be caught or ignored
+pub(crate) const SIGKILL: c_int = 9;
+
+extern "C" {
+    pub(crate) fn getpriority(which: c_int, who: Id) -> c_int;
+    pub(crate) fn setpriority(which: c_int, who: Id, prio: c_int) -> c_int;
+    pub(crate) fn kill(pid: c_int, sig: c_int) -> c_int;
+    #[cfg(feature = "resource_usage")]
+    pub(crate) fn getrusage(who: c_int, usage: *mut RUsage) -> c_int;
+}
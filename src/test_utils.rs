@@ -0,0 +1,175 @@
+// SPDX-FileCopyrightText: 2023 Klarälvdalens Datakonsult AB, a KDAB Group company <info@kdab.com>
+// SPDX-FileContributor: Andrew Hayzen <andrew.hayzen@kdab.com>
+//
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Runners and helpers which are shared by the tests of several modules
+
+use std::ffi::{OsStr, OsString};
+#[cfg(feature = "async")]
+use std::future::Future;
+use std::process::{Command, ExitStatus, Output};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "async")]
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread;
+#[cfg(feature = "async")]
+use std::thread::Thread;
+use std::time::Duration;
+
+use crate::{ClangFormatError, FormatRunner};
+
+/// Echoes the input in uppercase
+#[derive(Debug)]
+pub(crate) struct UppercaseRunner;
+
+impl FormatRunner for UppercaseRunner {
+    fn run(&self, _command: &mut Command, input: &[u8]) -> Result<Output, ClangFormatError> {
+        Ok(Output {
+            status: ExitStatus::default(),
+            stdout: input.to_ascii_uppercase(),
+            stderr: vec![],
+        })
+    }
+}
+
+/// Replies with scripted output, recording the arguments it is run with
+///
+/// Without a fixed stdout the input is echoed. A reply replaces stdout when the program
+/// or any argument of the command equals its trigger, the first matching reply is used.
+#[derive(Debug, Default)]
+pub(crate) struct ScriptedRunner {
+    status: ExitStatus,
+    stdout: Option<Vec<u8>>,
+    stderr: Vec<u8>,
+    replies: Vec<(OsString, Vec<u8>)>,
+    required_arg: Option<OsString>,
+    args: Mutex<Vec<Vec<OsString>>>,
+}
+
+impl ScriptedRunner {
+    /// A runner which succeeds and echoes the input
+    pub(crate) fn echo() -> Self {
+        Self::default()
+    }
+
+    /// A runner which succeeds and always outputs the given stdout
+    pub(crate) fn output(stdout: &str) -> Self {
+        Self {
+            stdout: Some(stdout.as_bytes().to_vec()),
+            ..Self::default()
+        }
+    }
+
+    /// Exit with the given status
+    pub(crate) fn status(mut self, status: ExitStatus) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// Print the given stderr
+    pub(crate) fn stderr(mut self, stderr: &str) -> Self {
+        self.stderr = stderr.as_bytes().to_vec();
+        self
+    }
+
+    /// Output the given stdout instead when the program or an argument equals the trigger
+    pub(crate) fn reply_to(mut self, trigger: &str, stdout: &str) -> Self {
+        self.replies
+            .push((trigger.into(), stdout.as_bytes().to_vec()));
+        self
+    }
+
+    /// Panic if the runner is run without the given argument
+    pub(crate) fn require_arg(mut self, arg: &str) -> Self {
+        self.required_arg = Some(arg.into());
+        self
+    }
+
+    /// The arguments of each run so far
+    pub(crate) fn args(&self) -> Vec<Vec<OsString>> {
+        self.args.lock().unwrap().clone()
+    }
+}
+
+impl FormatRunner for ScriptedRunner {
+    fn run(&self, command: &mut Command, input: &[u8]) -> Result<Output, ClangFormatError> {
+        let args: Vec<OsString> = command.get_args().map(OsStr::to_os_string).collect();
+        if let Some(required) = &self.required_arg {
+            assert!(args.contains(required), "missing argument {required:?}");
+        }
+        let reply = self.replies.iter().find_map(|(trigger, stdout)| {
+            (command.get_program() == trigger || args.contains(trigger)).then_some(stdout)
+        });
+        let stdout = reply
+            .or(self.stdout.as_ref())
+            .cloned()
+            .unwrap_or_else(|| input.to_vec());
+        self.args.lock().unwrap().push(args);
+        Ok(Output {
+            status: self.status,
+            stdout,
+            stderr: self.stderr.clone(),
+        })
+    }
+}
+
+impl FormatRunner for Arc<ScriptedRunner> {
+    fn run(&self, command: &mut Command, input: &[u8]) -> Result<Output, ClangFormatError> {
+        self.as_ref().run(command, input)
+    }
+}
+
+/// Echoes the input, recording the largest number of concurrent runs
+#[derive(Debug, Default)]
+pub(crate) struct ConcurrencyRunner {
+    active: AtomicUsize,
+    max_active: AtomicUsize,
+}
+
+impl ConcurrencyRunner {
+    /// The largest number of runs which were active at the same time
+    pub(crate) fn max_active(&self) -> usize {
+        self.max_active.load(Ordering::SeqCst)
+    }
+}
+
+impl FormatRunner for Arc<ConcurrencyRunner> {
+    fn run(&self, _command: &mut Command, input: &[u8]) -> Result<Output, ClangFormatError> {
+        let active = self.active.fetch_add(1, Ordering::SeqCst) + 1;
+        self.max_active.fetch_max(active, Ordering::SeqCst);
+        thread::sleep(Duration::from_millis(10));
+        self.active.fetch_sub(1, Ordering::SeqCst);
+        Ok(Output {
+            status: ExitStatus::default(),
+            stdout: input.to_vec(),
+            stderr: vec![],
+        })
+    }
+}
+
+/// Wakes the thread which is blocked on the future
+#[cfg(feature = "async")]
+struct ThreadWaker(Thread);
+
+#[cfg(feature = "async")]
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Poll the future on the current thread until it is ready
+#[cfg(feature = "async")]
+pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = std::pin::pin!(future);
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
@@ -0,0 +1,69 @@
+// SPDX-FileCopyrightText: 2023 Klarälvdalens Datakonsult AB, a KDAB Group company <info@kdab.com>
+// SPDX-FileContributor: Andrew Hayzen <andrew.hayzen@kdab.com>
+//
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Helpers for test suites which compare formatted code with expected golden output
+
+use crate::{diff, ClangFormat, ClangFormatStyle};
+
+/// The number of unchanged lines shown around each change in the diff of a mismatch
+const DIFF_CONTEXT: usize = 3;
+
+/// Format the input with the style and assert that the output is the expected text
+///
+/// If formatting fails or the output differs, this panics with the error or with a
+/// unified diff from the expected text to the output.
+///
+/// # Example
+///
+/// ```
+/// # use clang_format::testing::assert_formats_to;
+/// # use clang_format::ClangFormatStyle;
+/// # fn main() {
+/// assert_formats_to("struct Test {};\n", &ClangFormatStyle::Mozilla, "struct Test\n{};\n");
+/// # }
+/// ```
+#[track_caller]
+pub fn assert_formats_to(input: &str, style: impl Into<ClangFormatStyle>, expected: &str) {
+    assert_formats_to_with(&ClangFormat::new().style(style), input, expected);
+}
+
+/// Format the input with the builder and assert that the output is the expected text
+///
+/// This is `assert_formats_to` with all of the options of a `ClangFormat`, such as an
+/// assumed filename or a `FormatRunner`.
+#[track_caller]
+pub fn assert_formats_to_with(format: &ClangFormat, input: &str, expected: &str) {
+    let output = match format.format(input) {
+        Ok(output) => output,
+        Err(err) => panic!("clang-format failed: {err}"),
+    };
+    if output != expected {
+        panic!(
+            "formatted output does not match the expected output\n{}",
+            diff::unified(expected, &output, "expected", "formatted", DIFF_CONTEXT)
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::UppercaseRunner;
+
+    #[test]
+    fn formats_to() {
+        let format = ClangFormat::new().runner(UppercaseRunner);
+        assert_formats_to_with(&format, "int a;\n", "INT A;\n");
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "formatted output does not match the expected output\n--- expected\n+++ formatted\n@@ -1,2 +1,2 @@\n INT A;\n-int b;\n+INT B;\n"
+    )]
+    fn formats_to_mismatch() {
+        let format = ClangFormat::new().runner(UppercaseRunner);
+        assert_formats_to_with(&format, "int a;\nint b;\n", "INT A;\nint b;\n");
+    }
+}
@@ -0,0 +1,248 @@
+// SPDX-FileCopyrightText: 2023 Klarälvdalens Datakonsult AB, a KDAB Group company <info@kdab.com>
+// SPDX-FileContributor: Andrew Hayzen <andrew.hayzen@kdab.com>
+//
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use std::fmt;
+
+use crate::yaml::unquote;
+use crate::{ClangFormatStyle, Language};
+
+/// Describes the version of a clang-format binary
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ClangFormatVersion {
+    /// The major version, eg 16 for 16.0.6
+    pub major: u32,
+    /// The minor version, eg 0 for 16.0.6
+    pub minor: u32,
+    /// The patch version, eg 6 for 16.0.6
+    pub patch: u32,
+}
+
+impl ClangFormatVersion {
+    /// Create a new version from its components
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// Parse the output of `clang-format --version`
+    ///
+    /// This is in the form `clang-format version 16.0.6 (...)`, which may be prefixed by a
+    /// vendor such as `Ubuntu clang-format version 14.0.0-1ubuntu1`.
+    /// Missing minor or patch components are treated as zero.
+    pub(crate) fn parse(output: &str) -> Option<Self> {
+        const NEEDLE: &str = "version ";
+
+        let start = output.find(NEEDLE)? + NEEDLE.len();
+        let version = output[start..]
+            .split(|c: char| !c.is_ascii_digit() && c != '.')
+            .next()?;
+        let mut components = version.split('.').map(str::parse::<u32>);
+        let major = components.next()?.ok()?;
+        let minor = components.next().unwrap_or(Ok(0)).ok()?;
+        let patch = components.next().unwrap_or(Ok(0)).ok()?;
+        Some(Self::new(major, minor, patch))
+    }
+}
+
+impl From<(u32, u32, u32)> for ClangFormatVersion {
+    fn from((major, minor, patch): (u32, u32, u32)) -> Self {
+        Self::new(major, minor, patch)
+    }
+}
+
+impl fmt::Display for ClangFormatVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+impl ClangFormatStyle {
+    /// The first version of clang-format which supports this style, if it is not always supported
+    pub(crate) fn required_version(&self) -> Option<ClangFormatVersion> {
+        match self {
+            Self::GNU => Some(ClangFormatVersion::new(11, 0, 0)),
+            Self::Microsoft => Some(ClangFormatVersion::new(9, 0, 0)),
+            Self::Custom(custom) if custom.trim_start().starts_with("file:") => {
+                Some(ClangFormatVersion::new(14, 0, 0))
+            }
+            Self::Custom(_)
+                if self.key_values().iter().any(|(key, value)| {
+                    key == "BasedOnStyle" && unquote(value) == "InheritParentConfig"
+                }) =>
+            {
+                Some(ClangFormatVersion::new(12, 0, 0))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The major version of clang-format which introduced each style option, sorted by key
+///
+/// Options which have been supported since before clang-format 6 are not listed.
+const KEY_VERSIONS: &[(&str, u32)] = &[
+    ("AlignArrayOfStructures", 13),
+    ("AlignConsecutiveBitFields", 11),
+    ("AlignConsecutiveMacros", 10),
+    ("AlignConsecutiveShortCaseStatements", 17),
+    ("AllowBreakBeforeNoexceptSpecifier", 18),
+    ("AllowShortCaseExpressionOnASingleLine", 19),
+    ("AllowShortCompoundRequirementOnASingleLine", 18),
+    ("AllowShortEnumsOnASingleLine", 11),
+    ("AllowShortLambdasOnASingleLine", 9),
+    ("AttributeMacros", 12),
+    ("BitFieldColonSpacing", 12),
+    ("BracedInitializerIndentation", 17),
+    ("BreakAdjacentStringLiterals", 18),
+    ("BreakAfterAttributes", 16),
+    ("BreakAfterReturnType", 19),
+    ("BreakArrays", 16),
+    ("BreakBeforeConceptDeclarations", 12),
+    ("BreakBeforeInlineASMColon", 16),
+    ("BreakBinaryOperations", 20),
+    ("BreakFunctionDefinitionParameters", 19),
+    ("BreakInheritanceList", 7),
+    ("BreakTemplateDeclarations", 19),
+    ("EmptyLineAfterAccessModifier", 13),
+    ("EmptyLineBeforeAccessModifier", 12),
+    ("IfMacros", 13),
+    ("IncludeBlocks", 6),
+    ("IndentCaseBlocks", 11),
+    ("IndentExternBlock", 11),
+    ("IndentGotoLabels", 10),
+    ("IndentPPDirectives", 6),
+    ("IndentRequiresClause", 15),
+    ("InsertBraces", 15),
+    ("InsertNewlineAtEOF", 16),
+    ("InsertTrailingCommas", 11),
+    ("IntegerLiteralSeparator", 16),
+    ("KeepEmptyLines", 19),
+    ("KeepEmptyLinesAtEOF", 17),
+    ("LambdaBodyIndentation", 13),
+    ("LineEnding", 16),
+    ("Macros", 17),
+    ("PackConstructorInitializers", 14),
+    ("QualifierAlignment", 14),
+    ("ReferenceAlignment", 13),
+    ("RemoveBracesLLVM", 14),
+    ("RemoveParentheses", 17),
+    ("RemoveSemicolon", 16),
+    ("RequiresClausePosition", 15),
+    ("SeparateDefinitionBlocks", 14),
+    ("ShortNamespaceLines", 13),
+    ("SpaceAroundPointerQualifiers", 12),
+    ("SpaceBeforeCaseColon", 12),
+    ("SpaceBeforeCpp11BracedList", 7),
+    ("SpaceBeforeParensOptions", 14),
+    ("SpaceBeforeSquareBrackets", 11),
+    ("SpacesInLineCommentPrefix", 13),
+    ("SpacesInParens", 17),
+    ("StatementAttributeLikeMacros", 12),
+    ("StatementMacros", 8),
+    ("TypeNames", 17),
+    ("TypenameMacros", 9),
+    ("VerilogBreakBetweenInstancePorts", 17),
+    ("WhitespaceSensitiveMacros", 11),
+];
+
+/// The first version of clang-format which supports the style option, if it is not always supported
+pub(crate) fn key_required_version(key: &str) -> Option<ClangFormatVersion> {
+    KEY_VERSIONS
+        .binary_search_by(|(candidate, _)| candidate.cmp(&key))
+        .ok()
+        .map(|index| ClangFormatVersion::new(KEY_VERSIONS[index].1, 0, 0))
+}
+
+impl Language {
+    /// The first version of clang-format which supports this language, if it is not always supported
+    pub(crate) fn required_version(&self) -> Option<ClangFormatVersion> {
+        match self {
+            Self::TextProto => Some(ClangFormatVersion::new(6, 0, 0)),
+            Self::CSharp => Some(ClangFormatVersion::new(9, 0, 0)),
+            Self::Json => Some(ClangFormatVersion::new(14, 0, 0)),
+            Self::Verilog => Some(ClangFormatVersion::new(16, 0, 0)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_version() {
+        assert_eq!(
+            ClangFormatVersion::parse("clang-format version 16.0.6 (Fedora 16.0.6-3.fc38)\n"),
+            Some(ClangFormatVersion::new(16, 0, 6))
+        );
+        assert_eq!(
+            ClangFormatVersion::parse("Ubuntu clang-format version 14.0.0-1ubuntu1.1\n"),
+            Some(ClangFormatVersion::new(14, 0, 0))
+        );
+        assert_eq!(
+            ClangFormatVersion::parse("clang-format version 18\n"),
+            Some(ClangFormatVersion::new(18, 0, 0))
+        );
+        assert_eq!(
+            ClangFormatVersion::parse("clang-format custom build\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn compare_version() {
+        assert!(ClangFormatVersion::new(10, 0, 1) < ClangFormatVersion::new(11, 0, 0));
+        assert!(ClangFormatVersion::new(14, 0, 0) > ClangFormatVersion::new(9, 1, 0));
+        assert_eq!(ClangFormatVersion::new(16, 0, 6).to_string(), "16.0.6");
+        assert_eq!(
+            ClangFormatVersion::from((14, 0, 1)),
+            ClangFormatVersion::new(14, 0, 1)
+        );
+    }
+
+    #[test]
+    fn key_version() {
+        assert!(KEY_VERSIONS.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        assert_eq!(key_required_version("IndentWidth"), None);
+        assert_eq!(
+            key_required_version("InsertBraces"),
+            Some(ClangFormatVersion::new(15, 0, 0))
+        );
+    }
+
+    #[test]
+    fn language_required_version() {
+        assert_eq!(Language::Cpp.required_version(), None);
+        assert_eq!(
+            Language::Json.required_version(),
+            Some(ClangFormatVersion::new(14, 0, 0))
+        );
+    }
+
+    #[test]
+    fn style_required_version() {
+        assert_eq!(
+            ClangFormatStyle::GNU.required_version(),
+            Some(ClangFormatVersion::new(11, 0, 0))
+        );
+        assert_eq!(ClangFormatStyle::Mozilla.required_version(), None);
+        assert_eq!(
+            ClangFormatStyle::inherit_parent_config(ClangFormatStyle::Default).required_version(),
+            Some(ClangFormatVersion::new(12, 0, 0))
+        );
+        assert_eq!(
+            ClangFormatStyle::Custom("{ IndentWidth: 8 }".to_string()).required_version(),
+            None
+        );
+        assert_eq!(
+            ClangFormatStyle::Custom("file:/tmp/style.yaml".to_string()).required_version(),
+            Some(ClangFormatVersion::new(14, 0, 0))
+        );
+    }
+}
@@ -0,0 +1,322 @@
+// SPDX-FileCopyrightText: 2023 Klarälvdalens Datakonsult AB, a KDAB Group company <info@kdab.com>
+// SPDX-FileContributor: Andrew Hayzen <andrew.hayzen@kdab.com>
+//
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! A minimal parser for the subset of block YAML used by `.clang-format` files
+//!
+//! This supports nested mappings, sequences, plain and quoted scalars, and comments,
+//! which is enough for configuration files and the output of `--dump-config`.
+//! Anchors, tags, block scalars, and multiple documents are not supported.
+
+/// A node of a parsed YAML document
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub(crate) enum Node {
+    /// A scalar, kept as its raw text including any quotes
+    Scalar(String),
+    /// A mapping of keys to nodes, in the order they appear
+    Mapping(Vec<(String, Node)>),
+    /// A sequence of nodes
+    Sequence(Vec<Node>),
+}
+
+impl Node {
+    /// Convert the node to YAML flow style, such as `{ IndentWidth: 8 }`
+    pub(crate) fn to_flow(&self) -> String {
+        match self {
+            Self::Scalar(scalar) => scalar.clone(),
+            Self::Mapping(entries) if entries.is_empty() => "{}".to_string(),
+            Self::Mapping(entries) => {
+                let entries: Vec<String> = entries
+                    .iter()
+                    .map(|(key, value)| format!("{key}: {}", value.to_flow()))
+                    .collect();
+                format!("{{ {} }}", entries.join(", "))
+            }
+            Self::Sequence(items) => {
+                let items: Vec<String> = items.iter().map(Node::to_flow).collect();
+                format!("[{}]", items.join(", "))
+            }
+        }
+    }
+}
+
+/// A non-empty line with its indentation and comments removed
+struct Line {
+    number: usize,
+    indent: usize,
+    content: String,
+}
+
+/// Parse a block YAML document into a node
+///
+/// An empty document is parsed as an empty mapping. On failure the error describes
+/// the problem and the 1-based line number it was found on.
+pub(crate) fn parse(input: &str) -> Result<Node, String> {
+    let mut lines = vec![];
+    let mut started = false;
+    for (index, raw) in input.lines().enumerate() {
+        let number = index + 1;
+        let trimmed_end = strip_comment(raw).trim_end();
+        match trimmed_end {
+            "---" if !started => continue,
+            "---" => {
+                return Err(format!(
+                    "line {number}: multiple documents are not supported"
+                ))
+            }
+            "..." => break,
+            "" => continue,
+            _ => {}
+        }
+        if trimmed_end.starts_with('\t') {
+            return Err(format!(
+                "line {number}: tabs are not allowed for indentation"
+            ));
+        }
+
+        started = true;
+        let content = trimmed_end.trim_start();
+        lines.push(Line {
+            number,
+            indent: trimmed_end.len() - content.len(),
+            content: content.to_string(),
+        });
+    }
+
+    if lines.is_empty() {
+        return Ok(Node::Mapping(vec![]));
+    }
+
+    let mut index = 0;
+    let indent = lines[0].indent;
+    let node = parse_node(&mut lines, &mut index, indent)?;
+    match lines.get(index) {
+        Some(line) => Err(format!("line {}: unexpected indentation", line.number)),
+        None => Ok(node),
+    }
+}
+
+/// Parse the mapping or sequence starting at the given line with the given indentation
+fn parse_node(lines: &mut [Line], index: &mut usize, indent: usize) -> Result<Node, String> {
+    if is_sequence_item(&lines[*index].content) {
+        parse_sequence(lines, index, indent)
+    } else {
+        parse_mapping(lines, index, indent)
+    }
+}
+
+fn parse_sequence(lines: &mut [Line], index: &mut usize, indent: usize) -> Result<Node, String> {
+    let mut items = vec![];
+    while *index < lines.len()
+        && lines[*index].indent == indent
+        && is_sequence_item(&lines[*index].content)
+    {
+        let line = &mut lines[*index];
+        let item = line.content[1..].trim_start().to_string();
+        if item.is_empty() {
+            // The item is a nested node on the following lines
+            *index += 1;
+            items.push(parse_child(lines, index, indent)?);
+        } else if split_key_value(&item).is_some() {
+            // The item is a mapping starting on this line, eg `- Regex: '^<'`
+            line.indent += line.content.len() - item.len();
+            line.content = item;
+            let item_indent = line.indent;
+            items.push(parse_mapping(lines, index, item_indent)?);
+        } else {
+            *index += 1;
+            items.push(Node::Scalar(item));
+        }
+    }
+    Ok(Node::Sequence(items))
+}
+
+fn parse_mapping(lines: &mut [Line], index: &mut usize, indent: usize) -> Result<Node, String> {
+    let mut entries = vec![];
+    while *index < lines.len() && lines[*index].indent == indent {
+        let line = &lines[*index];
+        let (key, value) = split_key_value(&line.content)
+            .ok_or_else(|| format!("line {}: expected a key", line.number))?;
+        let (key, value) = (key.to_string(), value.to_string());
+        if value.starts_with('|') || value.starts_with('>') {
+            return Err(format!(
+                "line {}: block scalars are not supported",
+                line.number
+            ));
+        }
+
+        *index += 1;
+        let node = if value.is_empty() {
+            parse_child(lines, index, indent)?
+        } else {
+            Node::Scalar(value)
+        };
+        entries.push((key, node));
+    }
+    Ok(Node::Mapping(entries))
+}
+
+/// Parse the node nested below a key or sequence item with the given indentation
+fn parse_child(lines: &mut [Line], index: &mut usize, indent: usize) -> Result<Node, String> {
+    match lines.get(*index) {
+        Some(line) if line.indent > indent => {
+            let child_indent = line.indent;
+            parse_node(lines, index, child_indent)
+        }
+        // Sequences are allowed at the same indentation as their key
+        Some(line) if line.indent == indent && is_sequence_item(&line.content) => {
+            parse_sequence(lines, index, indent)
+        }
+        _ => Ok(Node::Scalar("null".to_string())),
+    }
+}
+
+fn is_sequence_item(content: &str) -> bool {
+    content == "-" || content.starts_with("- ")
+}
+
+/// Split `key: value` at the first colon outside of quotes followed by a space or the end
+fn split_key_value(content: &str) -> Option<(&str, &str)> {
+    let mut quote: Option<char> = None;
+    for (index, c) in content.char_indices() {
+        match (quote, c) {
+            (Some(open), c) if c == open => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"') if index == 0 => quote = Some(c),
+            (None, '{' | '[') if index == 0 => return None,
+            (None, ':') => {
+                let rest = &content[index + 1..];
+                if rest.is_empty() || rest.starts_with(' ') {
+                    return Some((unquote(content[..index].trim()), rest.trim()));
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Remove a comment from the line, ignoring `#` characters inside quotes
+fn strip_comment(line: &str) -> &str {
+    let mut quote: Option<char> = None;
+    let mut previous = ' ';
+    for (index, c) in line.char_indices() {
+        match (quote, c) {
+            (Some(open), c) if c == open => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"') => quote = Some(c),
+            (None, '#') if previous.is_whitespace() => return &line[..index],
+            _ => {}
+        }
+        previous = c;
+    }
+    line
+}
+
+/// Remove matching single or double quotes around a key
+pub(crate) fn unquote(key: &str) -> &str {
+    for quote in ['\'', '"'] {
+        if let Some(inner) = key
+            .strip_prefix(quote)
+            .and_then(|key| key.strip_suffix(quote))
+        {
+            return inner;
+        }
+    }
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scalar(value: &str) -> Node {
+        Node::Scalar(value.to_string())
+    }
+
+    #[test]
+    fn parse_config() {
+        let input = r#"---
+# Our style
+BasedOnStyle: Mozilla
+IndentWidth: 8 # wider
+BraceWrapping:
+  AfterClass: true
+  AfterEnum:  false
+CommentPragmas: '^ IWYU pragma: #'
+IncludeCategories:
+  - Regex:           '^<'
+    Priority:        2
+  - Regex:           '.*'
+    Priority:        1
+StatementMacros:
+- Q_UNUSED
+- QT_REQUIRE_VERSION
+...
+"#;
+        let node = parse(input).unwrap();
+        assert_eq!(
+            node,
+            Node::Mapping(vec![
+                ("BasedOnStyle".to_string(), scalar("Mozilla")),
+                ("IndentWidth".to_string(), scalar("8")),
+                (
+                    "BraceWrapping".to_string(),
+                    Node::Mapping(vec![
+                        ("AfterClass".to_string(), scalar("true")),
+                        ("AfterEnum".to_string(), scalar("false")),
+                    ])
+                ),
+                ("CommentPragmas".to_string(), scalar("'^ IWYU pragma: #'")),
+                (
+                    "IncludeCategories".to_string(),
+                    Node::Sequence(vec![
+                        Node::Mapping(vec![
+                            ("Regex".to_string(), scalar("'^<'")),
+                            ("Priority".to_string(), scalar("2")),
+                        ]),
+                        Node::Mapping(vec![
+                            ("Regex".to_string(), scalar("'.*'")),
+                            ("Priority".to_string(), scalar("1")),
+                        ]),
+                    ])
+                ),
+                (
+                    "StatementMacros".to_string(),
+                    Node::Sequence(vec![scalar("Q_UNUSED"), scalar("QT_REQUIRE_VERSION")])
+                ),
+            ])
+        );
+        assert_eq!(
+            node.to_flow(),
+            "{ BasedOnStyle: Mozilla, IndentWidth: 8, BraceWrapping: { AfterClass: true, AfterEnum: false }, CommentPragmas: '^ IWYU pragma: #', IncludeCategories: [{ Regex: '^<', Priority: 2 }, { Regex: '.*', Priority: 1 }], StatementMacros: [Q_UNUSED, QT_REQUIRE_VERSION] }"
+        );
+    }
+
+    #[test]
+    fn parse_empty() {
+        assert_eq!(parse("").unwrap(), Node::Mapping(vec![]));
+        assert_eq!(parse("---\n# comment\n").unwrap().to_flow(), "{}");
+    }
+
+    #[test]
+    fn parse_unsupported() {
+        assert_eq!(
+            parse("Language: Cpp\n---\nLanguage: Java\n").unwrap_err(),
+            "line 2: multiple documents are not supported"
+        );
+        assert_eq!(
+            parse("CommentPragmas: |\n  text\n").unwrap_err(),
+            "line 1: block scalars are not supported"
+        );
+        assert_eq!(
+            parse("IndentWidth: 8\n  ColumnLimit: 80\n").unwrap_err(),
+            "line 2: unexpected indentation"
+        );
+        assert_eq!(
+            parse("IndentWidth\n").unwrap_err(),
+            "line 1: expected a key"
+        );
+    }
+}